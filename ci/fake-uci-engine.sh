@@ -0,0 +1,40 @@
+#!/bin/bash
+
+# Fake UCI engine for trying out external engine players.
+# It answers 'go' with the moves in FAKE_ENGINE_MOVES, one after the other,
+# and can misbehave depending on FAKE_ENGINE_MODE:
+# - crash: exit when asked for a move
+# - hang: never answer when asked for a move
+# - illegal: answer with a move that is not possible
+
+MOVES=(${FAKE_ENGINE_MOVES:-e7e5 b8c6 g8f6 f8c5 d7d6})
+MODE=${FAKE_ENGINE_MODE:-normal}
+NEXT=0
+
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name FakeEngine"
+            echo "id author RustChess"
+            echo "uciok"
+            ;;
+        isready)
+            echo "readyok"
+            ;;
+        go*)
+            case "$MODE" in
+                crash) exit 1 ;;
+                hang) ;;
+                illegal) echo "bestmove a1a1" ;;
+                *)
+                    echo "info depth 1 score cp 0 pv ${MOVES[$NEXT]}"
+                    echo "bestmove ${MOVES[$NEXT]}"
+                    NEXT=$(( (NEXT + 1) % ${#MOVES[@]} ))
+                    ;;
+            esac
+            ;;
+        quit)
+            exit 0
+            ;;
+    esac
+done
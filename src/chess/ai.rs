@@ -1,24 +1,289 @@
+//! The AI players: picks moves for the Dumb and the Smart AI, decides about resigning
+//! and draws and explains its moves.
+
 extern crate rand;
 
-use chess::logic::{Figure, Board, Position};
+use std::io;
+use std::time::Duration;
+
+use chess::ChessGame;
+use chess::clock::Bonus;
+use chess::engine::PendingMove;
+use chess::eval::Eval;
+use chess::logic::{format_move, Color, Figure, Board, Position};
 use chess::player::{Player, PlayerType};
+use chess::search::{self, SearchLimits, SearchResult};
+
+use self::rand::Rng;
+
+/// Difficulty levels of the smart AI, from weakest to strongest
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Difficulty {
+    Beginner,
+    Novice,
+    Intermediate,
+    Advanced,
+    Expert,
+    Strongest
+}
+
+impl Difficulty {
+    /// Constructs a difficulty from its name, if there is one with that name
+    pub fn from_name(n: &str) -> Option<Self> {
+        match n {
+            "beginner" => Some(Difficulty::Beginner),
+            "novice" => Some(Difficulty::Novice),
+            "intermediate" => Some(Difficulty::Intermediate),
+            "advanced" => Some(Difficulty::Advanced),
+            "expert" => Some(Difficulty::Expert),
+            "strongest" => Some(Difficulty::Strongest),
+            _ => None
+        }
+    }
+
+    /// Returns the difficulty's name
+    pub fn name(&self) -> String {
+        match *self {
+            Difficulty::Beginner => String::from("beginner"),
+            Difficulty::Novice => String::from("novice"),
+            Difficulty::Intermediate => String::from("intermediate"),
+            Difficulty::Advanced => String::from("advanced"),
+            Difficulty::Expert => String::from("expert"),
+            Difficulty::Strongest => String::from("strongest")
+        }
+    }
+
+    /// Returns the next stronger difficulty, wrapping around to the weakest
+    pub fn next(&self) -> Self {
+        match *self {
+            Difficulty::Beginner => Difficulty::Novice,
+            Difficulty::Novice => Difficulty::Intermediate,
+            Difficulty::Intermediate => Difficulty::Advanced,
+            Difficulty::Advanced => Difficulty::Expert,
+            Difficulty::Expert => Difficulty::Strongest,
+            Difficulty::Strongest => Difficulty::Beginner
+        }
+    }
+
+    /// Returns the deepest search allowed, None if only the player's limits apply
+    pub fn max_depth(&self) -> Option<u8> {
+        match *self {
+            Difficulty::Beginner => Some(1),
+            Difficulty::Novice | Difficulty::Intermediate => Some(2),
+            Difficulty::Advanced => Some(3),
+            Difficulty::Expert => Some(4),
+            Difficulty::Strongest => None
+        }
+    }
+
+    /// Returns how much random noise is added to the scores of the moves
+    pub fn noise(&self) -> i32 {
+        match *self {
+            Difficulty::Beginner => 15,
+            Difficulty::Novice => 10,
+            Difficulty::Intermediate => 5,
+            Difficulty::Advanced => 2,
+            Difficulty::Expert | Difficulty::Strongest => 0
+        }
+    }
+
+    /// Returns the probability of deliberately playing a move that is not the best
+    pub fn blunder_chance(&self) -> f64 {
+        match *self {
+            Difficulty::Beginner => 0.3,
+            Difficulty::Novice => 0.15,
+            Difficulty::Intermediate => 0.05,
+            Difficulty::Advanced => 0.02,
+            Difficulty::Expert | Difficulty::Strongest => 0.0
+        }
+    }
+}
+
+impl ::std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Number of evaluation terms an explanation names
+const EXPLAINED_TERMS: usize = 3;
+
+/// Why the AI chose a move: what its search found and which evaluation terms
+/// matter most in the position at the end of the principal variation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// Depth the search completed
+    pub depth: u8,
+    /// Score of the move in tenths of a pawn from the view of the moving player
+    pub score: i32,
+    /// Moves until mate if the search found one, negative if the moving player gets mated
+    pub mate_in: Option<i32>,
+    /// Number of searched positions
+    pub nodes: u64,
+    /// Principal variation in long algebraic notation
+    pub pv: Vec<String>,
+    /// Largest terms from the view of the moving player, in tenths of a pawn
+    pub terms: Vec<(&'static str, i32)>
+}
+
+impl Explanation {
+    /// Explains the search result for the game's position, 'eval' is the evaluation the search used
+    pub fn new(game: &ChessGame, result: &SearchResult, eval: &Eval) -> Self {
+        let mut end = game.clone();
+        let mut pv = Vec::new();
+        for &(from, to) in result.pv.iter() {
+            let text = format_move(&end.board, from, to);
+            if !end.play(from, to) {
+                break
+            }
+            pv.push(text);
+        }
 
-use self::rand::{thread_rng, Rng};
+        let sign = if game.turn { 1 } else { -1 };
+        let mut terms: Vec<(&'static str, i32)> = eval.contributions(&end)
+                                                      .into_iter()
+                                                      .map(|(name, value)| (name, value * sign))
+                                                      .filter(|&(_, value)| value != 0)
+                                                      .collect();
+        terms.sort_by_key(|&(_, value)| -value.abs());
+        terms.truncate(EXPLAINED_TERMS);
 
-/// Returns a move for the AI, depending on which one it is
-pub fn get_move(board: &Board, me: &Player, other: &Player) -> (Position, Position) {
+        Explanation{ depth: result.depth,
+                     score: result.score,
+                     mate_in: result.mate_in(),
+                     nodes: result.nodes,
+                     pv: pv,
+                     terms: terms }
+    }
+}
+
+impl ::std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        // Scores are in tenths of a pawn
+        let score = match self.mate_in {
+            Some(n) => format!("mate in {}", n),
+            None => format!("{:+.1}", self.score as f32 / 10.0)
+        };
+        try!(write!(f, "depth {}, score {}, nodes {}, pv {}", self.depth, score, self.nodes, self.pv.join(" ")));
+
+        let terms: Vec<String> = self.terms.iter().map(|&(name, value)| format!("{} {:+.1}", name, value as f32 / 10.0)).collect();
+        if !terms.is_empty() {
+            try!(write!(f, "; {}", terms.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+/// Returns a move for the AI whose turn it is, depending on which one it is
+pub fn get_move(game: &ChessGame) -> (Position, Position) {
+    get_move_and_search(game).0
+}
+
+/// Returns a move for the AI whose turn it is and the result of the search
+/// that found it, if it was found by a search
+pub fn get_move_and_search(game: &ChessGame) -> ((Position, Position), Option<SearchResult>) {
+    if !game.variant().hides_figures() {
+        return choose_move(game)
+    }
+
+    // In variants that hide figures the AI only knows what its own figures see. A figure it
+    // only remembers can make a move look possible that is not, like a pawn taking it. The
+    // field is known to be empty then, so the AI thinks again without the figure there.
+    let legal = game.clone().possible_moves();
+    let mut seen = game.seen_by(game.turn_color());
+    loop {
+        let (m, result) = choose_move(&seen);
+        if legal.contains(&m) {
+            return (m, result)
+        }
+        let ghost = match (seen.board.get_figure(m.1), seen.board.get_figure_color(m.1)) {
+            (Some(fig), Some(color)) if color != game.turn_color() => fig,
+            // Should not happen, but a legal move is better than none
+            _ => return (legal[game.rng().gen_range(0, legal.len())], None)
+        };
+        let opponent = if game.turn { &mut seen.black_player } else { &mut seen.white_player };
+        opponent.forget_promotion(m.1);
+        opponent.capture(ghost.name(), m.1);
+        seen.board[m.1].set_empty(m.1);
+    }
+}
+
+/// Returns a move and its search for the AI whose turn it is in 'game', which is all the AI knows
+fn choose_move(game: &ChessGame) -> ((Position, Position), Option<SearchResult>) {
+    let (me, other) = if game.turn {
+        (&game.white_player, &game.black_player)
+    } else {
+        (&game.black_player, &game.white_player)
+    };
+
+    // Books, tablebases and engines only know standard chess
+    let standard = game.variant().is_standard();
+
+    // An external engine decides on its own, if it fails the smart AI takes over. Callers that
+    // want to know why it failed ask it with 'get_engine_move' themselves.
+    if me.ptype() == PlayerType::Engine && standard {
+        if let Some(Ok(m)) = get_engine_move(game) {
+            return (m, None)
+        }
+        return get_smart_move(game, me)
+    }
+
+    // Early in the game, play from the opening book if it knows the position
+    if let Some(book) = me.book().filter(|_| standard) {
+        if game.history.len() < me.book_depth() {
+            if let Some(m) = book.pick(game, &mut game.rng()) {
+                return (m, None)
+            }
+        }
+    }
+
+    // With few enough figures left play perfectly from the endgame tablebase
+    if let Some(tb) = me.tablebase().filter(|_| standard) {
+        if let Some(m) = tb.best_move(game) {
+            return (m, None)
+        }
+    }
 
     // If AI is stupid
-    if me.ptype() != PlayerType::Smart {
-        get_dumb_move(&mut board.clone(), &mut me.clone(), &mut other.clone())
+    if me.ptype() == PlayerType::Dumb {
+        let moves = game.clone().possible_moves();
+        (get_dumb_move(&mut game.board.clone(), &mut me.clone(), &mut other.clone(), moves, &mut game.rng()), None)
     // If AI is smart
     } else {
-        get_smart_move(board.clone(), me.clone(), other.clone())
+        get_smart_move(game, me)
     }
 }
 
+/// Asks the external engine of the player whose turn it is for its move and waits for it,
+/// None if that player has no engine or the variant is not standard chess
+pub fn get_engine_move(game: &ChessGame) -> Option<io::Result<(Position, Position)>> {
+    let me = game.active_player();
+    if me.ptype() != PlayerType::Engine || !game.variant().is_standard() {
+        return None
+    }
+    me.engine().map(|engine| match engine.lock() {
+        Ok(mut engine) => engine.best_move(game, &move_limits(game, me)),
+        Err(_) => Err(io::Error::new(io::ErrorKind::Other, "engine is unusable"))
+    })
+}
+
+/// Asks the external engine of the player whose turn it is for its move in another thread,
+/// None if that player has no engine or the variant is not standard chess
+pub fn start_engine_move(game: &ChessGame) -> Option<PendingMove> {
+    let me = game.active_player();
+    if me.ptype() != PlayerType::Engine || !game.variant().is_standard() {
+        return None
+    }
+    me.engine().map(|engine| PendingMove::start(engine, game, move_limits(game, me)))
+}
+
+/// Returns the smart AI's move for the player whose turn it is, e.g. when its engine failed
+pub fn get_smart_move_and_search(game: &ChessGame) -> ((Position, Position), Option<SearchResult>) {
+    get_smart_move(game, game.active_player())
+}
+
 /// Returns the measure of a figure's value
-fn figure_value(fig: &Figure) -> i32 {
+pub fn figure_value(fig: &Figure) -> i32 {
     match *fig {
         Figure::King => 500,
         Figure::Queen => 100,
@@ -28,17 +293,26 @@ fn figure_value(fig: &Figure) -> i32 {
     }
 }
 
+/// Puts moves in the order of their fields, so which one a seed picks does not depend
+/// on the order the moves were generated in
+fn sort_moves(moves: &mut Vec<(Position, Position)>) {
+    moves.sort_by_key(|m| (m.0.y, m.0.x, m.1.y, m.1.x));
+}
+
 /// Returns a random move in 'moves'
-fn random_move(moves: &Vec<(Position, Position)>) -> (Position, Position) {
-    let mut rng = thread_rng();
+fn random_move<R: Rng>(moves: &Vec<(Position, Position)>, rng: &mut R) -> (Position, Position) {
+    let mut moves = moves.clone();
+    sort_moves(&mut moves);
     let index = rng.gen_range(0, moves.len());
 
     moves[index]
 }
 
-/// Returns a dumb move
-fn get_dumb_move(board: &mut Board, me: &mut Player, other: &mut Player) -> (Position, Position) {
-    let my_moves = me.get_possible_moves(board, other);
+/// Returns a dumb move out of 'moves'
+fn get_dumb_move<R: Rng>(board: &mut Board, me: &mut Player, other: &mut Player, moves: Vec<(Position, Position)>,
+                         rng: &mut R) -> (Position, Position) {
+    let mut my_moves = moves;
+    sort_moves(&mut my_moves);
 
     let move_values: Vec<(i32, (Position, Position))> = my_moves.iter()
                                                                 .map(|x| (capture_and_evade(board, x, me, other), *x))
@@ -46,7 +320,7 @@ fn get_dumb_move(board: &mut Board, me: &mut Player, other: &mut Player) -> (Pos
 
     if let Some(at) = move_values.iter().max_by_key(|x| x.0) {
         if at.0 == 0 {
-            return random_move(&my_moves)
+            return random_move(&my_moves, rng)
         } else {
             return at.1
         }
@@ -58,8 +332,13 @@ fn get_dumb_move(board: &mut Board, me: &mut Player, other: &mut Player) -> (Pos
 }
 
 
-/// Return a measure that tries to capture opponent figures and evade being captured
+/// Return a measure that tries to capture opponent figures and evade being captured,
+/// weighed by the personality of the active player
 fn capture_and_evade(board: &mut Board, pos: &(Position, Position), active: &mut Player, inactive: &mut Player) -> i32 {
+    // Dropped figures neither capture nor flee
+    if pos.0.reserve_figure().is_some() {
+        return 0
+    }
     let capture = {
         if board.is_capture_move(pos.0, pos.1) {
             figure_value(&board.get_figure(pos.1).unwrap())
@@ -76,13 +355,225 @@ fn capture_and_evade(board: &mut Board, pos: &(Position, Position), active: &mut
         }
     };
 
-    capture + evade
+    let personality = active.personality();
+    capture * personality.capture / 100 + evade * personality.evade / 100
+}
+
+/// Tenths of a pawn the smart AI must be behind to resign
+const RESIGN_SCORE: i32 = 120;
+
+/// Plies into the game before the smart AI offers draws and plies between its offers
+const DRAW_OFFER_PLIES: usize = 60;
+const DRAW_OFFER_INTERVAL: usize = 20;
+
+/// How close to equal in tenths of a pawn a position must be for the smart AI to offer a draw
+const DRAW_OFFER_WINDOW: i32 = 2;
+
+/// Depth of the search that decides about a draw offer
+const DRAW_ANSWER_DEPTH: u8 = 4;
+
+/// Returns whether the AI gives up after its search found 'result'
+pub fn wants_to_resign(result: &SearchResult) -> bool {
+    result.mate_in().map_or(false, |n| n < 0) || result.score <= -RESIGN_SCORE
+}
+
+/// Returns whether the AI would rather have a draw than a position with 'score' for it,
+/// which depends on the contempt of its personality
+pub fn prefers_draw(me: &Player, score: i32) -> bool {
+    score < -me.personality().contempt
+}
+
+/// Returns whether the AI whose turn it is accepts the opponent's draw offer
+pub fn accepts_draw(game: &ChessGame) -> bool {
+    // The AI judges the position by what it knows, in variants that hide figures only by what it sees
+    let game = &game.seen_by(game.turn_color());
+    let me = game.active_player();
+    let score = match me.ptype() {
+        PlayerType::Smart | PlayerType::Engine => {
+            let limits = SearchLimits{ depth: Some(DRAW_ANSWER_DEPTH), .. me.search_limits() };
+            search::run(game, &limits, 0, &mut game.rng(), &me.search_context(), |_| ()).score
+        },
+        _ => me.search_eval().evaluate(game)
+    };
+    prefers_draw(me, score)
+}
+
+/// Returns whether the AI that just moved offers a draw, 'result' is the search that found its move
+pub fn offers_draw(game: &ChessGame, result: &SearchResult) -> bool {
+    let (me, color) = if game.turn { (&game.black_player, Color::Black) } else { (&game.white_player, Color::White) };
+    let recent = game.draw_offers.iter().any(|&(c, ply)| c == color && ply + DRAW_OFFER_INTERVAL > game.history.len());
+
+    game.history.len() >= DRAW_OFFER_PLIES && !recent && result.score.abs() <= DRAW_OFFER_WINDOW &&
+    result.score <= -me.personality().contempt
+}
+
+/// Returns the player's search limits, the time for the move comes from the clock if the game has one
+fn move_limits(game: &ChessGame, me: &Player) -> SearchLimits {
+    let mut limits = me.search_limits();
+    if let Some(ref clock) = game.clock {
+        let bonus = match clock.bonus() {
+            Bonus::None => Duration::from_millis(0),
+            Bonus::Increment(d) | Bonus::Delay(d) => d
+        };
+        limits.movetime = SearchLimits::from_clock(clock.remaining(game.turn), bonus, clock.moves_to_go(game.turn)).movetime;
+    }
+    limits
+}
+
+/// Chooses a smart AI move by searching within the player's limits and difficulty,
+/// also returns the result of the search
+fn get_smart_move(game: &ChessGame, me: &Player) -> ((Position, Position), Option<SearchResult>) {
+    let context = me.search_context();
+    let result = smart_search(game, me.difficulty(), move_limits(game, me), &context, |_| ());
+
+    match result.best {
+        Some(best) => (best, Some(result)),
+        // Same as for the dumb AI, we should never be asked to move without a valid move
+        None => unreachable!()
+    }
+}
+
+/// Returns the 'count' best moves for the player whose turn it is with their principal
+/// variations and scores, best first, searching as the strongest smart AI within 'limits'
+pub fn analyse(game: &ChessGame, count: usize, limits: &SearchLimits) -> Vec<search::SearchResult> {
+    search::analyse(game, limits, count, 0, &mut game.rng(), &search::SearchContext::new(), |_| ())
 }
 
-/// Chooses a smart AI move
-#[allow(dead_code, unused_variables)]
-fn get_smart_move(board: Board, me: Player, other: Player) -> (Position, Position) {
-    // TODO: implement this (minimax?)
+/// Searches the game like the smart AI of the given difficulty does, 'report'
+/// is called after every finished iteration of the search
+pub fn smart_search<F>(game: &ChessGame, difficulty: Difficulty, mut limits: SearchLimits,
+                       context: &search::SearchContext, report: F) -> search::SearchResult
+    where F: FnMut(&search::SearchResult)
+{
+    if let Some(max) = difficulty.max_depth() {
+        limits.depth = Some(limits.depth.map_or(max, |d| d.min(max)));
+    }
+
+    let mut rng = game.rng();
+    let mut result = search::run(game, &limits, difficulty.noise(), &mut rng, context, report);
 
-    (Position::new(1, 1), Position::new(1, 2))
+    // Deliberately play another move every now and then
+    if let Some(best) = result.best {
+        if rng.gen::<f64>() < difficulty.blunder_chance() {
+            let others: Vec<(Position, Position)> = game.clone()
+                                                        .possible_moves()
+                                                        .into_iter()
+                                                        .filter(|&m| m != best)
+                                                        .collect();
+            if !others.is_empty() {
+                let other = random_move(&others, &mut rng);
+                result.best = Some(other);
+                result.pv = vec![other];
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::search::SearchContext;
+    use chess::{variant, START_FEN};
+
+    const ALL: [Difficulty; 6] = [Difficulty::Beginner, Difficulty::Novice, Difficulty::Intermediate,
+                                  Difficulty::Advanced, Difficulty::Expert, Difficulty::Strongest];
+
+    #[test]
+    fn difficulty_names() {
+        for d in ALL.iter() {
+            assert_eq!(Difficulty::from_name(&d.name()), Some(*d));
+        }
+        assert_eq!(Difficulty::from_name("grandmaster"), None);
+
+        let mut d = Difficulty::Beginner;
+        for next in ALL.iter().skip(1).chain(ALL.iter().take(1)) {
+            d = d.next();
+            assert_eq!(d, *next);
+        }
+    }
+
+    #[test]
+    fn weaker_difficulties_search_less() {
+        let game = ChessGame::new();
+        for d in ALL.iter() {
+            let result = smart_search(&game, *d, SearchLimits::depth(4), &SearchContext::new(), |_| ());
+            assert!(result.depth <= d.max_depth().unwrap_or(4), "{} searched too deep", d);
+            assert!(game.clone().possible_moves().contains(&result.best.unwrap()));
+        }
+    }
+
+    #[test]
+    fn explains_moves() {
+        let game = ChessGame::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let a1 = Position::from_algebraic("a1").unwrap();
+        let a8 = Position::from_algebraic("a8").unwrap();
+        let result = SearchResult{ best: Some((a1, a8)), score: 45, depth: 3, nodes: 1200,
+                                   time: Duration::from_millis(5), pv: vec![(a1, a8)] };
+        let explanation = Explanation::new(&game, &result, &Eval::default());
+        assert_eq!(explanation.pv, vec!["a1a8"]);
+        assert_eq!(explanation.terms, vec![("rook", 50), ("pawn", -30)]);
+        assert_eq!(explanation.to_string(), "depth 3, score +4.5, nodes 1200, pv a1a8; rook +5.0, pawn -3.0");
+
+        // Moves after an impossible one are left out
+        let result = SearchResult{ pv: vec![(a1, a8), (a1, a8)], .. search::search(&game, &SearchLimits::depth(2)) };
+        let explanation = Explanation::new(&game, &result, &Eval::default());
+        assert_eq!(explanation.pv, vec!["a1a8"]);
+        assert_eq!(explanation.mate_in, Some(1));
+        assert!(explanation.to_string().contains(", score mate in 1, "));
+    }
+
+    /// Lets two AIs of 'ptype' play 'plies' moves with the seed
+    fn play_seeded(seed: u64, ptype: PlayerType, plies: usize) -> Vec<(Position, Position)> {
+        let mut game = ChessGame::new();
+        game.seed = seed;
+        for player in [&mut game.white_player, &mut game.black_player].iter_mut() {
+            player.set_ptype(ptype);
+            player.set_difficulty(Difficulty::Beginner);
+        }
+        for _ in 0..plies {
+            game.do_ai_turn();
+        }
+        game.history
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        for &ptype in [PlayerType::Dumb, PlayerType::Smart].iter() {
+            assert_eq!(play_seeded(42, ptype, 12), play_seeded(42, ptype, 12));
+        }
+        let games: Vec<_> = (0..4).map(|seed| play_seeded(seed, PlayerType::Dumb, 12)).collect();
+        assert!(games.iter().any(|g| *g != games[0]));
+    }
+
+    #[test]
+    fn strongest_finds_mate() {
+        let game = ChessGame::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = smart_search(&game, Difficulty::Strongest, SearchLimits::depth(3), &SearchContext::new(), |_| ());
+        assert_eq!(result.best, Some((Position::from_algebraic("a1").unwrap(), Position::from_algebraic("a8").unwrap())));
+    }
+
+    // The knight was last seen on d5, where the pawn on e4 could take it if it still stood there
+    #[test]
+    fn dark_chess_moves_are_legal() {
+        let pos = |s: &str| Position::from_algebraic(s).unwrap();
+        let mut game = ChessGame::from_fen_variant(START_FEN, variant::find("darkchess").unwrap()).unwrap();
+        for &(from, to) in [("e2", "e4"), ("g8", "f6"), ("a2", "a3"), ("f6", "d5"), ("a3", "a4"), ("d5", "b6")].iter() {
+            assert!(game.play(pos(from), pos(to)));
+        }
+        assert_eq!(game.seen_by(Color::White).board.get_figure(pos("d5")), Some(Figure::Knight));
+
+        let legal = game.clone().possible_moves();
+        assert!(!legal.contains(&(pos("e4"), pos("d5"))));
+        for &ptype in [PlayerType::Dumb, PlayerType::Smart].iter() {
+            for seed in 0..2 {
+                let mut game = game.clone();
+                game.seed = seed;
+                game.white_player.set_ptype(ptype);
+                let (m, _) = game.do_ai_turn().unwrap();
+                assert!(legal.contains(&m), "{:?} AI played {:?} with seed {}", ptype, m, seed);
+            }
+        }
+    }
 }
@@ -0,0 +1,342 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::time::{Duration, Instant};
+
+/// Part of a time control: 'time' for 'moves' moves, or for the rest of the game if
+/// 'moves' is None
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Period {
+    pub moves: Option<u32>,
+    pub time: Duration
+}
+
+/// What a player gets back for each move
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bonus {
+    None,
+    // Fischer: a fixed amount is added after every move
+    Increment(Duration),
+    // Bronstein: the time used for the move is given back, up to the delay
+    Delay(Duration)
+}
+
+/// Time control of a game: one or more periods and a bonus for every move.
+/// After the last period ends it starts again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeControl {
+    pub periods: Vec<Period>,
+    pub bonus: Bonus
+}
+
+impl TimeControl {
+    /// The whole game in 'time'
+    pub fn sudden_death(time: Duration) -> Self {
+        TimeControl{ periods: vec![Period{ moves: None, time: time }], bonus: Bonus::None }
+    }
+
+    /// The whole game in 'time', with 'increment' added after every move
+    pub fn fischer(time: Duration, increment: Duration) -> Self {
+        TimeControl{ bonus: Bonus::Increment(increment), .. TimeControl::sudden_death(time) }
+    }
+
+    /// The whole game in 'time', each move's time is given back up to 'delay'
+    pub fn bronstein(time: Duration, delay: Duration) -> Self {
+        TimeControl{ bonus: Bonus::Delay(delay), .. TimeControl::sudden_death(time) }
+    }
+
+    /// Parses periods in seconds as in the PGN TimeControl tag, separated by ':', each either
+    /// 'moves/seconds' or 'seconds', followed by '+seconds' for a Fischer increment or
+    /// 'd' and seconds for a Bronstein delay. E.g. '40/5400:1800+30' or '300d2'.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let seconds = |s: &str| s.parse::<f64>().ok()
+                                 .filter(|&v| v >= 0.0)
+                                 .map(|v| Duration::from_millis((v * 1000.0) as u64))
+                                 .ok_or(format!("invalid time '{}' in '{}'", s, text));
+
+        let (periods, bonus) = if let Some(i) = text.find('+') {
+            (&text[..i], Bonus::Increment(try!(seconds(&text[i + 1..]))))
+        } else if let Some(i) = text.find('d') {
+            (&text[..i], Bonus::Delay(try!(seconds(&text[i + 1..]))))
+        } else {
+            (text, Bonus::None)
+        };
+
+        let mut control = TimeControl{ periods: Vec::new(), bonus: bonus };
+        for period in periods.split(':') {
+            let mut parts = period.splitn(2, '/');
+            let (first, second) = (parts.next().unwrap_or(""), parts.next());
+            control.periods.push(match second {
+                Some(time) => {
+                    let moves = try!(first.parse::<u32>().ok().filter(|&m| m > 0)
+                                          .ok_or(format!("invalid number of moves '{}' in '{}'", first, text)));
+                    Period{ moves: Some(moves), time: try!(seconds(time)) }
+                },
+                None => Period{ moves: None, time: try!(seconds(first)) }
+            });
+        }
+        Ok(control)
+    }
+
+    /// Returns the period a player is in after 'index' periods, the last one repeats
+    fn period(&self, index: usize) -> Period {
+        self.periods[index.min(self.periods.len() - 1)]
+    }
+}
+
+/// Formats a duration in seconds, with a fraction only if it has one
+fn seconds(time: Duration) -> String {
+    let millis = time.subsec_nanos() / 1000000;
+    if millis == 0 {
+        time.as_secs().to_string()
+    } else {
+        format!("{}.{:03}", time.as_secs(), millis).trim_end_matches('0').to_string()
+    }
+}
+
+impl ::std::fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let periods: Vec<String> = self.periods.iter()
+                                               .map(|p| match p.moves {
+                                                   Some(m) => format!("{}/{}", m, seconds(p.time)),
+                                                   None => seconds(p.time)
+                                               })
+                                               .collect();
+        try!(write!(f, "{}", periods.join(":")));
+        match self.bonus {
+            Bonus::None => Ok(()),
+            Bonus::Increment(d) => write!(f, "+{}", seconds(d)),
+            Bonus::Delay(d) => write!(f, "d{}", seconds(d))
+        }
+    }
+}
+
+/// Clocks of both players, index 0 is white
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    // Moves made in the current period and index of that period
+    moves: [u32; 2],
+    period: [usize; 2],
+    // Whose clock runs and since when
+    running: Option<(usize, Instant)>
+}
+
+impl Clock {
+    /// Creates stopped clocks with the time of the first period for both players
+    pub fn new(control: TimeControl) -> Self {
+        let time = control.period(0).time;
+        Clock{ control: control, remaining: [time; 2], moves: [0; 2], period: [0; 2], running: None }
+    }
+
+    /// Returns the time control
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /// Starts the clock of the white ('white' is true) or black player, stopping the other one
+    pub fn start(&mut self, white: bool) {
+        self.stop();
+        self.running = Some((if white { 0 } else { 1 }, Instant::now()));
+    }
+
+    /// Stops the running clock without giving a bonus
+    pub fn stop(&mut self) {
+        if let Some((side, since)) = self.running.take() {
+            self.remaining[side] = self.remaining[side].checked_sub(since.elapsed()).unwrap_or(Duration::from_millis(0));
+        }
+    }
+
+    /// Returns whether a clock runs
+    pub fn running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Returns the time left for the white ('white' is true) or black player
+    pub fn remaining(&self, white: bool) -> Duration {
+        let side = if white { 0 } else { 1 };
+        match self.running {
+            Some((s, since)) if s == side => self.remaining[side].checked_sub(since.elapsed()).unwrap_or(Duration::from_millis(0)),
+            _ => self.remaining[side]
+        }
+    }
+
+    /// Returns whether the player has no time left
+    pub fn flagged(&self, white: bool) -> bool {
+        self.remaining(white) == Duration::from_millis(0)
+    }
+
+    /// Returns the bonus the player gets for each move
+    pub fn bonus(&self) -> Bonus {
+        self.control.bonus
+    }
+
+    /// Returns how many moves the player has to make until the current period ends
+    pub fn moves_to_go(&self, white: bool) -> Option<u32> {
+        let side = if white { 0 } else { 1 };
+        self.control.period(self.period[side]).moves.map(|m| m - self.moves[side])
+    }
+
+    /// The player whose clock runs finished a move: the bonus is applied, the next
+    /// period is added if this one is over, and the opponent's clock starts
+    pub fn press(&mut self) {
+        let (side, since) = match self.running {
+            Some(r) => r,
+            None => return
+        };
+        let used = since.elapsed();
+        self.stop();
+        if self.remaining[side] == Duration::from_millis(0) {
+            return
+        }
+
+        match self.control.bonus {
+            Bonus::None => (),
+            Bonus::Increment(inc) => self.remaining[side] += inc,
+            Bonus::Delay(delay) => self.remaining[side] += used.min(delay)
+        }
+
+        self.moves[side] += 1;
+        if self.control.period(self.period[side]).moves == Some(self.moves[side]) {
+            self.moves[side] = 0;
+            self.period[side] += 1;
+            self.remaining[side] += self.control.period(self.period[side]).time;
+        }
+
+        self.start(side == 1);
+    }
+
+    /// A move of the white ('white' is true) or black player was taken back,
+    /// their clock runs again. Time already used is not given back.
+    pub fn take_back(&mut self, white: bool) {
+        let side = if white { 0 } else { 1 };
+        self.moves[side] = self.moves[side].saturating_sub(1);
+        self.start(white);
+    }
+}
+
+/// Formats a clock time as 'h:mm:ss', 'm:ss' or, in the last ten seconds, 's.t'
+pub fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else if secs >= 10 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("{}.{}", secs, time.subsec_nanos() / 100000000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn parse_and_format() {
+        for text in &["300", "300+2", "300d2", "40/5400:1800+30", "2/60:30", "0.5+0.25"] {
+            let control = TimeControl::parse(text).unwrap();
+            assert_eq!(control.to_string(), *text);
+        }
+        assert_eq!(TimeControl::parse("300+2").unwrap(), TimeControl::fischer(secs(300), secs(2)));
+        assert_eq!(TimeControl::parse("300d2").unwrap(), TimeControl::bronstein(secs(300), secs(2)));
+        assert!(TimeControl::parse("abc").is_err());
+        assert!(TimeControl::parse("0/60").is_err());
+        assert!(TimeControl::parse("60+-1").is_err());
+    }
+
+    // A press right after the start uses almost no time: the increment is added in
+    // full and the delay gives back what was used
+    #[test]
+    fn increment_and_delay() {
+        let mut clock = Clock::new(TimeControl::fischer(secs(60), secs(5)));
+        clock.start(true);
+        clock.press();
+        assert!(clock.remaining(true) > secs(64));
+        assert!(clock.remaining(true) <= secs(65));
+        // Black's clock runs now
+        assert!(clock.remaining(false) <= secs(60));
+        assert!(clock.remaining(false) > secs(59));
+
+        let mut clock = Clock::new(TimeControl::bronstein(secs(60), secs(5)));
+        clock.start(true);
+        clock.press();
+        assert!(clock.remaining(true) <= secs(60));
+        assert!(clock.remaining(true) > secs(59));
+    }
+
+    #[test]
+    fn press_switches_sides() {
+        let mut clock = Clock::new(TimeControl::sudden_death(secs(60)));
+        assert!(!clock.running());
+        clock.press();
+        assert!(!clock.running());
+
+        clock.start(true);
+        clock.press();
+        assert!(clock.running());
+        clock.stop();
+        assert!(!clock.running());
+        assert!(clock.remaining(false) < secs(60));
+    }
+
+    #[test]
+    fn periods_and_moves_to_go() {
+        let mut clock = Clock::new(TimeControl::parse("2/60:30").unwrap());
+        assert_eq!(clock.moves_to_go(true), Some(2));
+        clock.start(true);
+        clock.press();
+        assert_eq!(clock.moves_to_go(true), Some(1));
+        assert_eq!(clock.moves_to_go(false), Some(2));
+        clock.press();
+        clock.press();
+        // The second period is added after the second move and lasts the rest of the game
+        assert_eq!(clock.moves_to_go(true), None);
+        assert!(clock.remaining(true) > secs(89));
+
+        clock.take_back(true);
+        assert!(clock.running());
+        assert_eq!(clock.moves_to_go(true), None);
+    }
+
+    #[test]
+    fn flag_fall() {
+        let mut clock = Clock::new(TimeControl::fischer(secs(0), secs(5)));
+        assert!(clock.flagged(true));
+        clock.start(true);
+        clock.press();
+        // No bonus after the flag fell
+        assert!(clock.flagged(true));
+        assert!(clock.flagged(false));
+    }
+
+    #[test]
+    fn time_format() {
+        assert_eq!(format_time(secs(3725)), "1:02:05");
+        assert_eq!(format_time(secs(65)), "1:05");
+        assert_eq!(format_time(Duration::from_millis(9450)), "9.4");
+    }
+}
@@ -0,0 +1,426 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chess::ChessGame;
+use chess::logic::{parse_move, format_move, Position};
+use chess::search::SearchLimits;
+
+/// Time the engine gets to answer 'uci' and 'isready'
+const HANDSHAKE_TIMEOUT: u64 = 5000;
+
+/// Time the engine gets beyond its search time to send 'bestmove'
+const MOVE_GRACE: u64 = 2000;
+
+/// Time the engine gets for a move if its limits have no time
+const MOVE_TIMEOUT: u64 = 30000;
+
+/// A running engine process
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    // Lines the engine wrote, read by a separate thread so we can wait with a timeout
+    lines: Receiver<String>
+}
+
+/// An external engine speaking the Universal Chess Interface, which is started from a path.
+/// If the engine crashes or does not answer in time it is killed and started again for the next move.
+#[derive(Debug)]
+pub struct ExternalEngine {
+    path: String,
+    name: String,
+    process: Option<Process>,
+    // Time the engine gets beyond its search time to send 'bestmove'
+    grace: Duration
+}
+
+impl ::std::fmt::Debug for Process {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "Process {{ id: {} }}", self.child.id())
+    }
+}
+
+impl ExternalEngine {
+    /// Starts the engine at 'path' and waits until it is ready
+    pub fn start(path: &str) -> io::Result<Self> {
+        let mut engine = ExternalEngine{ path: String::from(path),
+                                         name: String::from(path),
+                                         process: None,
+                                         grace: Duration::from_millis(MOVE_GRACE) };
+        try!(engine.ensure_running());
+        Ok(engine)
+    }
+
+    /// Returns the engine's name as it reported it
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Sets the time the engine gets beyond its search time to send 'bestmove', and again after
+    /// it was told to stop, before it is given up on
+    pub fn set_grace(&mut self, grace: Duration) {
+        self.grace = grace;
+    }
+
+    /// Starts the process if it is not running and does the handshake
+    fn ensure_running(&mut self) -> io::Result<()> {
+        if self.process.is_some() {
+            return Ok(())
+        }
+
+        let mut child = try!(Command::new(&self.path)
+                                     .stdin(Stdio::piped())
+                                     .stdout(Stdio::piped())
+                                     .stderr(Stdio::null())
+                                     .spawn());
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() {
+                        break
+                    },
+                    Err(_) => break
+                }
+            }
+        });
+        self.process = Some(Process{ child: child, stdin: stdin, lines: receiver });
+
+        match self.handshake() {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                self.kill();
+                Err(e)
+            }
+        }
+    }
+
+    /// Tells the engine to use UCI, remembers its name and waits until it is ready
+    fn handshake(&mut self) -> io::Result<()> {
+        let timeout = Duration::from_millis(HANDSHAKE_TIMEOUT);
+
+        try!(self.send("uci"));
+        for line in try!(self.wait_for("uciok", timeout)) {
+            if line.starts_with("id name ") {
+                self.name = String::from(line[8..].trim());
+            }
+        }
+        try!(self.send("isready"));
+        try!(self.wait_for("readyok", timeout));
+        Ok(())
+    }
+
+    /// Sends a command to the engine
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        match self.process {
+            Some(ref mut p) => writeln!(p.stdin, "{}", command).and_then(|_| p.stdin.flush()),
+            None => Err(io::Error::new(io::ErrorKind::NotConnected, "engine is not running"))
+        }
+    }
+
+    /// Reads lines until one starts with 'token' and returns all lines read
+    fn wait_for(&mut self, token: &str, timeout: Duration) -> io::Result<Vec<String>> {
+        let start = Instant::now();
+        let mut lines = Vec::new();
+
+        loop {
+            let left = timeout.checked_sub(start.elapsed()).unwrap_or(Duration::from_millis(0));
+            let line = match self.process {
+                Some(ref p) => p.lines.recv_timeout(left),
+                None => return Err(io::Error::new(io::ErrorKind::NotConnected, "engine is not running"))
+            };
+
+            match line {
+                Ok(line) => {
+                    let done = line.split_whitespace().next() == Some(token);
+                    lines.push(line);
+                    if done {
+                        return Ok(lines)
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, format!("engine did not send '{}' in time", token)))
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(io::ErrorKind::BrokenPipe, "engine quit unexpectedly"))
+                }
+            }
+        }
+    }
+
+    /// Kills the engine process
+    fn kill(&mut self) {
+        if let Some(mut p) = self.process.take() {
+            p.child.kill().unwrap_or(());
+            p.child.wait().ok();
+        }
+    }
+
+    /// Asks the engine for its move in the game's current position. The engine gets the
+    /// start position and all moves of the game.
+    pub fn best_move(&mut self, game: &ChessGame, limits: &SearchLimits) -> io::Result<(Position, Position)> {
+        let result = self.ask(game, limits);
+        if result.is_err() {
+            self.kill();
+        }
+        result
+    }
+
+    /// Does the work of 'best_move'
+    fn ask(&mut self, game: &ChessGame, limits: &SearchLimits) -> io::Result<(Position, Position)> {
+        try!(self.ensure_running());
+
+        let mut replay = match ChessGame::from_fen(&game.start_fen) {
+            Ok(g) => g,
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e))
+        };
+        let mut position = format!("position fen {}", game.start_fen);
+        if !game.history.is_empty() {
+            position.push_str(" moves");
+            for &(from, to) in game.history.iter() {
+                position.push(' ');
+                position.push_str(&format_move(&replay.board, from, to));
+                replay.play(from, to);
+            }
+        }
+
+        let mut go = String::from("go");
+        if let Some(depth) = limits.depth {
+            go.push_str(&format!(" depth {}", depth));
+        }
+        if let Some(nodes) = limits.nodes {
+            go.push_str(&format!(" nodes {}", nodes));
+        }
+        let timeout = match limits.movetime {
+            Some(t) => {
+                go.push_str(&format!(" movetime {}", t.as_secs() * 1000 + (t.subsec_nanos() / 1000000) as u64));
+                t + self.grace
+            },
+            None => Duration::from_millis(MOVE_TIMEOUT)
+        };
+
+        try!(self.send(&position));
+        try!(self.send(&go));
+        let lines = match self.wait_for("bestmove", timeout) {
+            Ok(lines) => lines,
+            // Give a slow engine one last chance to answer before we give up on it
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                try!(self.send("stop"));
+                let grace = self.grace;
+                try!(self.wait_for("bestmove", grace))
+            },
+            Err(e) => return Err(e)
+        };
+
+        let answer = lines.last().and_then(|l| l.split_whitespace().nth(1)).unwrap_or("");
+        match parse_move(answer) {
+            Some(m) if game.clone().possible_moves().contains(&m) => Ok(m),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("engine sent an invalid move '{}'", answer)))
+        }
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        if self.send("quit").is_ok() {
+            if let Some(ref mut p) = self.process {
+                // Give the engine a moment to quit on its own
+                for _ in 0..10 {
+                    if let Ok(Some(_)) = p.child.try_wait() {
+                        break
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+        }
+        self.kill();
+    }
+}
+
+/// A move an external engine is looking for in another thread, so the caller can go on
+/// (e.g. drawing frames) until it arrives instead of waiting for the engine's timeouts
+#[derive(Debug)]
+pub struct PendingMove {
+    // Position the move is for, as the number of moves made and the FEN
+    position: (usize, String),
+    receiver: Receiver<io::Result<(Position, Position)>>
+}
+
+impl PendingMove {
+    /// Asks 'engine' for its move in the game's current position
+    pub fn start(engine: Arc<Mutex<ExternalEngine>>, game: &ChessGame, limits: SearchLimits) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let game = game.clone();
+        let position = (game.history.len(), game.to_fen());
+
+        thread::spawn(move || {
+            let result = match engine.lock() {
+                Ok(mut engine) => engine.best_move(&game, &limits),
+                Err(_) => Err(io::Error::new(io::ErrorKind::Other, "engine is unusable"))
+            };
+            sender.send(result).unwrap_or(());
+        });
+        PendingMove{ position: position, receiver: receiver }
+    }
+
+    /// Returns whether the move is for the game's current position
+    pub fn is_for(&self, game: &ChessGame) -> bool {
+        self.position.0 == game.history.len() && self.position.1 == game.to_fen()
+    }
+
+    /// Returns the engine's answer once it is there, None while it is still searching
+    pub fn poll(&self) -> Option<io::Result<(Position, Position)>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(io::Error::new(io::ErrorKind::Other, "engine thread stopped")))
+        }
+    }
+}
+
+// The fake engine is a shell script
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use chess::ai::Difficulty;
+    use chess::event::GameEvent;
+    use chess::logic::Color;
+    use chess::player::PlayerType;
+    use std::env;
+    use std::fs::{self, File};
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Writes a script starting ci/fake-uci-engine.sh in 'mode' and returns its path
+    fn fake_engine(mode: &str) -> String {
+        let script = format!("{}/ci/fake-uci-engine.sh", env!("CARGO_MANIFEST_DIR"));
+        let path = env::temp_dir().join(format!("rust-chess-fake-engine-{}-{}.sh", mode, ::std::process::id()));
+        {
+            let mut file = File::create(&path).unwrap();
+            writeln!(file, "#!/bin/sh\nFAKE_ENGINE_MODE={} FAKE_ENGINE_MOVES='e2e4 d2d4' exec {}", mode, script).unwrap();
+        }
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Time the fake engine gets beyond its search time, short so the tests that wait for it are quick
+    const GRACE: u64 = 50;
+
+    /// Starts the fake engine in 'mode'
+    fn start(mode: &str) -> ExternalEngine {
+        let mut engine = ExternalEngine::start(&fake_engine(mode)).unwrap();
+        engine.set_grace(Duration::from_millis(GRACE));
+        engine
+    }
+
+    fn pos(s: &str) -> Position {
+        Position::from_algebraic(s).unwrap()
+    }
+
+    #[test]
+    fn plays_moves() {
+        let mut engine = start("normal");
+        assert_eq!(engine.name(), "FakeEngine");
+
+        let mut game = ChessGame::new();
+        let m = engine.best_move(&game, &SearchLimits::depth(1)).unwrap();
+        assert_eq!(m, (pos("e2"), pos("e4")));
+        game.play(m.0, m.1);
+        game.play(pos("e7"), pos("e5"));
+        assert_eq!(engine.best_move(&game, &SearchLimits::depth(1)).unwrap(), (pos("d2"), pos("d4")));
+    }
+
+    #[test]
+    fn crash() {
+        let mut engine = start("crash");
+        let game = ChessGame::new();
+        assert_eq!(engine.best_move(&game, &SearchLimits::depth(1)).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        // The engine is started again for the next move
+        assert_eq!(engine.best_move(&game, &SearchLimits::depth(1)).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        assert!(engine.process.is_none());
+    }
+
+    #[test]
+    fn hang() {
+        let mut engine = start("hang");
+        let start = Instant::now();
+        let result = engine.best_move(&ChessGame::new(), &SearchLimits::movetime(Duration::from_millis(100)));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(100 + 10 * GRACE));
+        assert!(engine.process.is_none());
+    }
+
+    #[test]
+    fn illegal() {
+        let mut engine = start("illegal");
+        let result = engine.best_move(&ChessGame::new(), &SearchLimits::depth(1));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn pending_move() {
+        let engine = Arc::new(Mutex::new(start("hang")));
+        let mut game = ChessGame::new();
+        let pending = PendingMove::start(engine, &game, SearchLimits::movetime(Duration::from_millis(100)));
+
+        // The answer is not there right away, the caller can go on meanwhile
+        assert!(pending.poll().is_none());
+        assert!(pending.is_for(&game));
+        let start = Instant::now();
+        let result = loop {
+            if let Some(result) = pending.poll() {
+                break result
+            }
+            assert!(start.elapsed() < Duration::from_millis(100 + 10 * GRACE));
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        game.play(pos("e2"), pos("e4"));
+        assert!(!pending.is_for(&game));
+    }
+
+    #[test]
+    fn failure_event() {
+        let mut game = ChessGame::new();
+        game.white_player.set_ptype(PlayerType::Engine);
+        game.white_player.set_difficulty(Difficulty::Beginner);
+        game.white_player.set_engine(Some(Arc::new(Mutex::new(start("crash")))));
+        let events = game.events();
+
+        // The smart AI moves in place of the engine
+        assert!(game.do_ai_turn().is_some());
+        match events.try_iter().next() {
+            Some(GameEvent::EngineFailed{ color, .. }) => assert_eq!(color, Color::White),
+            other => panic!("expected the engine to fail, got {:?}", other)
+        }
+        assert_eq!(game.history.len(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use chess::ChessGame;
+use chess::logic::{Color, Figure, Position};
+use chess::player::Player;
+
+/// Number of terms of the evaluation
+pub const TERMS: usize = 9;
+
+/// Names of the terms as used in parameter files
+pub const TERM_NAMES: [&'static str; TERMS] = ["pawn", "knight", "bishop", "rook", "queen",
+                                               "pawn_advance", "center", "bishop_pair", "king_shelter"];
+
+/// Weights of the hand-picked evaluation, only material counts
+const DEFAULT_WEIGHTS: [i32; TERMS] = [10, 25, 25, 50, 100, 0, 0, 0, 0];
+
+/// A linear evaluation: every term is a feature of the position counted for white minus black,
+/// multiplied by its weight. Scores are in tenths of a pawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Eval {
+    pub weights: [i32; TERMS]
+}
+
+impl Default for Eval {
+    fn default() -> Self {
+        Eval{ weights: DEFAULT_WEIGHTS }
+    }
+}
+
+impl Eval {
+    /// Evaluates the position from the view of the player whose turn it is
+    pub fn evaluate(&self, game: &ChessGame) -> i32 {
+        let score = self.score(&features(game));
+
+        if game.turn { score } else { -score }
+    }
+
+    /// Returns the score of the features from white's view
+    pub fn score(&self, features: &[i32; TERMS]) -> i32 {
+        self.weights.iter().zip(features.iter()).map(|(w, f)| w * f).sum()
+    }
+
+    /// Returns how much each term adds to the score from white's view
+    pub fn contributions(&self, game: &ChessGame) -> Vec<(&'static str, i32)> {
+        let features = features(game);
+        (0..TERMS).map(|i| (TERM_NAMES[i], self.weights[i] * features[i])).collect()
+    }
+
+    /// Reads weights from a file with one 'name value' pair per line, terms
+    /// missing in the file keep their default weight
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+
+        let mut eval = Eval::default();
+        for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let mut parts = line.split_whitespace();
+            let (name, value) = (parts.next().unwrap_or(""), parts.next().and_then(|v| v.parse::<i32>().ok()));
+            match (TERM_NAMES.iter().position(|&n| n == name), value) {
+                (Some(i), Some(v)) => eval.weights[i] = v,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid line '{}'", line)))
+            }
+        }
+        Ok(eval)
+    }
+
+    /// Writes the weights in the format 'load' reads
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = try!(File::create(path));
+        write!(file, "{}", self)
+    }
+}
+
+impl ::std::fmt::Display for Eval {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        for i in 0..TERMS {
+            try!(write!(f, "{} {}\n", TERM_NAMES[i], self.weights[i]));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the features of a position, each counted for white minus black
+pub fn features(game: &ChessGame) -> [i32; TERMS] {
+    let white = player_features(&game.white_player, game);
+    let black = player_features(&game.black_player, game);
+
+    let mut features = [0; TERMS];
+    for i in 0..TERMS {
+        features[i] = white[i] - black[i];
+    }
+    features
+}
+
+/// Returns the features of one player
+fn player_features(player: &Player, game: &ChessGame) -> [i32; TERMS] {
+    let mut f = [0; TERMS];
+    let forward = |y: u8| if player.color() == Color::White { y as i32 } else { 7 - y as i32 };
+
+    for (name, positions) in player.figures.iter() {
+        let fig = Figure::from_name(name);
+        let count = positions.len() as i32;
+        match fig {
+            Figure::Pawn => {
+                f[0] += count;
+                // Pawns start on their second row
+                f[5] += positions.iter().map(|p| forward(p.y) - 1).sum::<i32>();
+            },
+            Figure::Knight => f[1] += count,
+            Figure::Bishop => {
+                f[2] += count;
+                if count >= 2 {
+                    f[7] += 1;
+                }
+            },
+            Figure::Rook => f[3] += count,
+            Figure::Queen => f[4] += count,
+            Figure::King => ()
+        }
+        if fig != Figure::King {
+            f[6] += positions.iter().filter(|p| (p.x == 3 || p.x == 4) && (p.y == 3 || p.y == 4)).count() as i32;
+        }
+    }
+
+    // Figures in the reserve can still be dropped, so they count as material
+    for &fig in player.reserve() {
+        match fig {
+            Figure::Pawn => f[0] += 1,
+            Figure::Knight => f[1] += 1,
+            Figure::Bishop => f[2] += 1,
+            Figure::Rook => f[3] += 1,
+            Figure::Queen => f[4] += 1,
+            Figure::King => ()
+        }
+    }
+
+    // Own pawns on the three fields in front of the king, which can be gone in Atomic chess
+    if !player.has_king() {
+        return f
+    }
+    let king = player.king();
+    let ahead = forward(king.y) + 1;
+    if ahead < 8 {
+        let y = if player.color() == Color::White { ahead } else { 7 - ahead } as u8;
+        for x in king.x.saturating_sub(1)..(king.x + 2).min(8) {
+            let pos = Position::new(x, y);
+            if game.board.get_figure(pos) == Some(Figure::Pawn) && game.board.get_figure_color(pos) == Some(player.color()) {
+                f[8] += 1;
+            }
+        }
+    }
+
+    f
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn material() {
+        let eval = Eval::default();
+        assert_eq!(eval.evaluate(&ChessGame::new()), 0);
+
+        // White is a rook up, which is good for white and bad for black
+        let white = ChessGame::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let black = ChessGame::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert_eq!(eval.evaluate(&white), 50);
+        assert_eq!(eval.evaluate(&black), -50);
+        assert_eq!(eval.score(&features(&white)), eval.contributions(&white).iter().map(|c| c.1).sum::<i32>());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = env::temp_dir().join(format!("rust-chess-eval-{}.txt", ::std::process::id()));
+        let mut eval = Eval::default();
+        eval.weights[5] = 3;
+        eval.weights[8] = -2;
+        eval.save(&path).unwrap();
+        assert_eq!(Eval::load(&path).unwrap(), eval);
+
+        // Missing terms keep their default weight, unknown ones are errors
+        fs::write(&path, "# comment\n\nknight 30\n").unwrap();
+        assert_eq!(Eval::load(&path).unwrap().weights[1], 30);
+        assert_eq!(Eval::load(&path).unwrap().weights[0], DEFAULT_WEIGHTS[0]);
+        fs::write(&path, "dragon 5\n").unwrap();
+        assert_eq!(Eval::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
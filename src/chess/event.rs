@@ -0,0 +1,194 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Events a game emits while it is played, so renderers, logging, network sync or sound
+//! can follow it without looking at the board themselves.
+
+use std::sync::mpsc::Sender;
+
+use chess::logic::{Color, Figure, Position};
+
+/// Something that happened in a game
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// A figure of 'color' moved, 'san' is the move in standard algebraic notation
+    /// and 'ply' its index in the history. For a drop 'from' is the field of the reserve.
+    MoveMade { color: Color, figure: Figure, from: Position, to: Position, san: String, ply: usize },
+    /// A figure of 'color' was captured at 'at', which is not the field the capturing
+    /// figure moved to if it took a pawn en passant
+    PieceCaptured { color: Color, figure: Figure, at: Position },
+    /// A figure of 'color' was taken from the reserve and dropped at 'at'
+    PieceDropped { color: Color, figure: Figure, at: Position },
+    /// A capture at 'at' exploded in Atomic chess and took all 'figures' with it,
+    /// each with its color and field, the capturing figure included
+    Exploded { at: Position, figures: Vec<(Color, Figure, Position)> },
+    /// A pawn of 'color' reached the last row at 'at' and became 'figure'
+    PawnPromoted { color: Color, at: Position, figure: Figure },
+    /// The king of 'color' castled, the king's move is also told by 'MoveMade' but
+    /// the rook's move is only told here, each with its start and end position
+    Castled { color: Color, king: (Position, Position), rook: (Position, Position) },
+    /// The king of 'color' standing at 'king' is in check
+    Check { color: Color, king: Position },
+    /// The game is over, with the same result and reason as 'ChessGame::outcome'
+    GameEnded { result: &'static str, reason: &'static str },
+    /// It is the turn of 'color' after 'ply' moves, also after a move was taken back
+    TurnChanged { color: Color, ply: usize },
+    /// The external engine of 'color' could not move because of 'error', the smart AI moves instead
+    EngineFailed { color: Color, error: String }
+}
+
+/// Something that wants to know what happens in a game
+pub trait GameObserver: Send {
+    /// Called for every event in the order they happen
+    fn notify(&mut self, event: &GameEvent);
+}
+
+/// Closures can observe a game
+impl<F: FnMut(&GameEvent) + Send> GameObserver for F {
+    fn notify(&mut self, event: &GameEvent) {
+        self(event)
+    }
+}
+
+/// Sends every event into a channel, the receiver gets them as a stream
+impl GameObserver for Sender<GameEvent> {
+    fn notify(&mut self, event: &GameEvent) {
+        // Nobody listens anymore if the receiver is gone, which is fine
+        let _ = self.send(event.clone());
+    }
+}
+
+/// The observers of a game. Copies of a game, like the ones the AI searches with,
+/// start without observers so they stay quiet.
+pub struct Observers(Vec<Box<GameObserver>>);
+
+impl Observers {
+    /// Returns an empty list of observers
+    pub fn new() -> Self {
+        Observers(Vec::new())
+    }
+
+    /// Adds an observer
+    pub fn add(&mut self, observer: Box<GameObserver>) {
+        self.0.push(observer);
+    }
+
+    /// Returns whether nobody observes the game, then no events need to be made
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Tells all observers about the event
+    pub fn notify(&mut self, event: &GameEvent) {
+        for observer in self.0.iter_mut() {
+            observer.notify(event);
+        }
+    }
+}
+
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Observers::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::sync::mpsc::Receiver;
+    use chess::ChessGame;
+
+    fn pos(s: &str) -> Position {
+        Position::from_algebraic(s).unwrap()
+    }
+
+    fn drain(events: &Receiver<GameEvent>) -> Vec<GameEvent> {
+        events.try_iter().collect()
+    }
+
+    #[test]
+    fn moves_and_captures() {
+        let mut game = ChessGame::new();
+        let events = game.events();
+        assert!(game.play(pos("e2"), pos("e4")));
+        assert_eq!(drain(&events), vec![
+            GameEvent::MoveMade{ color: Color::White, figure: Figure::Pawn, from: pos("e2"), to: pos("e4"),
+                                 san: String::from("e4"), ply: 0 },
+            GameEvent::TurnChanged{ color: Color::Black, ply: 1 }
+        ]);
+
+        assert!(game.play(pos("d7"), pos("d5")));
+        assert!(game.play(pos("e4"), pos("d5")));
+        let made = drain(&events);
+        assert_eq!(made.len(), 5);
+        assert_eq!(made[3], GameEvent::PieceCaptured{ color: Color::Black, figure: Figure::Pawn, at: pos("d5") });
+        assert_eq!(made[4], GameEvent::TurnChanged{ color: Color::Black, ply: 3 });
+
+        assert!(game.undo());
+        assert_eq!(drain(&events), vec![GameEvent::TurnChanged{ color: Color::White, ply: 2 }]);
+    }
+
+    #[test]
+    fn castling_and_en_passant() {
+        let mut game = ChessGame::from_fen("4k3/8/8/8/3p4/8/4P3/4K2R w K - 0 1").unwrap();
+        let events = game.events();
+        assert!(game.play(pos("e2"), pos("e4")));
+        assert!(game.play(pos("d4"), pos("e3")));
+        let made = drain(&events);
+        assert_eq!(made[3], GameEvent::PieceCaptured{ color: Color::White, figure: Figure::Pawn, at: pos("e4") });
+
+        assert!(game.play(pos("e1"), pos("g1")));
+        let made = drain(&events);
+        assert_eq!(made[1], GameEvent::Castled{ color: Color::White, king: (pos("e1"), pos("g1")),
+                                                rook: (pos("h1"), pos("f1")) });
+    }
+
+    #[test]
+    fn check_and_game_end() {
+        let mut game = ChessGame::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        game.subscribe(Box::new(move |event: &GameEvent| log.lock().unwrap().push(event.clone())));
+
+        for &(from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4")].iter() {
+            assert!(game.play(pos(from), pos(to)));
+        }
+        // Only turns of the players end the game, not 'play'
+        assert_eq!(game.do_turn(pos("d8"), pos("h4")), 0);
+        let seen = seen.lock().unwrap();
+        let last = &seen[seen.len() - 3..];
+        assert_eq!(last[0], GameEvent::Check{ color: Color::White, king: pos("e1") });
+        assert_eq!(last[1], GameEvent::TurnChanged{ color: Color::White, ply: 4 });
+        assert_eq!(last[2], GameEvent::GameEnded{ result: "0-1", reason: "checkmate" });
+    }
+
+    // Copies like the ones the AI searches with do not tell the observers anything
+    #[test]
+    fn copies_are_quiet() {
+        let mut game = ChessGame::new();
+        let events = game.events();
+        let mut copy = game.clone();
+        assert!(copy.play(pos("e2"), pos("e4")));
+        assert!(drain(&events).is_empty());
+    }
+}
@@ -20,6 +20,8 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+//! The rules: positions, figures, colors and the board with move validation.
+
 use self::Color::*;
 use self::Figure::*;
 use chess::player::Player;
@@ -27,7 +29,9 @@ use chess::player::Player;
 /// Positions on the Board
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Position {
+    /// File from 0 (a) to 7 (h)
     pub x: u8,
+    /// Rank from 0 (white's first row) to 7
     pub y: u8
 }
 
@@ -48,6 +52,76 @@ impl Position {
             _ => false
         }
     }
+
+    /// Returns the field of the reserve beside the board that holds figures of type 'fig',
+    /// a move from there drops such a figure
+    pub fn reserve(fig: Figure) -> Self {
+        let index = RESERVE_FIGURES.iter().position(|&f| f == fig).expect("kings are never in a reserve");
+        Position{ x: RESERVE_FILE, y: index as u8 }
+    }
+
+    /// Returns the figure a move from this position drops, None for fields of the board
+    pub fn reserve_figure(&self) -> Option<Figure> {
+        if self.x == RESERVE_FILE { RESERVE_FIGURES.get(self.y as usize).cloned() } else { None }
+    }
+
+    /// Parses a position in algebraic notation like 'e4', or a reserve like 'N@'
+    pub fn from_algebraic(s: &str) -> Option<Self> {
+        let bytes = s.as_bytes();
+        if bytes.len() == 2 && bytes[1] == b'@' {
+            return match Figure::from_fen_char(bytes[0] as char) {
+                Some((King, _)) | None => None,
+                Some((fig, _)) => Some(Position::reserve(fig))
+            }
+        }
+        if bytes.len() != 2 || bytes[0] < b'a' || bytes[1] < b'1' {
+            return None
+        }
+
+        let (x, y) = (bytes[0] - b'a', bytes[1] - b'1');
+        if Position::is_pos(x, y) {
+            Some(Position{ x: x, y: y })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the position in algebraic notation like 'e4', a reserve like 'N@'
+    pub fn to_algebraic(&self) -> String {
+        match self.reserve_figure() {
+            Some(fig) => format!("{}@", fig.fen_char(White)),
+            None => format!("{}{}", (b'a' + self.x) as char, self.y + 1)
+        }
+    }
+}
+
+/// File of the reserves beside the board in Crazyhouse, each figure type has its row there
+pub const RESERVE_FILE: u8 = 8;
+
+/// Figures that can be in a reserve, by their row beside the board
+pub const RESERVE_FIGURES: [Figure; 5] = [Pawn, Knight, Bishop, Rook, Queen];
+
+/// Parses a move in long algebraic notation like 'e2e4', 'e7e8q' or the drop 'N@f3', we only
+/// promote to queens so other promotion figures are not a move we can play
+pub fn parse_move(s: &str) -> Option<(Position, Position)> {
+    if s.len() < 4 || s.len() > 5 || !s.is_char_boundary(2) || !s.is_char_boundary(4) {
+        return None
+    }
+    if s.len() == 5 && !s.ends_with('q') && !s.ends_with('Q') {
+        return None
+    }
+
+    match (Position::from_algebraic(&s[0..2]), Position::from_algebraic(&s[2..4])) {
+        (Some(from), Some(to)) => Some((from, to)),
+        _ => None
+    }
+}
+
+/// Returns a move in long algebraic notation, 'board' is the board before the move
+pub fn format_move(board: &Board, from: Position, to: Position) -> String {
+    let promotion = from.reserve_figure().is_none() && board.get_figure(from) == Some(Pawn) && (to.y == 0 || to.y == 7);
+
+    format!("{}{}{}", from.to_algebraic(), to.to_algebraic(), if promotion { "q" } else { "" })
 }
 
 impl ::std::fmt::Display for Position {
@@ -309,6 +383,34 @@ impl Figure {
         }
     }
 
+    /// Returns the figure and its color for a letter as used in FEN, uppercase is white
+    pub fn from_fen_char(c: char) -> Option<(Self, Color)> {
+        let color = if c.is_uppercase() { White } else { Black };
+        let fig = match c.to_ascii_lowercase() {
+            'k' => King,
+            'q' => Queen,
+            'r' => Rook,
+            'b' => Bishop,
+            'n' => Knight,
+            'p' => Pawn,
+            _ => return None
+        };
+        Some((fig, color))
+    }
+
+    /// Returns the letter of the figure as used in FEN, uppercase for white
+    pub fn fen_char(&self, color: Color) -> char {
+        let c = match *self {
+            King => 'k',
+            Queen => 'q',
+            Rook => 'r',
+            Bishop => 'b',
+            Knight => 'n',
+            Pawn => 'p'
+        };
+        if color == White { c.to_ascii_uppercase() } else { c }
+    }
+
     /// Return the short version of a figure's name
     fn short(&self) -> String {
         match *self {
@@ -320,8 +422,28 @@ impl Figure {
             Pawn => String::from("Pa")
         }
     }
+
+    /// Return the Unicode chess symbol of a figure, the filled one is used for both colors
+    pub fn symbol(&self) -> char {
+        match *self {
+            King => '\u{265A}',
+            Queen => '\u{265B}',
+            Rook => '\u{265C}',
+            Bishop => '\u{265D}',
+            Knight => '\u{265E}',
+            Pawn => '\u{265F}'
+        }
+    }
 }
 
+/// Terminal colors of the board in its alternate format: light, dark and hidden fields, white and black figures
+const LIGHT_FIELD: &'static str = "\x1b[48;5;223m";
+const DARK_FIELD: &'static str = "\x1b[48;5;137m";
+const HIDDEN_FIELD: &'static str = "\x1b[48;5;238m";
+const WHITE_FIGURE: &'static str = "\x1b[1;38;5;231m";
+const BLACK_FIGURE: &'static str = "\x1b[1;38;5;16m";
+const RESET: &'static str = "\x1b[0m";
+
 /// Field on the Board
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Field {
@@ -329,11 +451,16 @@ pub struct Field {
     figure: Option<Figure>
 }
 
-/// Print a field, for debug purposes
+/// Print a field, for debug purposes. The alternate format '{:#}' shows the figure
+/// as a colored Unicode symbol for terminals.
 impl ::std::fmt::Display for Field {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         if self.is_empty() {
             write!(f, "   ")
+        } else if f.alternate() {
+            let color = if self.color == Color::White { WHITE_FIGURE } else { BLACK_FIGURE };
+            // Only reset the figure's color and boldness, the field's background stays
+            write!(f, "{} {} \x1b[22;39m", color, self.figure.unwrap().symbol())
         } else {
             let c = if self.color == Color::White {'W'} else {'B'};
             write!(f, "{}{}", c, self.figure.unwrap())
@@ -382,6 +509,7 @@ impl Field {
     }
 }
 
+/// Colors of the two sides
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Color {
     Black,
@@ -401,6 +529,7 @@ impl ::std::ops::Not for Color {
     }
 }
 
+/// The 8x8 fields of the board, indexed by row and then by file
 #[derive(Debug, Clone)]
 pub struct Board {
     fields: Vec<Vec<Field>>
@@ -444,6 +573,15 @@ impl Board {
         Board{ fields: f }
     }
 
+    /// Construct a board without any figures
+    pub fn empty() -> Self {
+        let fields = (0u8..8).map(|y| (0u8..8).map(|x| Field{ color: Field::get_field_color(Position::new(x, y)), figure: None })
+                                              .collect())
+                             .collect();
+
+        Board{ fields: fields }
+    }
+
     /// Get the figure at position 'pos'
     pub fn get_figure(&self, pos: Position) -> Option<Figure> {
         self[pos].get_figure()
@@ -540,6 +678,90 @@ impl Board {
                 })
     }
 
+    /// Returns the figures as written in the first part of a FEN, the figures at the fields
+    /// for which 'promoted' is true get a '~' for having been pawns
+    pub fn placement<F: Fn(Position) -> bool>(&self, promoted: F) -> String {
+        let mut fen = String::new();
+        for y in (0u8..8).rev() {
+            let mut empty = 0;
+            for x in 0u8..8 {
+                let pos = Position::new(x, y);
+                match (self.get_figure(pos), self.get_figure_color(pos)) {
+                    (Some(fig), Some(color)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(fig.fen_char(color));
+                        if promoted(pos) {
+                            fen.push('~');
+                        }
+                    },
+                    _ => empty += 1
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                fen.push('/');
+            }
+        }
+        fen
+    }
+
+    /// Returns which fields the player of 'color' sees in Dark chess, indexed like the board:
+    /// the fields of their figures and all fields these can move to
+    pub fn visible(&self, color: Color) -> [[bool; 8]; 8] {
+        let mut seen = [[false; 8]; 8];
+        for y in 0u8..8 {
+            for x in 0u8..8 {
+                let from = Position::new(x, y);
+                if self.get_figure_color(from) != Some(color) {
+                    continue
+                }
+                seen[y as usize][x as usize] = true;
+                let fig = self.get_figure(from).unwrap();
+                for to_y in 0u8..8 {
+                    for to_x in 0u8..8 {
+                        if fig.valid_move(self, from, Position::new(to_x, to_y), &color) {
+                            seen[to_y as usize][to_x as usize] = true;
+                        }
+                    }
+                }
+            }
+        }
+        seen
+    }
+
+    /// Returns the board drawn like its alternate format as the player of 'color' sees it
+    /// in Dark chess, hidden fields are dark and show no figures
+    pub fn fogged(&self, color: Color) -> String {
+        let mut text = String::new();
+        // Writing into a string never fails
+        let _ = self.write_colored(&mut text, Some(self.visible(color)));
+        text
+    }
+
+    /// Draws colored fields and Unicode figures, only the 'visible' fields if given
+    fn write_colored<W: ::std::fmt::Write>(&self, f: &mut W, visible: Option<[[bool; 8]; 8]>) -> ::std::fmt::Result {
+        try!(write!(f, "\n   a  b  c  d  e  f  g  h\n"));
+        for outer in (0u8..8).rev() {
+            try!(write!(f, "{} ", outer + 1));
+            for x in 0u8..8 {
+                let pos = Position::new(x, outer);
+                if visible.map_or(false, |v| !v[outer as usize][x as usize]) {
+                    try!(write!(f, "{}   ", HIDDEN_FIELD));
+                    continue
+                }
+                let background = if Field::get_field_color(pos) == White { LIGHT_FIELD } else { DARK_FIELD };
+                try!(write!(f, "{}{:#}", background, self[(x, outer)]));
+            }
+            try!(write!(f, "{} {}\n", RESET, outer + 1));
+        }
+        write!(f, "   a  b  c  d  e  f  g  h\n")
+    }
+
     /// Return wether a king is in checkmate
     pub fn checkmate(&mut self, one: &mut Player, two: &mut Player) -> bool {
         // Check if first king is in checkmate
@@ -588,8 +810,14 @@ impl ::std::ops::IndexMut<(u8, u8)> for Board {
     }
 }
 
+/// Print the board with white at the bottom. The alternate format '{:#}' draws colored fields
+/// and Unicode figures for terminals.
 impl ::std::fmt::Display for Board {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        if f.alternate() {
+            return self.write_colored(f, None)
+        }
+
         try!(write!(f, "\n  | a | b | c | d | e | f | g | h |\n"));
         try!(write!(f, "--|---|---|---|---|---|---|---|---|--\n"));
         for outer in (0u8..8).rev() {
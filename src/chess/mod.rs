@@ -20,28 +20,339 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+//! The chess core: `ChessGame` ties the board, both players and the history of a game
+//! together, the submodules hold the rules, the AI and everything around it.
+
 pub mod player;
 pub mod logic;
 pub mod ai;
+pub mod search;
+pub mod eval;
+pub mod polyglot;
+pub mod syzygy;
+pub mod engine;
+pub mod ponder;
+pub mod pgn;
+pub mod personality;
+pub mod clock;
+pub mod event;
+pub mod variant;
+pub mod setup;
+
+extern crate rand;
 
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver};
+
+use self::rand::{Rng, SeedableRng, StdRng};
 use self::player::{PlayerType, Player};
-use self::logic::{Color, Board, Position, Figure};
+use self::logic::{Color, Board, Position, Figure, RESERVE_FIGURES};
+use self::search::SearchResult;
+use self::ai::Explanation;
+use self::clock::{Clock, TimeControl};
+use self::event::{GameEvent, GameObserver, Observers};
+use self::variant::{Variant, Standard};
 
+/// A game of chess: the board, both players, whose turn it is and everything that happened so far.
+/// Moves are made with 'do_turn' for humans and 'do_ai_turn' for AI players.
+#[derive(Clone)]
 pub struct ChessGame {
+    /// Players of both sides, they also know where their figures are
     pub white_player: Player,
     pub black_player: Player,
+    /// The fields with the figures on them
     pub board: Board,
+    /// True if it is white's turn
     pub turn: bool,
-    pub gameover: bool
+    /// Whether no more moves can be made
+    pub gameover: bool,
+    /// Field a pawn skipped in the last move when it moved two fields forward
+    pub en_passant: Option<Position>,
+    /// Position the game started from
+    pub start_fen: String,
+    /// All moves made since the start position
+    pub history: Vec<(Position, Position)>,
+    /// Why the AI made each move in 'history', None for moves not found by a search
+    pub explanations: Vec<Option<Explanation>>,
+    /// Plies since the last capture or pawn move, as in FEN
+    pub halfmove_clock: u32,
+    /// Number of the current full move, as in FEN
+    pub fullmove_number: u32,
+    /// Reply the AI expects to its last move, None if it has no idea
+    pub expected_reply: Option<(Position, Position)>,
+    /// Seed of all random decisions of the AI
+    pub seed: u64,
+    /// Clocks of both players, None if the game is not timed
+    pub clock: Option<Clock>,
+    /// How the game ended, None while it goes on or if nobody knows
+    pub outcome: Option<Outcome>,
+    /// Player whose draw offer the opponent has not answered yet
+    pub draw_offer: Option<Color>,
+    /// Every draw offer made, by whom and after how many plies
+    pub draw_offers: Vec<(Color, usize)>,
+    /// Checks given by white and black, for variants that count them
+    pub checks: [u32; 2],
+    /// Whether white and black captured the opponent's king, for variants in which that wins
+    pub kings_captured: [bool; 2],
+    // Keys of all positions since the start position, the current one last
+    position_keys: Vec<u64>,
+    // What white and black remember of the opponent's figures, only kept in games of
+    // variants that hide figures
+    memories: Option<[Memory; 2]>,
+    // Rules of the game
+    variant: Arc<Variant>,
+    // Who gets told about the events of this game
+    observers: Observers
+}
+
+/// How a game ended
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Outcome {
+    /// '1-0', '0-1' or '1/2-1/2'
+    pub result: &'static str,
+    /// Why it ended, like 'checkmate' or 'resignation'
+    pub reason: &'static str
+}
+
+/// What a player remembers of the opponent's figures in variants that hide figures
+#[derive(Clone)]
+struct Memory {
+    /// The opponent figure last seen on each field and the ply it was seen in
+    last_seen: [[Option<(Figure, usize)>; 8]; 8],
+    /// How many opponent figures of each kind the player knows to be left
+    left: HashMap<String, usize>
+}
+
+impl Memory {
+    /// Returns what the player of 'color' knows in 'game' without having seen anything before
+    fn new(game: &ChessGame, color: Color) -> Self {
+        let opponent = if color == Color::White { &game.black_player } else { &game.white_player };
+        let mut memory = Memory{ last_seen: [[None; 8]; 8],
+                                 left: opponent.figures.iter().map(|(name, v)| (name.clone(), v.len())).collect() };
+        // Everybody knows the start position
+        for y in 0u8..8 {
+            for x in 0u8..8 {
+                let pos = Position::new(x, y);
+                if game.board.get_figure_color(pos) == Some(!color) {
+                    memory.last_seen[y as usize][x as usize] = game.board.get_figure(pos).map(|fig| (fig, 0));
+                }
+            }
+        }
+        memory.look(game, color);
+        memory
+    }
+
+    /// Remembers what the player of 'color' sees in 'game' now
+    fn look(&mut self, game: &ChessGame, color: Color) {
+        let ply = game.history.len();
+        let visible = game.board.visible(color);
+        for y in 0u8..8 {
+            for x in 0u8..8 {
+                let pos = Position::new(x, y);
+                if visible[y as usize][x as usize] {
+                    let theirs = game.board.get_figure_color(pos) == Some(!color);
+                    self.last_seen[y as usize][x as usize] = game.board.get_figure(pos).filter(|_| theirs).map(|fig| (fig, ply));
+                }
+            }
+        }
+    }
 }
 
+/// FEN of the standard start position
+pub const START_FEN: &'static str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
 impl ChessGame {
+    /// Returns a game in the start position between two human players
     pub fn new() -> Self {
         ChessGame{ white_player: Player::new(PlayerType::Human, Color::White),
                    black_player: Player::new(PlayerType::Human, Color::Black),
                    board: Board::new(),
                    turn: true,
-                   gameover: false }
+                   gameover: false,
+                   en_passant: None,
+                   start_fen: String::from(START_FEN),
+                   history: Vec::new(),
+                   explanations: Vec::new(),
+                   halfmove_clock: 0,
+                   fullmove_number: 1,
+                   expected_reply: None,
+                   seed: rand::thread_rng().gen(),
+                   clock: None,
+                   outcome: None,
+                   draw_offer: None,
+                   draw_offers: Vec::new(),
+                   checks: [0, 0],
+                   kings_captured: [false, false],
+                   position_keys: Vec::new(),
+                   memories: None,
+                   variant: Arc::new(Standard),
+                   observers: Observers::new() }.with_start_keys()
+    }
+
+    /// Creates a game from a position in Forsyth-Edwards Notation, both players are human
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = fen.split_whitespace().collect();
+        if parts.len() < 2 {
+            return Err(format!("FEN needs at least figures and side to move: '{}'", fen))
+        }
+
+        // Crazyhouse positions list the reserves in brackets after the figures
+        let (placement, reserves) = match parts[0].find('[') {
+            Some(i) => (&parts[0][..i], parts[0][i + 1..].trim_end_matches(']')),
+            None => (parts[0], "")
+        };
+        let mut board = Board::empty();
+        let mut promoted = Vec::new();
+        let rows: Vec<&str> = placement.split('/').collect();
+        if rows.len() != 8 {
+            return Err(format!("FEN needs 8 rows: '{}'", placement))
+        }
+        for (i, row) in rows.iter().enumerate() {
+            let y = 7 - i as u8;
+            let mut x = 0u8;
+            for c in row.chars() {
+                if let Some(n) = c.to_digit(10) {
+                    x = match x.checked_add(n as u8) {
+                        Some(x) if x <= 8 => x,
+                        _ => return Err(format!("Row {} is too long", 8 - i))
+                    };
+                } else if c == '~' && x > 0 {
+                    // The figure before was a pawn before it was promoted
+                    promoted.push(Position::new(x - 1, y));
+                } else if let Some((fig, color)) = Figure::from_fen_char(c) {
+                    if x > 7 {
+                        return Err(format!("Row {} is too long", 8 - i))
+                    }
+                    board.set_figure(Position::new(x, y), fig, color);
+                    x += 1;
+                } else {
+                    return Err(format!("Unknown figure '{}'", c))
+                }
+            }
+            if x != 8 {
+                return Err(format!("Row {} does not have 8 fields", 8 - i))
+            }
+        }
+
+        let turn = match parts[1] {
+            "w" => true,
+            "b" => false,
+            other => return Err(format!("Unknown side to move '{}'", other))
+        };
+
+        let mut white = Player::from_board(PlayerType::Human, Color::White, &board);
+        let mut black = Player::from_board(PlayerType::Human, Color::Black, &board);
+        for &(ref player, name) in [(&white, "White"), (&black, "Black")].iter() {
+            match player.figures.get("king") {
+                Some(v) if v.len() == 1 => (),
+                _ => return Err(format!("{} needs exactly one king", name))
+            }
+        }
+
+        for c in reserves.chars() {
+            match Figure::from_fen_char(c) {
+                Some((Figure::King, _)) | None => return Err(format!("Invalid figure '{}' in a reserve", c)),
+                Some((fig, Color::White)) => white.add_to_reserve(fig),
+                Some((fig, Color::Black)) => black.add_to_reserve(fig)
+            }
+        }
+        for &pos in promoted.iter() {
+            match board.get_figure_color(pos) {
+                Some(Color::White) => white.set_promoted(pos),
+                Some(Color::Black) => black.set_promoted(pos),
+                None => ()
+            }
+        }
+
+        let castling = parts.get(2).cloned().unwrap_or("-");
+        white.set_castling_rights(castling.contains('K'), castling.contains('Q'));
+        black.set_castling_rights(castling.contains('k'), castling.contains('q'));
+
+        let en_passant = match parts.get(3) {
+            Some(&"-") | None => None,
+            Some(s) => match Position::from_algebraic(s) {
+                Some(pos) => Some(pos),
+                None => return Err(format!("Invalid en passant field '{}'", s))
+            }
+        };
+
+        let number = |i: usize, default: u32| parts.get(i).map_or(Ok(default), |s| s.parse::<u32>())
+                                                          .map_err(|_| format!("Invalid move number '{}'", parts[i]));
+
+        Ok(ChessGame{ white_player: white,
+                      black_player: black,
+                      board: board,
+                      turn: turn,
+                      gameover: false,
+                      en_passant: en_passant,
+                      start_fen: parts.join(" "),
+                      history: Vec::new(),
+                      explanations: Vec::new(),
+                      halfmove_clock: try!(number(4, 0)),
+                      fullmove_number: try!(number(5, 1)),
+                      expected_reply: None,
+                      seed: rand::thread_rng().gen(),
+                      clock: None,
+                      outcome: None,
+                      draw_offer: None,
+                      draw_offers: Vec::new(),
+                      checks: [0, 0],
+                      kings_captured: [false, false],
+                      position_keys: Vec::new(),
+                      memories: None,
+                      variant: Arc::new(Standard),
+                      observers: Observers::new() }.with_start_keys())
+    }
+
+    /// Returns the game knowing the key of its start position and, if the variant hides
+    /// figures, what both players see of it
+    fn with_start_keys(mut self) -> Self {
+        self.position_keys = vec![self.position_key()];
+        self.memories = if self.variant.hides_figures() {
+            Some([Memory::new(&self, Color::White), Memory::new(&self, Color::Black)])
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Returns the random number generator for the AI in the current position. Every ply
+    /// gets its own one derived from the seed, so a game plays the same again with the
+    /// same seed, even after taking back moves.
+    pub fn rng(&self) -> StdRng {
+        StdRng::from_seed(&[self.seed as usize, (self.seed >> 32) as usize, self.history.len()])
+    }
+
+    /// Returns the current position in Forsyth-Edwards Notation
+    pub fn to_fen(&self) -> String {
+        let mut fen = self.board.placement(|pos| self.white_player.is_promoted(pos) || self.black_player.is_promoted(pos));
+        if self.variant.has_drops() {
+            // Strongest figures first, so the same reserves are always written the same
+            fen.push('[');
+            for player in [&self.white_player, &self.black_player].iter() {
+                for &fig in RESERVE_FIGURES.iter().rev() {
+                    let count = player.reserve().iter().filter(|&&f| f == fig).count();
+                    fen.extend((0..count).map(|_| fig.fen_char(player.color())));
+                }
+            }
+            fen.push(']');
+        }
+
+        let (white_short, white_long) = self.white_player.castling_rights();
+        let (black_short, black_long) = self.black_player.castling_rights();
+        let mut castling: String = [(white_short, 'K'), (white_long, 'Q'), (black_short, 'k'), (black_long, 'q')]
+            .iter()
+            .filter(|r| r.0)
+            .map(|r| r.1)
+            .collect();
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        format!("{} {} {} {} {} {}", fen, if self.turn { "w" } else { "b" }, castling,
+                self.en_passant.map_or(String::from("-"), |p| p.to_algebraic()),
+                self.halfmove_clock, self.fullmove_number)
     }
 
     /// Returns the color of the player whose turn it is
@@ -53,17 +364,454 @@ impl ChessGame {
         }
     }
 
+    /// Returns the player whose turn it is
+    pub fn active_player(&self) -> &Player {
+        if self.turn {
+            &self.white_player
+        } else {
+            &self.black_player
+        }
+    }
+
+    /// Returns all valid moves for the player whose turn it is, none once the variant has a winner
+    pub fn possible_moves(&mut self) -> Vec<(Position, Position)> {
+        let variant = self.variant.clone();
+        if variant.winner(self).is_some() {
+            return Vec::new()
+        }
+        variant.legal_moves(self)
+    }
+
+    /// Returns the rules of the game
+    pub fn variant(&self) -> &Variant {
+        &*self.variant
+    }
+
+    /// Switches to the rules and the start position of another variant, keeping the players
+    /// and their settings. Only possible before the first move.
+    pub fn set_variant(&mut self, variant: Arc<Variant>) -> bool {
+        if !self.history.is_empty() {
+            return false
+        }
+        match ChessGame::from_fen_variant(variant.start_fen(), variant) {
+            Ok(start) => {
+                self.start_from(start);
+                true
+            },
+            Err(_) => false
+        }
+    }
+
+    /// Starts the game anew from a position in Forsyth-Edwards Notation with the same rules,
+    /// keeping the players and their settings
+    pub fn set_position(&mut self, fen: &str) -> Result<(), String> {
+        let start = try!(ChessGame::from_fen_variant(fen, self.variant.clone()));
+        self.start_from(start);
+        Ok(())
+    }
+
+    /// Takes over the position and the rules of the new game 'start', forgetting everything
+    /// that happened so far but keeping the players and their settings
+    fn start_from(&mut self, start: ChessGame) {
+        self.white_player.set_figures_from(&start.white_player);
+        self.black_player.set_figures_from(&start.black_player);
+        self.board = start.board;
+        self.turn = start.turn;
+        self.gameover = false;
+        self.en_passant = start.en_passant;
+        self.start_fen = start.start_fen;
+        self.history.clear();
+        self.explanations.clear();
+        self.halfmove_clock = start.halfmove_clock;
+        self.fullmove_number = start.fullmove_number;
+        self.expected_reply = None;
+        self.outcome = None;
+        self.draw_offer = None;
+        self.draw_offers.clear();
+        self.checks = [0, 0];
+        self.kings_captured = [false, false];
+        self.position_keys = start.position_keys;
+        self.memories = start.memories;
+        self.variant = start.variant;
+        let control = self.clock.as_ref().map(|clock| clock.control().clone());
+        self.set_time_control(control);
+        let color = self.turn_color();
+        self.emit(GameEvent::TurnChanged{ color: color, ply: 0 });
+    }
+
+    /// Creates a game of a variant from a position in Forsyth-Edwards Notation, both players are human
+    pub fn from_fen_variant(fen: &str, variant: Arc<Variant>) -> Result<Self, String> {
+        let mut game = try!(ChessGame::from_fen(fen));
+        game.variant = variant;
+        Ok(game.with_start_keys())
+    }
+
+    /// Returns a new game in the start position of this one with the same rules, to replay its moves
+    pub fn restart(&self) -> Result<ChessGame, String> {
+        ChessGame::from_fen_variant(&self.start_fen, self.variant.clone())
+    }
+
+    /// Returns a copy of the game with only what the player of 'color' knows in variants that
+    /// hide figures. Opponent figures on fields they cannot see stand where the player saw them
+    /// last, as far as they can still be there. A hidden figure blocking an own pawn, which the
+    /// pawn gives away, is taken for a pawn if nothing was seen there.
+    pub fn seen_by(&self, color: Color) -> ChessGame {
+        let mut seen = self.clone();
+        if !self.variant.hides_figures() {
+            return seen
+        }
+        // The copy only knows what the player knows, there is nothing more to remember in it
+        seen.memories = None;
+
+        let (memory, mut left) = self.memory_of(color);
+        let visible = self.board.visible(color);
+        let (forward, start) = if color == Color::White { (1i8, 1i8) } else { (-1, 6) };
+        let own_pawn = |x: u8, y: i8| {
+            y >= 0 && y < 8 && self.board.get_figure(Position::new(x, y as u8)) == Some(Figure::Pawn) &&
+            self.board.get_figure_color(Position::new(x, y as u8)) == Some(color)
+        };
+        let mut blockers = Vec::new();
+        for y in 0u8..8 {
+            for x in 0u8..8 {
+                let pos = Position::new(x, y);
+                let fig = match self.board.get_figure(pos) {
+                    Some(fig) if self.board.get_figure_color(pos) == Some(!color) => fig,
+                    _ => continue
+                };
+                if visible[y as usize][x as usize] {
+                    if let Some(n) = left.get_mut(&fig.name()) {
+                        *n = n.saturating_sub(1);
+                    }
+                    continue
+                }
+
+                let opponent = if color == Color::White { &mut seen.black_player } else { &mut seen.white_player };
+                opponent.forget_promotion(pos);
+                opponent.capture(fig.name(), pos);
+                seen.board[pos].set_empty(pos);
+
+                let y = y as i8;
+                if own_pawn(x, y - forward) ||
+                   y - 2 * forward == start && own_pawn(x, start) && self.board.is_empty(Position::new(x, (y - forward) as u8)) {
+                    blockers.push(pos);
+                }
+            }
+        }
+
+        // Something surely stands in front of a blocked pawn, so the blockers come first
+        let opponent = if color == Color::White { &mut seen.black_player } else { &mut seen.white_player };
+        for pos in blockers {
+            let fig = memory.iter().find(|m| m.1 == pos).map_or(Figure::Pawn, |m| m.0);
+            seen.board.set_figure(pos, fig, !color);
+            opponent.reverse_capture(fig.name(), pos);
+            if let Some(n) = left.get_mut(&fig.name()) {
+                *n = n.saturating_sub(1);
+            }
+        }
+        // Then the remembered figures, the most recently seen first
+        for &(fig, pos) in memory.iter() {
+            let left = left.entry(fig.name()).or_insert(0);
+            if *left > 0 && seen.board.is_empty(pos) && !visible[pos.y as usize][pos.x as usize] {
+                seen.board.set_figure(pos, fig, !color);
+                opponent.reverse_capture(fig.name(), pos);
+                *left -= 1;
+            }
+        }
+        seen
+    }
+
+    /// Returns where the player of 'color' last saw opponent figures, the most recently seen
+    /// first, and how many opponent figures of each kind they know to be left
+    fn memory_of(&self, color: Color) -> (Vec<(Figure, Position)>, HashMap<String, usize>) {
+        let index = if color == Color::White { 0 } else { 1 };
+        let remembered = match self.memories {
+            Some(ref memories) => memories[index].clone(),
+            None => Memory::new(self, color)
+        };
+
+        let mut memory: Vec<(Figure, Position, usize)> = Vec::new();
+        for y in 0u8..8 {
+            for x in 0u8..8 {
+                if let Some((fig, ply)) = remembered.last_seen[y as usize][x as usize] {
+                    memory.push((fig, Position::new(x, y), ply));
+                }
+            }
+        }
+        memory.sort_by(|a, b| b.2.cmp(&a.2));
+        (memory.into_iter().map(|(fig, pos, _)| (fig, pos)).collect(), remembered.left)
+    }
+
+    /// Returns whether the king of the player whose turn it is is in check
+    pub fn in_check(&self) -> bool {
+        self.variant.in_check(self)
+    }
+
+    /// Returns whether neither player has enough figures left to ever checkmate,
+    /// that is only kings and at most one knight or bishop
+    pub fn insufficient_material(&self) -> bool {
+        !self.can_checkmate(true) && !self.can_checkmate(false)
+    }
+
+    /// Returns whether the white ('white' is true) or black player has enough figures to
+    /// checkmate. A lone king never can, a king with one knight or bishop only if the
+    /// opponent has figures that can block its own king.
+    pub fn can_checkmate(&self, white: bool) -> bool {
+        // Figures in a reserve can still be dropped
+        let figures = |p: &Player| -> Vec<Figure> {
+            p.figures.iter()
+                     .flat_map(|(name, positions)| positions.iter().map(move |_| Figure::from_name(name)))
+                     .filter(|&f| f != Figure::King)
+                     .chain(p.reserve().iter().cloned())
+                     .collect()
+        };
+        let (mine, theirs) = if white {
+            (figures(&self.white_player), figures(&self.black_player))
+        } else {
+            (figures(&self.black_player), figures(&self.white_player))
+        };
+
+        match mine.len() {
+            0 => false,
+            1 if mine[0] == Figure::Knight || mine[0] == Figure::Bishop => !theirs.is_empty(),
+            _ => true
+        }
+    }
+
+    /// Sets the time control, the clock of the player whose turn it is starts right away.
+    /// None removes the clocks.
+    pub fn set_time_control(&mut self, control: Option<TimeControl>) {
+        self.clock = control.map(Clock::new);
+        if let Some(ref mut clock) = self.clock {
+            if !self.gameover {
+                clock.start(self.turn);
+            }
+        }
+    }
+
+    /// Ends the game if the player whose turn it is ran out of time and returns whether that happened.
+    /// The opponent wins, unless they can not checkmate at all.
+    pub fn check_clock(&mut self) -> bool {
+        let flagged = match self.clock {
+            Some(ref clock) => !self.gameover && clock.flagged(self.turn),
+            None => false
+        };
+        if flagged {
+            let result = match (self.can_checkmate(!self.turn), self.turn) {
+                (false, _) => "1/2-1/2",
+                (true, true) => "0-1",
+                (true, false) => "1-0"
+            };
+            self.finish(result, "time forfeit");
+        }
+        flagged
+    }
+
+    /// Lets the white ('white' is true) or black player resign, returns false if the game is already over
+    pub fn resign(&mut self, white: bool) -> bool {
+        if self.gameover {
+            return false
+        }
+        self.finish(if white { "0-1" } else { "1-0" }, "resignation");
+        true
+    }
+
+    /// The white ('white' is true) or black player offers a draw, which stands until the opponent
+    /// answers it or makes a move. Returns false if the game is over or an offer already stands.
+    pub fn offer_draw(&mut self, white: bool) -> bool {
+        if self.gameover || self.draw_offer.is_some() {
+            return false
+        }
+        let color = if white { Color::White } else { Color::Black };
+        self.draw_offer = Some(color);
+        self.draw_offers.push((color, self.history.len()));
+        true
+    }
+
+    /// The white ('white' is true) or black player accepts the opponent's draw offer,
+    /// returns false if there is none
+    pub fn accept_draw(&mut self, white: bool) -> bool {
+        if !self.decline_draw(white) {
+            return false
+        }
+        self.finish("1/2-1/2", "agreement");
+        true
+    }
+
+    /// The white ('white' is true) or black player declines the opponent's draw offer,
+    /// returns false if there is none
+    pub fn decline_draw(&mut self, white: bool) -> bool {
+        let opponent = if white { Color::Black } else { Color::White };
+        if self.gameover || self.draw_offer != Some(opponent) {
+            return false
+        }
+        self.draw_offer = None;
+        true
+    }
+
+    /// Returns why the player whose turn it is may claim a draw, None if they may not
+    pub fn draw_claim(&self) -> Option<&'static str> {
+        if self.halfmove_clock >= 100 {
+            Some("fifty move rule")
+        } else if self.repetitions() >= 3 {
+            Some("threefold repetition")
+        } else {
+            None
+        }
+    }
+
+    /// The player whose turn it is claims a draw, returns false if the claim is not valid
+    pub fn claim_draw(&mut self) -> bool {
+        match self.draw_claim() {
+            Some(reason) if !self.gameover => {
+                self.finish("1/2-1/2", reason);
+                true
+            },
+            _ => false
+        }
+    }
+
+    /// Returns how often the current position occurred in this game, positions are the same
+    /// if figures, reserves, side to move, castling rights and en passant captures are
+    pub fn repetitions(&self) -> usize {
+        let current = self.position_key();
+        self.position_keys.iter().filter(|&&key| key == current).count()
+    }
+
+    /// Returns the key of the current position to compare it with earlier ones, the Polyglot
+    /// key mixed with the reserves in variants with drops
+    fn position_key(&self) -> u64 {
+        let mut key = polyglot::key(self);
+        if self.variant.has_drops() {
+            for (side, player) in [&self.white_player, &self.black_player].iter().enumerate() {
+                for (i, &fig) in RESERVE_FIGURES.iter().enumerate() {
+                    let count = player.reserve().iter().filter(|&&f| f == fig).count() as u64;
+                    key ^= count.wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(((side * RESERVE_FIGURES.len() + i) * 6) as u32);
+                }
+            }
+        }
+        key
+    }
+
+    /// Ends the game with the result, stopping the clocks
+    pub fn finish(&mut self, result: &'static str, reason: &'static str) {
+        self.gameover = true;
+        self.outcome = Some(Outcome{ result: result, reason: reason });
+        if let Some(ref mut clock) = self.clock {
+            clock.stop();
+        }
+        self.emit(GameEvent::GameEnded{ result: result, reason: reason });
+    }
+
+    /// Ends the game if a player won by a rule of the variant or the player whose turn it is can not move
+    fn finish_if_stuck(&mut self) {
+        if self.gameover {
+            return
+        }
+        if let Some((winner, reason)) = self.variant.winner(self) {
+            self.finish(if winner == Color::White { "1-0" } else { "0-1" }, reason);
+        } else if self.possible_moves().is_empty() {
+            if self.in_check() {
+                self.finish(if self.turn { "0-1" } else { "1-0" }, "checkmate");
+            } else {
+                self.finish("1/2-1/2", "stalemate");
+            }
+        }
+    }
+
+    /// Lets 'observer' know about everything that happens in this game from now on,
+    /// copies of the game made later do not tell it anything
+    pub fn subscribe(&mut self, observer: Box<GameObserver>) {
+        self.observers.add(observer);
+    }
+
+    /// Returns a stream of all events of this game from now on
+    pub fn events(&mut self) -> Receiver<GameEvent> {
+        let (sender, receiver) = channel();
+        self.subscribe(Box::new(sender));
+        receiver
+    }
+
+    /// Tells all observers about the event
+    fn emit(&mut self, event: GameEvent) {
+        self.observers.notify(&event);
+    }
+
     /// Makes the move from 'from' to 'to' and return whether a figure was captured
     fn make_move(&mut self, from: Position, to: Position) -> (bool, bool) {
         let mut captured = false;
         let mut upgrade = false;
-        if self.turn {
-            // If a figure is at 'to' capture it and set flag
-            if !self.board.is_empty(to) {
-                let name = self.board[to].get_figure().unwrap().name();
-                self.black_player.capture(name.clone(), to);
+        // Describing the move costs time, so only do it if somebody listens
+        let observed = !self.observers.is_empty();
+        let san = if observed { pgn::move_to_san(self, from, to) } else { String::new() };
+        let color = self.turn_color();
+        let dropped = from.reserve_figure();
+        let figure = dropped.or_else(|| self.board.get_figure(from));
+        // A pawn moving sideways onto an empty field takes the pawn beside it en passant
+        let passed = Position::new(to.x, from.y);
+        let en_passant = dropped.is_none() && figure == Some(Figure::Pawn) && from.x != to.x && self.board.is_empty(to);
+        let victim_at = if en_passant { passed } else { to };
+        let victim = self.board.get_figure(victim_at);
+        // A captured figure that was promoted counts as the pawn it was
+        let demoted = victim.is_some() &&
+                      if self.turn { self.black_player.forget_promotion(to) } else { self.white_player.forget_promotion(to) };
+        let taken = if demoted { Some(Figure::Pawn) } else { victim };
+        // Castling is the king's move of two fields, the rook jumps over it
+        let castling = if dropped.is_none() && figure == Some(Figure::King) && (from.x as i8 - to.x as i8).abs() == 2 {
+            Some(if to.x > from.x { (Position::new(7, from.y), Position::new(5, from.y)) }
+                 else { (Position::new(0, from.y), Position::new(3, from.y)) })
+        } else {
+            None
+        };
+
+        self.en_passant = match figure {
+            Some(Figure::Pawn) if dropped.is_none() && (from.y + 2 == to.y || to.y + 2 == from.y) => {
+                Some(Position::new(from.x, (from.y + to.y) / 2))
+            },
+            _ => None
+        };
+        // The player knows which figures they captured
+        if let (Some(memories), Some(fig)) = (self.memories.as_mut(), victim) {
+            if let Some(n) = memories[if self.turn { 0 } else { 1 }].left.get_mut(&fig.name()) {
+                *n = n.saturating_sub(1);
+            }
+        }
+        self.history.push((from, to));
+        self.explanations.push(None);
+        // Moving instead of answering declines the opponent's draw offer
+        if self.draw_offer.is_some() && self.draw_offer != Some(self.turn_color()) {
+            self.draw_offer = None;
+        }
+        if figure == Some(Figure::Pawn) || victim.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if !self.turn {
+            self.fullmove_number += 1;
+        }
+
+        if let Some(fig) = dropped {
+            // A figure from the reserve is put on an empty field
+            self.board.set_figure(to, fig, color);
+            if self.turn {
+                self.white_player.drop_figure(fig, to);
+            } else {
+                self.black_player.drop_figure(fig, to);
+            }
+        } else if self.turn {
+            // Moving the king or a rook or losing a rook gives up castling
+            self.white_player.lose_castling(from);
+            self.black_player.lose_castling(to);
+            // If a figure is at 'to' or passed en passant capture it and set flag
+            if let Some(fig) = victim {
+                self.black_player.capture(fig.name(), victim_at);
+                self.board[victim_at].set_empty(victim_at);
                 captured = true;
             }
+            if let Some((rook_from, rook_to)) = castling {
+                self.board.move_figure(rook_from, rook_to);
+                self.white_player.move_figure(rook_from, rook_to);
+            }
             // If a pawn moved to the end of the board make it a queen
             if to.y == 7 && self.board.get_figure(from).unwrap() == Figure::Pawn {
                 self.board.set_figure(from, Figure::Queen, Color::White);
@@ -74,12 +822,19 @@ impl ChessGame {
             self.board.move_figure(from, to);
             self.white_player.move_figure(from, to);
         } else {
-            // If a figure is at 'to' capture it and set flag
-            if !self.board.is_empty(to) {
-                let name = self.board[to].get_figure().unwrap().name();
-                self.white_player.capture(name.clone(), to);
+            // Moving the king or a rook or losing a rook gives up castling
+            self.black_player.lose_castling(from);
+            self.white_player.lose_castling(to);
+            // If a figure is at 'to' or passed en passant capture it and set flag
+            if let Some(fig) = victim {
+                self.white_player.capture(fig.name(), victim_at);
+                self.board[victim_at].set_empty(victim_at);
                 captured = true;
             }
+            if let Some((rook_from, rook_to)) = castling {
+                self.board.move_figure(rook_from, rook_to);
+                self.black_player.move_figure(rook_from, rook_to);
+            }
             // If a pawn moved to the end of the board make it a queen
             if to.y == 0 && self.board.get_figure(from).unwrap() == Figure::Pawn {
                 self.board.set_figure(from, Figure::Queen, Color::Black);
@@ -91,37 +846,183 @@ impl ChessGame {
             self.black_player.move_figure(from, to);
         }
         self.turn = !self.turn;
+        if let Some(ref mut clock) = self.clock {
+            clock.press();
+        }
+        let ply = self.history.len();
+        if observed {
+            self.emit(GameEvent::MoveMade{ color: color, figure: figure.unwrap(), from: from, to: to, san: san, ply: ply - 1 });
+            if let Some(fig) = dropped {
+                self.emit(GameEvent::PieceDropped{ color: color, figure: fig, at: to });
+            }
+            if let Some(victim) = victim {
+                self.emit(GameEvent::PieceCaptured{ color: !color, figure: victim, at: victim_at });
+            }
+            if let Some(rook) = castling {
+                self.emit(GameEvent::Castled{ color: color, king: (from, to), rook: rook });
+            }
+            if upgrade {
+                self.emit(GameEvent::PawnPromoted{ color: color, at: to, figure: Figure::Queen });
+            }
+        }
+        // The variant's changes come after the move, and so do their events
+        let variant = self.variant.clone();
+        variant.after_move(self, from, to, taken);
+        let key = self.position_key();
+        self.position_keys.push(key);
+        if let Some(mut memories) = self.memories.take() {
+            memories[0].look(self, Color::White);
+            memories[1].look(self, Color::Black);
+            self.memories = Some(memories);
+        }
+
+        if observed {
+            if self.in_check() {
+                let king = self.active_player().king();
+                self.emit(GameEvent::Check{ color: !color, king: king });
+            }
+            self.emit(GameEvent::TurnChanged{ color: !color, ply: ply });
+        }
         (captured, upgrade)
     }
 
+    /// Makes the move from 'from' to 'to' if it is valid for the player whose turn it is,
+    /// returns whether the move was made
+    pub fn play(&mut self, from: Position, to: Position) -> bool {
+        if self.gameover || !self.possible_moves().contains(&(from, to)) {
+            return false
+        }
+
+        self.make_move(from, to);
+        true
+    }
+
+    /// Takes back the last move by replaying the game from its start without it,
+    /// returns false if there is no move to take back
+    pub fn undo(&mut self) -> bool {
+        let mut replay = match (self.history.len(), self.restart()) {
+            (0, _) | (_, Err(_)) => return false,
+            (_, Ok(game)) => game
+        };
+
+        let last = self.history.len() - 1;
+        for &(from, to) in self.history[..last].iter() {
+            replay.make_move(from, to);
+        }
+
+        self.white_player.set_figures_from(&replay.white_player);
+        self.black_player.set_figures_from(&replay.black_player);
+        self.board = replay.board;
+        self.turn = replay.turn;
+        self.gameover = false;
+        self.en_passant = replay.en_passant;
+        self.history = replay.history;
+        self.explanations.truncate(last);
+        self.halfmove_clock = replay.halfmove_clock;
+        self.fullmove_number = replay.fullmove_number;
+        self.checks = replay.checks;
+        self.kings_captured = replay.kings_captured;
+        self.position_keys = replay.position_keys;
+        self.memories = replay.memories;
+        self.outcome = None;
+        self.draw_offer = None;
+        if let Some(ref mut clock) = self.clock {
+            clock.take_back(self.turn);
+        }
+        let (color, ply) = (self.turn_color(), self.history.len());
+        self.emit(GameEvent::TurnChanged{ color: color, ply: ply });
+        true
+    }
+
     /// Makes a turn using the AI
     pub fn do_ai_turn(&mut self) -> Option<((Position, Position), (bool, bool))> {
+        self.do_ai_turn_with(None)
+    }
+
+    /// Makes a turn using the AI, 'prepared' is the result of a search the AI already did
+    /// for this position, e.g. while pondering
+    pub fn do_ai_turn_with(&mut self, prepared: Option<SearchResult>) -> Option<((Position, Position), (bool, bool))> {
+        // Use the prepared move if it is valid, otherwise ask the AI. A search of the whole
+        // position knows figures the AI must not see if the variant hides them.
+        let hidden = self.variant.hides_figures();
+        self.ai_turn(|game| match prepared {
+            Some(ref r) if !hidden && r.best.map_or(false, |m| game.possible_moves().contains(&m)) => (r.best.unwrap(), Some(r.clone())),
+            _ => game.choose_ai_move()
+        })
+    }
+
+    /// Returns the AI's move and the search that found it, if an external engine
+    /// fails the observers are told and the smart AI moves instead
+    fn choose_ai_move(&mut self) -> ((Position, Position), Option<SearchResult>) {
+        match ai::get_engine_move(self) {
+            Some(Ok(m)) => (m, None),
+            Some(Err(e)) => {
+                let color = self.turn_color();
+                self.emit(GameEvent::EngineFailed{ color: color, error: e.to_string() });
+                ai::get_smart_move_and_search(self)
+            },
+            None => ai::get_move_and_search(self)
+        }
+    }
+
+    /// Makes a turn with a move the AI chose elsewhere, e.g. an external engine in another
+    /// thread, the AI searches again if it is not valid
+    pub fn do_ai_turn_with_move(&mut self, chosen: ((Position, Position), Option<SearchResult>))
+                                -> Option<((Position, Position), (bool, bool))> {
+        self.ai_turn(|game| if game.possible_moves().contains(&chosen.0) { chosen } else { ai::get_move_and_search(game) })
+    }
 
+    /// Does the work of 'do_ai_turn_with', 'choose' returns the AI's move and the search that found it
+    fn ai_turn<F>(&mut self, choose: F) -> Option<((Position, Position), (bool, bool))>
+        where F: FnOnce(&mut ChessGame) -> ((Position, Position), Option<SearchResult>) {
         if !self.gameover {
-            if self.board.checkmate(&mut self.white_player, &mut self.black_player) {
-                self.gameover = true;
+            self.finish_if_stuck();
+            if self.gameover {
                 println!("Game is over");
                 return None
             }
 
-            let (from , to) = match self.turn {
-                true => {
-                    if self.white_player.ptype() != PlayerType::Human {
-                        self.white_player.get_ai_move(&self.board, &self.black_player)
-                    } else {
-                        return None
-                    }
-                },
-                false => {
-                    if self.black_player.ptype() != PlayerType::Human {
-                        self.black_player.get_ai_move(&self.board, &self.white_player)
-                    } else {
-                        return None
-                    }
+            if self.active_player().ptype() == PlayerType::Human {
+                return None
+            }
+
+            // Answer a draw offer of the opponent before moving
+            let white = self.turn;
+            if self.draw_offer.is_some() && self.draw_offer != Some(self.turn_color()) {
+                if ai::accepts_draw(self) {
+                    self.accept_draw(white);
+                    return None
                 }
-            };
+                self.decline_draw(white);
+            }
+
+            let ((from, to), result) = choose(self);
+            self.expected_reply = result.as_ref().and_then(|r| r.pv.get(1).cloned());
+            // The AI explains its move with what it knows
+            let known = self.seen_by(self.turn_color());
+            let explanation = result.as_ref().map(|r| Explanation::new(&known, r, &self.active_player().search_eval()));
+
+            // Give up a lost game and take a draw that is better than playing on
+            if let Some(ref r) = result {
+                if ai::wants_to_resign(r) {
+                    self.resign(white);
+                    return None
+                }
+                if self.draw_claim().is_some() && ai::prefers_draw(self.active_player(), r.score) {
+                    self.claim_draw();
+                    return None
+                }
+            }
 
-            return Some(((from, to), self.make_move(from , to)))
+            let outcome = self.make_move(from , to);
+            if let Some(last) = self.explanations.last_mut() {
+                *last = explanation;
+            }
+            self.finish_if_stuck();
+            if !self.gameover && result.map_or(false, |r| ai::offers_draw(self, &r)) {
+                self.offer_draw(white);
+            }
+            return Some(((from, to), outcome))
         }
         None
     }
@@ -130,19 +1031,19 @@ impl ChessGame {
     pub fn do_turn(&mut self, from: Position, to: Position) -> i8 {
 
         if !self.gameover {
-            if self.board.checkmate(&mut self.white_player, &mut self.black_player) {
-                self.gameover = true;
+            self.finish_if_stuck();
+            if self.gameover {
                 println!("Game is over");
                 return -1
             }
 
-            let result = match self.turn {
-                true => self.board.is_move_valid(from, to, &mut self.white_player, &mut self.black_player),
-                false => self.board.is_move_valid(from, to, &mut self.black_player, &mut self.white_player)
-            };
+            let variant = self.variant.clone();
+            let result = variant.is_legal(self, from, to);
 
             if result {
-                return match self.make_move(from, to) {
+                let outcome = self.make_move(from, to);
+                self.finish_if_stuck();
+                return match outcome {
                     (true, true) => 3,
                     (true, false) => 2,
                     (false, true) => 1,
@@ -155,3 +1056,172 @@ impl ChessGame {
         -1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(s: &str) -> Position {
+        Position::from_algebraic(s).unwrap()
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let fens = ["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                    "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+                    "8/8/4k3/8/2P5/8/5K2/8 w - - 12 40",
+                    "4k3/8/8/8/8/8/8/R3K3 b Q - 0 1"];
+        for fen in fens.iter() {
+            assert_eq!(ChessGame::from_fen(fen).unwrap().to_fen(), *fen);
+        }
+        assert_eq!(ChessGame::new().to_fen(), fens[0]);
+    }
+
+    #[test]
+    fn fen_after_moves() {
+        let mut game = ChessGame::new();
+        assert!(game.play(pos("e2"), pos("e4")));
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert!(game.play(pos("g8"), pos("f6")));
+        assert_eq!(game.to_fen(), "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2");
+    }
+
+    #[test]
+    fn castling() {
+        let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+        let mut game = ChessGame::from_fen(fen).unwrap();
+        assert!(game.play(pos("e1"), pos("g1")));
+        assert!(game.play(pos("e8"), pos("c8")));
+        assert_eq!(game.to_fen(), "2kr3r/pppppppp/8/8/8/8/PPPPPPPP/R4RK1 w - - 2 2");
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(game.to_fen(), fen);
+
+        // Not out of, through or into check, and not after the rook moved
+        let mut game = ChessGame::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for &(from, to) in [("h1", "h2"), ("a8", "a7"), ("h2", "h1"), ("a7", "a8")].iter() {
+            assert!(game.play(pos(from), pos(to)));
+        }
+        assert!(!game.play(pos("e1"), pos("g1")));
+        assert!(game.play(pos("e1"), pos("c1")));
+        assert!(!game.play(pos("e8"), pos("c8")));
+        assert!(game.play(pos("e8"), pos("g8")));
+        for fen in ["4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1", "4k3/8/8/8/8/8/2r3r1/R3K2R w KQ - 0 1"].iter() {
+            let mut game = ChessGame::from_fen(fen).unwrap();
+            assert!(!game.play(pos("e1"), pos("g1")));
+            assert!(!game.play(pos("e1"), pos("c1")));
+        }
+        let mut game = ChessGame::from_fen("4k3/8/8/8/8/8/3r4/R3K2R w KQ - 0 1").unwrap();
+        assert!(!game.play(pos("e1"), pos("c1")));
+        assert!(game.play(pos("e1"), pos("g1")));
+    }
+
+    #[test]
+    fn en_passant() {
+        let mut game = ChessGame::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(game.play(pos("d7"), pos("d5")));
+        assert!(game.play(pos("e5"), pos("d6")));
+        assert_eq!(game.to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 2");
+
+        // Only right after the double step
+        let mut game = ChessGame::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(game.play(pos("d7"), pos("d5")));
+        assert!(game.play(pos("e1"), pos("e2")));
+        assert!(game.play(pos("e8"), pos("e7")));
+        assert!(!game.play(pos("e5"), pos("d6")));
+
+        // Not if it leaves the king in check along the row
+        let mut game = ChessGame::from_fen("8/8/8/KPp4r/8/8/8/4k3 w - c6 0 1").unwrap();
+        assert!(!game.play(pos("b5"), pos("c6")));
+    }
+
+    #[test]
+    fn invalid_fens() {
+        let invalid = ["",
+                       "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w",
+                       "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x",
+                       "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w",
+                       "rnbqkbnr/pppppppp/45/8/8/8/PPPPPPPP/RNBQKBNR w",
+                       "rnbqkbnr/pppppppp/7/8/8/8/PPPPPPPP/RNBQKBNR w",
+                       "rnbqkbnrp/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w",
+                       "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQQBNR w",
+                       "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w",
+                       "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq z9"];
+        for fen in invalid.iter() {
+            assert!(ChessGame::from_fen(fen).is_err(), "'{}' was accepted", fen);
+        }
+    }
+
+    // Long runs of digits used to overflow the field counter
+    #[test]
+    fn fen_rank_overflow() {
+        let fen = format!("rnbqkbnr/pppppppp/{}/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1", "9".repeat(40));
+        assert!(ChessGame::from_fen(&fen).is_err());
+        assert!(ChessGame::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1R w - - 0 1").is_err());
+    }
+
+    fn outcome(game: &ChessGame) -> Option<(&'static str, &'static str)> {
+        game.outcome.as_ref().map(|o| (o.result, o.reason))
+    }
+
+    #[test]
+    fn resign() {
+        let mut game = ChessGame::new();
+        assert!(game.resign(false));
+        assert!(game.gameover);
+        assert_eq!(outcome(&game), Some(("1-0", "resignation")));
+        assert!(!game.resign(true));
+        assert!(!game.play(pos("e2"), pos("e4")));
+    }
+
+    #[test]
+    fn draw_offers() {
+        let mut game = ChessGame::new();
+        assert!(game.offer_draw(true));
+        assert!(!game.offer_draw(false));
+        // Players can not answer their own offer
+        assert!(!game.accept_draw(true));
+        assert!(game.decline_draw(false));
+        assert_eq!(game.draw_offer, None);
+
+        // Moving instead of answering declines the offer
+        assert!(game.offer_draw(true));
+        assert!(game.play(pos("e2"), pos("e4")));
+        assert!(game.play(pos("e7"), pos("e5")));
+        assert_eq!(game.draw_offer, None);
+
+        assert!(game.offer_draw(true));
+        assert!(game.accept_draw(false));
+        assert_eq!(outcome(&game), Some(("1/2-1/2", "agreement")));
+        assert_eq!(game.draw_offers, vec![(Color::White, 0), (Color::White, 0), (Color::White, 2)]);
+    }
+
+    #[test]
+    fn threefold_repetition() {
+        let mut game = ChessGame::new();
+        assert!(!game.claim_draw());
+        for _ in 0..2 {
+            assert_eq!(game.draw_claim(), None);
+            assert!(game.play(pos("g1"), pos("f3")));
+            assert!(game.play(pos("g8"), pos("f6")));
+            assert!(game.play(pos("f3"), pos("g1")));
+            assert!(game.play(pos("f6"), pos("g8")));
+        }
+        assert_eq!(game.repetitions(), 3);
+        assert!(game.undo());
+        assert_eq!(game.repetitions(), 2);
+        assert!(game.play(pos("f6"), pos("g8")));
+        assert!(game.claim_draw());
+        assert_eq!(outcome(&game), Some(("1/2-1/2", "threefold repetition")));
+    }
+
+    #[test]
+    fn fifty_move_rule() {
+        let mut game = ChessGame::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+        assert_eq!(game.draw_claim(), None);
+        assert!(game.play(pos("a1"), pos("a2")));
+        assert_eq!(game.draw_claim(), Some("fifty move rule"));
+        assert!(game.claim_draw());
+        assert_eq!(outcome(&game), Some(("1/2-1/2", "fifty move rule")));
+    }
+}
@@ -0,0 +1,193 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use chess::eval::{Eval, TERMS, TERM_NAMES};
+
+/// Profiles every game knows, in the format of personality files
+const BUILTIN: &'static str = "
+[balanced]
+
+[aggressive]
+# Storms forward, goes for captures and avoids draws
+bonus pawn_advance 1
+bonus center 2
+scale king_shelter 50
+contempt 5
+capture 150
+evade 50
+
+[defensive]
+# Keeps its king safe, runs from threats and is happy with a draw
+bonus king_shelter 3
+bonus pawn_advance -1
+contempt -5
+capture 50
+evade 200
+
+[positional]
+# Values good fields and the bishop pair over a quick gain
+bonus center 3
+bonus bishop_pair 5
+bonus pawn_advance 1
+bonus king_shelter 1
+scale pawn 90
+capture 75
+
+[material-greedy]
+# Grabs whatever it can and ignores everything else
+scale pawn 120
+scale knight 120
+scale bishop 120
+scale rook 120
+scale queen 120
+scale pawn_advance 50
+scale center 50
+scale king_shelter 50
+capture 200
+";
+
+/// A playing style of the AI: how it weighs the terms of the evaluation and
+/// how it searches
+#[derive(Debug, Clone, PartialEq)]
+pub struct Personality {
+    pub name: String,
+    // Factor in percent and amount in tenths of a pawn for each evaluation weight
+    pub scales: [i32; TERMS],
+    pub bonuses: [i32; TERMS],
+    // Tenths of a pawn a draw is worse than an equal position for the smart AI,
+    // negative if it likes draws
+    pub contempt: i32,
+    // Factors in percent for how much the dumb AI wants to capture and to evade captures
+    pub capture: i32,
+    pub evade: i32
+}
+
+impl Default for Personality {
+    fn default() -> Self {
+        Personality::new("balanced")
+    }
+}
+
+impl Personality {
+    /// A personality that plays like the AI without one
+    pub fn new(name: &str) -> Self {
+        Personality{ name: String::from(name), scales: [100; TERMS], bonuses: [0; TERMS], contempt: 0, capture: 100, evade: 100 }
+    }
+
+    /// Returns the evaluation with the weights changed by this personality
+    pub fn apply(&self, eval: &Eval) -> Eval {
+        let mut changed = *eval;
+        for i in 0..TERMS {
+            changed.weights[i] = eval.weights[i] * self.scales[i] / 100 + self.bonuses[i];
+        }
+        changed
+    }
+
+    /// Returns the built-in personalities
+    pub fn builtin() -> Vec<Personality> {
+        Personality::parse(BUILTIN).expect("built-in personalities are invalid")
+    }
+
+    /// Returns the personality with the name, 'list' is searched before the built-in ones
+    pub fn find(list: &[Personality], name: &str) -> Option<Personality> {
+        list.iter().cloned().chain(Personality::builtin()).find(|p| p.name == name)
+    }
+
+    /// Reads personalities from a file, see 'parse'
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Personality>> {
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+
+        Personality::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Reads personalities from text with a '[name]' line starting each one, followed by lines
+    /// 'scale <term> <percent>', 'bonus <term> <tenths of a pawn>', 'contempt <tenths of a pawn>',
+    /// 'capture <percent>' and 'evade <percent>'. Lines starting with '#' are comments.
+    pub fn parse(text: &str) -> Result<Vec<Personality>, String> {
+        let mut list: Vec<Personality> = Vec::new();
+
+        for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            if line.starts_with('[') && line.ends_with(']') {
+                list.push(Personality::new(line[1..line.len() - 1].trim()));
+                continue
+            }
+
+            let current = match list.last_mut() {
+                Some(p) => p,
+                None => return Err(format!("'{}' is not part of a personality", line))
+            };
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let number = |i: usize| parts.get(i).and_then(|v| v.parse::<i32>().ok())
+                                             .ok_or(format!("invalid line '{}'", line));
+            let term = |i: usize| parts.get(i).and_then(|t| TERM_NAMES.iter().position(|n| n == t))
+                                           .ok_or(format!("unknown term in '{}'", line));
+            match parts[0] {
+                "scale" => current.scales[try!(term(1))] = try!(number(2)),
+                "bonus" => current.bonuses[try!(term(1))] = try!(number(2)),
+                "contempt" => current.contempt = try!(number(1)),
+                "capture" => current.capture = try!(number(1)),
+                "evade" => current.evade = try!(number(1)),
+                _ => return Err(format!("unknown setting in '{}'", line))
+            }
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_personalities() {
+        let names: Vec<String> = Personality::builtin().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["balanced", "aggressive", "defensive", "positional", "material-greedy"]);
+        assert_eq!(Personality::find(&[], "balanced"), Some(Personality::default()));
+        assert_eq!(Personality::find(&[], "defensive").unwrap().contempt, -5);
+        assert_eq!(Personality::find(&[], "reckless"), None);
+    }
+
+    #[test]
+    fn parse_and_apply() {
+        let list = Personality::parse("# mine\n[bold]\nscale queen 150\nbonus center 2\ncontempt 3\n\
+                                       [aggressive]\nevade 10\n").unwrap();
+        assert_eq!(list.len(), 2);
+        let eval = list[0].apply(&Eval::default());
+        assert_eq!(eval.weights[4], 150);
+        assert_eq!(eval.weights[6], 2);
+        assert_eq!(eval.weights[0], Eval::default().weights[0]);
+        assert_eq!(list[0].contempt, 3);
+
+        // Own personalities come before the built-in ones of the same name
+        assert_eq!(Personality::find(&list, "aggressive").unwrap().evade, 10);
+
+        assert!(Personality::parse("contempt 3").is_err());
+        assert!(Personality::parse("[x]\nscale dragons 3").is_err());
+        assert!(Personality::parse("[x]\nbonus center lots").is_err());
+        assert!(Personality::parse("[x]\nmood happy").is_err());
+    }
+}
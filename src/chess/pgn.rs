@@ -0,0 +1,606 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::Arc;
+
+use chess::{ChessGame, START_FEN};
+use chess::logic::{Figure, Position};
+use chess::variant;
+
+/// A game read from a PGN file, the moves are in standard algebraic notation
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    // Comment after each move
+    pub comments: Vec<Option<String>>,
+    pub result: String
+}
+
+impl PgnGame {
+    /// Returns a game without tags and moves
+    fn empty() -> Self {
+        PgnGame{ tags: Vec::new(), moves: Vec::new(), comments: Vec::new(), result: String::from("*") }
+    }
+
+    /// Records a game, 'tags' come before the ones for the start position and the result.
+    /// Moves of the AI are commented with its explanation.
+    pub fn from_game(game: &ChessGame, tags: Vec<(String, String)>, result: &str) -> Self {
+        let mut tags = tags;
+        if !game.variant().is_standard() {
+            tags.push((String::from("Variant"), String::from(game.variant().name())));
+        }
+        if game.start_fen != START_FEN {
+            tags.push((String::from("SetUp"), String::from("1")));
+            tags.push((String::from("FEN"), game.start_fen.clone()));
+        }
+        tags.push((String::from("Result"), String::from(result)));
+
+        let mut replay = game.restart().unwrap_or_else(|_| ChessGame::new());
+        let mut moves = Vec::new();
+        for &(from, to) in game.history.iter() {
+            moves.push(move_to_san(&replay, from, to));
+            replay.play(from, to);
+        }
+
+        let comments = (0..moves.len()).map(|i| game.explanations.get(i).and_then(|e| e.as_ref()).map(|e| e.to_string()))
+                                       .collect();
+
+        PgnGame{ tags: tags, moves: moves, comments: comments, result: String::from(result) }
+    }
+
+    /// Returns the value of a tag
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|t| t.0 == name).map(|t| t.1.as_str())
+    }
+
+    /// Replaces the position, rules and moves of 'game' by those of the record, keeping the players
+    /// and their settings. The seed of the AI is taken from the 'Seed' tag if there is one, so
+    /// the AI goes on as in the saved game. 'game' is left as it is if a move can not be played.
+    pub fn load_into(&self, game: &mut ChessGame) -> Result<(), String> {
+        let rules = match self.tag("Variant") {
+            Some(name) => try!(variant::find(name).ok_or(format!("Unknown variant '{}'", name))),
+            None => Arc::new(variant::Standard)
+        };
+        let seed = match self.tag("Seed") {
+            Some(seed) => Some(try!(seed.parse::<u64>().map_err(|_| format!("Invalid seed '{}'", seed)))),
+            None => None
+        };
+        let start = try!(ChessGame::from_fen_variant(self.tag("FEN").unwrap_or(rules.start_fen()), rules));
+
+        // Check all moves before the game is changed
+        let mut moves = Vec::new();
+        let mut replay = start.clone();
+        for (i, san) in self.moves.iter().enumerate() {
+            match parse_san(&mut replay, san) {
+                Some((from, to)) if replay.play(from, to) => moves.push((from, to)),
+                _ => return Err(format!("Move {} '{}' is not possible", i / 2 + 1, san))
+            }
+        }
+
+        game.start_from(start);
+        if let Some(seed) = seed {
+            game.seed = seed;
+        }
+        for &(from, to) in moves.iter() {
+            game.play(from, to);
+        }
+        Ok(())
+    }
+}
+
+impl ::std::fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        for &(ref name, ref value) in self.tags.iter() {
+            try!(write!(f, "[{} \"{}\"]\n", name, value.replace('"', "'")));
+        }
+        try!(write!(f, "\n"));
+
+        // Move numbers continue from the start position
+        let start = self.tag("FEN").and_then(|fen| ChessGame::from_fen(fen).ok()).unwrap_or_else(ChessGame::new);
+        let mut number = start.fullmove_number;
+        let mut white = start.turn;
+        let mut tokens = Vec::new();
+        for (i, san) in self.moves.iter().enumerate() {
+            if white {
+                tokens.push(format!("{}.", number));
+            } else if i == 0 {
+                tokens.push(format!("{}...", number));
+            }
+            tokens.push(san.clone());
+            // Comments may be broken into lines like the moves
+            if let Some(&Some(ref comment)) = self.comments.get(i) {
+                let words = format!("{{{}}}", comment.replace('}', ")"));
+                tokens.extend(words.split_whitespace().map(String::from));
+            }
+            if !white {
+                number += 1;
+            }
+            white = !white;
+        }
+        tokens.push(self.result.clone());
+
+        // Keep lines short, as the PGN standard asks
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() + 1 > 79 {
+                try!(write!(f, "{}\n", line));
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        write!(f, "{}\n\n", line)
+    }
+}
+
+/// Returns whether a token ends a game
+fn is_result(token: &str) -> bool {
+    token == "1-0" || token == "0-1" || token == "1/2-1/2" || token == "*"
+}
+
+/// Reads all games of a PGN file, variations and annotations are skipped
+pub fn read_games(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::empty();
+    let mut started = false;
+    let mut in_brace = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && !in_brace {
+            // A tag like [Event "Casual game"]
+            let inner = line.trim_start_matches('[').trim_end_matches(']');
+            let mut parts = inner.splitn(2, ' ');
+            let name = parts.next().unwrap_or("").to_string();
+            let value = parts.next().unwrap_or("").trim().trim_matches('"').to_string();
+            if started {
+                games.push(game);
+                game = PgnGame::empty();
+                started = false;
+            }
+            game.tags.push((name, value));
+            continue
+        }
+        if line.starts_with('%') {
+            continue
+        }
+
+        // Rest of line comments end here unless they are inside a brace comment,
+        // the movetext is cleaned up below
+        let mut end = line.len();
+        for (i, c) in line.char_indices() {
+            match c {
+                '{' => in_brace = true,
+                '}' => in_brace = false,
+                ';' if !in_brace => {
+                    end = i;
+                    break
+                },
+                _ => ()
+            }
+        }
+        let line = &line[..end];
+        started = started || !line.is_empty();
+        game.moves.extend(line.split_whitespace().map(String::from));
+    }
+    if started || !game.tags.is_empty() {
+        games.push(game);
+    }
+
+    for game in games.iter_mut() {
+        let raw = game.moves.join(" ");
+        game.moves.clear();
+
+        let mut depth = 0;
+        let mut comment = false;
+        let mut token = String::new();
+        let mut tokens = Vec::new();
+        for c in raw.chars() {
+            match c {
+                '{' if depth == 0 && !comment => {
+                    if !token.is_empty() {
+                        tokens.push(token.clone());
+                        token.clear();
+                    }
+                    comment = true;
+                    token.push(c);
+                },
+                '}' if comment => {
+                    // The whole comment becomes one token starting with '{'
+                    comment = false;
+                    tokens.push(token.clone());
+                    token.clear();
+                },
+                _ if comment => token.push(c),
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ if depth > 0 => (),
+                c if c.is_whitespace() => {
+                    if !token.is_empty() {
+                        tokens.push(token.clone());
+                        token.clear();
+                    }
+                },
+                c => token.push(c)
+            }
+        }
+        if !token.is_empty() {
+            tokens.push(token);
+        }
+
+        for token in tokens {
+            if token.starts_with('{') {
+                if let Some(last) = game.comments.last_mut() {
+                    *last = Some(String::from(token[1..].trim()));
+                }
+                continue
+            }
+            if is_result(&token) {
+                game.result = token;
+                break
+            }
+            // Skip move numbers like '12.' or '12...' and annotations like '$1'
+            let mv = token.trim_start_matches(|c: char| c.is_digit(10)).trim_start_matches('.');
+            if !mv.is_empty() && !mv.starts_with('$') {
+                game.moves.push(String::from(mv));
+                game.comments.push(None);
+            }
+        }
+        if let Some(result) = game.tag("Result").map(String::from) {
+            if game.result == "*" && is_result(&result) {
+                game.result = result;
+            }
+        }
+    }
+
+    games
+}
+
+/// Returns the figure for a letter in algebraic notation
+fn figure_from_letter(c: char) -> Option<Figure> {
+    match c {
+        'K' => Some(Figure::King),
+        'Q' => Some(Figure::Queen),
+        'R' => Some(Figure::Rook),
+        'B' => Some(Figure::Bishop),
+        'N' => Some(Figure::Knight),
+        _ => None
+    }
+}
+
+/// Returns the letter of a figure in algebraic notation, pawns have none
+fn figure_letter(fig: Figure) -> &'static str {
+    match fig {
+        Figure::King => "K",
+        Figure::Queen => "Q",
+        Figure::Rook => "R",
+        Figure::Bishop => "B",
+        Figure::Knight => "N",
+        Figure::Pawn => ""
+    }
+}
+
+/// Finds the valid move written in standard algebraic notation like 'Nbd7', 'exd8=Q+',
+/// 'O-O' or the drop 'N@f3'
+pub fn parse_san(game: &mut ChessGame, san: &str) -> Option<(Position, Position)> {
+    let san = san.trim_end_matches(|c| c == '+' || c == '#' || c == '!' || c == '?');
+    // Castling is the king's move of two fields, some write it with zeros
+    let side = match san {
+        "O-O" | "0-0" => Some(6),
+        "O-O-O" | "0-0-0" => Some(2),
+        _ => None
+    };
+    if let Some(x) = side {
+        let king = game.active_player().king();
+        let castle = (king, Position::new(x, king.y));
+        return Some(castle).filter(|&m| king.x == 4 && game.possible_moves().contains(&m))
+    }
+    if let Some(i) = san.find('@') {
+        // A pawn drop may leave out the figure
+        let from = if i == 0 { Some(Position::reserve(Figure::Pawn)) } else { Position::from_algebraic(&san[..i + 1]) };
+        return match (from, Position::from_algebraic(&san[i + 1..])) {
+            (Some(from), Some(to)) if game.possible_moves().contains(&(from, to)) => Some((from, to)),
+            _ => None
+        }
+    }
+    // We always promote to a queen
+    let san = match san.find('=') {
+        Some(i) => &san[..i],
+        None => san.trim_end_matches(|c| c == 'Q' || c == 'R' || c == 'B' || c == 'N')
+    };
+    if san.len() < 2 || !san.is_char_boundary(san.len() - 2) {
+        return None
+    }
+
+    let to = match Position::from_algebraic(&san[san.len() - 2..]) {
+        Some(to) => to,
+        None => return None
+    };
+    let mut rest = san[..san.len() - 2].chars().filter(|&c| c != 'x' && c != '-').peekable();
+    let fig = match rest.peek().and_then(|&c| figure_from_letter(c)) {
+        Some(fig) => {
+            rest.next();
+            fig
+        },
+        None => Figure::Pawn
+    };
+    let (mut file, mut row) = (None, None);
+    for c in rest {
+        match c {
+            'a'...'h' => file = Some(c as u8 - b'a'),
+            '1'...'8' => row = Some(c as u8 - b'1'),
+            _ => return None
+        }
+    }
+
+    let board = game.board.clone();
+    let mut candidates = game.possible_moves()
+                             .into_iter()
+                             .filter(|&(f, t)| {
+                                 t == to && f.reserve_figure().is_none() && board.get_figure(f) == Some(fig) &&
+                                 file.map_or(true, |x| f.x == x) && row.map_or(true, |y| f.y == y)
+                             });
+    match (candidates.next(), candidates.next()) {
+        (Some(m), None) => Some(m),
+        _ => None
+    }
+}
+
+/// Returns a valid move in standard algebraic notation, 'game' is the game before the move
+pub fn move_to_san(game: &ChessGame, from: Position, to: Position) -> String {
+    let mut game = game.clone();
+    let mut san = if from.reserve_figure().is_some() {
+        // Drops are written with the figure and '@' like 'N@f3'
+        format!("{}{}", from.to_algebraic(), to.to_algebraic())
+    } else {
+        match board_move_to_san(&mut game, from, to) {
+            Some(san) => san,
+            None => return String::from("--")
+        }
+    };
+
+    if game.play(from, to) && game.in_check() {
+        san.push(if game.possible_moves().is_empty() { '#' } else { '+' });
+    }
+    san
+}
+
+/// Returns a move of a figure on the board in standard algebraic notation without check or mate
+fn board_move_to_san(game: &mut ChessGame, from: Position, to: Position) -> Option<String> {
+    let fig = match game.board.get_figure(from) {
+        Some(fig) => fig,
+        None => return None
+    };
+    // A pawn moving sideways always captures, en passant onto an empty field
+    let capture = !game.board.is_empty(to) || fig == Figure::Pawn && from.x != to.x;
+    if fig == Figure::King && (from.x as i8 - to.x as i8).abs() == 2 {
+        return Some(String::from(if to.x > from.x { "O-O" } else { "O-O-O" }))
+    }
+
+    let mut san = String::from(figure_letter(fig));
+    if fig == Figure::Pawn {
+        if capture {
+            san.push((b'a' + from.x) as char);
+        }
+    } else {
+        // Name the start file or row if another figure of the same kind can move there as well
+        let board = game.board.clone();
+        let others: Vec<Position> = game.possible_moves()
+                                        .into_iter()
+                                        .filter(|&(f, t)| t == to && f != from && f.reserve_figure().is_none() && board.get_figure(f) == Some(fig))
+                                        .map(|(f, _)| f)
+                                        .collect();
+        if !others.is_empty() {
+            if others.iter().all(|o| o.x != from.x) {
+                san.push((b'a' + from.x) as char);
+            } else if others.iter().all(|o| o.y != from.y) {
+                san.push((b'1' + from.y) as char);
+            } else {
+                san.push_str(&from.to_algebraic());
+            }
+        }
+    }
+    if capture {
+        san.push('x');
+    }
+    san.push_str(&to.to_algebraic());
+    if fig == Figure::Pawn && (to.y == 0 || to.y == 7) {
+        san.push_str("=Q");
+    }
+    Some(san)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::rand::Rng;
+
+    fn pos(s: &str) -> Position {
+        Position::from_algebraic(s).unwrap()
+    }
+
+    /// Plays moves in standard algebraic notation
+    fn play(game: &mut ChessGame, moves: &[&str]) {
+        for san in moves.iter() {
+            let (from, to) = parse_san(game, san).expect(san);
+            assert!(game.play(from, to), "{} is not possible", san);
+        }
+    }
+
+    #[test]
+    fn parse_moves() {
+        let mut game = ChessGame::new();
+        assert_eq!(parse_san(&mut game, "e4"), Some((pos("e2"), pos("e4"))));
+        assert_eq!(parse_san(&mut game, "Nf3"), Some((pos("g1"), pos("f3"))));
+        assert_eq!(parse_san(&mut game, "Ng1-f3!?"), Some((pos("g1"), pos("f3"))));
+        assert_eq!(parse_san(&mut game, "e5"), None);
+        assert_eq!(parse_san(&mut game, "Nd2"), None);
+        assert_eq!(parse_san(&mut game, "O-O"), None);
+        assert_eq!(parse_san(&mut game, "0-0-0"), None);
+        assert_eq!(parse_san(&mut game, "x"), None);
+
+        play(&mut game, &["e4", "d5"]);
+        assert_eq!(parse_san(&mut game, "exd5"), Some((pos("e4"), pos("d5"))));
+        assert_eq!(parse_san(&mut game, "ed5"), Some((pos("e4"), pos("d5"))));
+    }
+
+    #[test]
+    fn parse_ambiguous_moves() {
+        let mut game = ChessGame::from_fen("4k3/8/8/8/8/8/8/RN2KN1R w - - 0 1").unwrap();
+        assert_eq!(parse_san(&mut game, "Nd2"), None);
+        assert_eq!(parse_san(&mut game, "Nbd2"), Some((pos("b1"), pos("d2"))));
+        assert_eq!(parse_san(&mut game, "Nfd2"), Some((pos("f1"), pos("d2"))));
+        assert_eq!(parse_san(&mut game, "Ng3"), Some((pos("f1"), pos("g3"))));
+
+        let mut game = ChessGame::from_fen("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(parse_san(&mut game, "Ra3"), None);
+        assert_eq!(parse_san(&mut game, "R1a3"), Some((pos("a1"), pos("a3"))));
+        assert_eq!(parse_san(&mut game, "R5a3+"), Some((pos("a5"), pos("a3"))));
+    }
+
+    #[test]
+    fn parse_promotion() {
+        let mut game = ChessGame::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(parse_san(&mut game, "a8=Q+"), Some((pos("a7"), pos("a8"))));
+        assert_eq!(parse_san(&mut game, "axb8Q"), Some((pos("a7"), pos("b8"))));
+    }
+
+    #[test]
+    fn write_moves() {
+        let game = ChessGame::new();
+        assert_eq!(move_to_san(&game, pos("g1"), pos("f3")), "Nf3");
+        assert_eq!(move_to_san(&game, pos("e2"), pos("e4")), "e4");
+
+        let game = ChessGame::from_fen("4k3/8/8/R7/8/8/8/RN2KN1R w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&game, pos("b1"), pos("d2")), "Nbd2");
+        assert_eq!(move_to_san(&game, pos("f1"), pos("g3")), "Ng3");
+        assert_eq!(move_to_san(&game, pos("a1"), pos("a3")), "R1a3");
+        assert_eq!(move_to_san(&game, pos("a5"), pos("a8")), "Ra8+");
+
+        let game = ChessGame::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&game, pos("a1"), pos("a8")), "Ra8#");
+
+        let game = ChessGame::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&game, pos("a7"), pos("b8")), "axb8=Q+");
+    }
+
+    #[test]
+    fn castling_and_en_passant() {
+        let mut game = ChessGame::from_fen("r3k2r/8/8/8/3p4/8/4P3/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(parse_san(&mut game, "O-O"), Some((pos("e1"), pos("g1"))));
+        assert_eq!(parse_san(&mut game, "0-0-0"), Some((pos("e1"), pos("c1"))));
+        assert_eq!(move_to_san(&game, pos("e1"), pos("g1")), "O-O");
+        assert_eq!(move_to_san(&game, pos("e1"), pos("c1")), "O-O-O");
+
+        play(&mut game, &["e4"]);
+        assert_eq!(parse_san(&mut game, "dxe3"), Some((pos("d4"), pos("e3"))));
+        assert_eq!(move_to_san(&game, pos("d4"), pos("e3")), "dxe3");
+        play(&mut game, &["dxe3", "O-O-O"]);
+        assert_eq!(game.to_fen(), "r3k2r/8/8/8/8/4p3/8/2KR3R b kq - 1 2");
+    }
+
+    #[test]
+    fn read_tags_comments_and_variations() {
+        let text = "[Event \"Test\"]\n[Result \"1-0\"]\n\n\
+                    1. e4 {best by test} e5 (1... c5 2. Nf3) 2. Nf3 $1 Nc6 ; rest of line\n\
+                    3. Bb5 1-0\n\n\
+                    [Event \"Second\"]\n\n1. d4 d5 *\n";
+        let games = read_games(text);
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].tag("Event"), Some("Test"));
+        assert_eq!(games[0].moves, vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert_eq!(games[0].comments[0], Some(String::from("best by test")));
+        assert_eq!(games[0].comments[1], None);
+        assert_eq!(games[0].result, "1-0");
+
+        assert_eq!(games[1].tag("Event"), Some("Second"));
+        assert_eq!(games[1].moves, vec!["d4", "d5"]);
+        assert_eq!(games[1].result, "*");
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let mut game = ChessGame::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 7").unwrap();
+        play(&mut game, &["e5", "e4", "Nc6", "Nf3", "a6"]);
+
+        let pgn = PgnGame::from_game(&game, vec![(String::from("Event"), String::from("Round \"trip\""))], "*");
+        let text = pgn.to_string();
+        assert!(text.contains("[Event \"Round 'trip'\"]"));
+        assert!(text.contains("[SetUp \"1\"]"));
+        assert!(text.contains("7... e5 8. e4 Nc6 9. Nf3 a6 *"));
+
+        let read = read_games(&text).remove(0);
+        assert_eq!(read.moves, pgn.moves);
+        assert_eq!(read.tag("FEN"), Some(game.start_fen.as_str()));
+        let mut replay = ChessGame::from_fen(read.tag("FEN").unwrap()).unwrap();
+        play(&mut replay, &read.moves.iter().map(|m| m.as_str()).collect::<Vec<_>>());
+        assert_eq!(replay.to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn load_restores_seed() {
+        let mut game = ChessGame::new();
+        game.seed = 1234567890123;
+        play(&mut game, &["e4", "e5", "Nf3"]);
+        let tags = vec![(String::from("Seed"), game.seed.to_string())];
+        let text = PgnGame::from_game(&game, tags, "*").to_string();
+
+        let mut loaded = ChessGame::new();
+        read_games(&text)[0].load_into(&mut loaded).unwrap();
+        assert_eq!(loaded.seed, game.seed);
+        assert_eq!(loaded.history, game.history);
+        assert_eq!(loaded.to_fen(), game.to_fen());
+        // The AI decides as it would have in the saved game
+        assert_eq!(loaded.rng().gen::<u64>(), game.rng().gen::<u64>());
+    }
+
+    #[test]
+    fn load_variant_and_position() {
+        let mut game = ChessGame::from_fen_variant("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", variant::find("koth").unwrap()).unwrap();
+        play(&mut game, &["e4", "Kd7"]);
+        let text = PgnGame::from_game(&game, Vec::new(), "*").to_string();
+
+        let mut loaded = ChessGame::new();
+        let seed = loaded.seed;
+        read_games(&text)[0].load_into(&mut loaded).unwrap();
+        assert_eq!(loaded.variant().name(), "kingofthehill");
+        assert_eq!(loaded.start_fen, game.start_fen);
+        assert_eq!(loaded.to_fen(), game.to_fen());
+        // Without a seed tag the game keeps its own
+        assert_eq!(loaded.seed, seed);
+    }
+
+    #[test]
+    fn load_errors_keep_the_game() {
+        let mut game = ChessGame::new();
+        play(&mut game, &["d4"]);
+        let fen = game.to_fen();
+        for text in ["1. e4 e4 *", "[Seed \"x\"]\n\n1. e4 *", "[Variant \"chess960\"]\n\n1. e4 *"].iter() {
+            assert!(read_games(text)[0].load_into(&mut game).is_err(), "{} was loaded", text);
+            assert_eq!(game.to_fen(), fen);
+        }
+    }
+}
@@ -20,24 +20,55 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+//! Players of a side: humans, the Dumb AI, the Smart AI with its settings or an external engine.
+
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-use chess::logic::{Color, Board, Position};
+use chess::logic::{Color, Board, Figure, Position};
+use chess::ai::Difficulty;
+use chess::polyglot::Book;
+use chess::syzygy::Tablebase;
+use chess::engine::ExternalEngine;
+use chess::search::{SearchContext, SearchLimits};
+use chess::eval::Eval;
+use chess::personality::Personality;
 
 /// Types the player can have
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum PlayerType {
     Human,
     Dumb,
-    Smart
+    Smart,
+    // An external UCI engine, see 'Player::set_engine'
+    Engine
 }
 
+/// Number of plies for which an opening book is used by default
+pub const DEFAULT_BOOK_DEPTH: usize = 16;
+
 /// The player
 #[derive(Debug)]
 pub struct Player {
     ptype: PlayerType,
     color: Color,
+    // Whether the king, the queenside rook and the kingside rook have not moved yet
     castling: [bool; 3],
+    limits: SearchLimits,
+    difficulty: Difficulty,
+    book: Option<Arc<Book>>,
+    book_depth: usize,
+    tablebase: Option<Arc<Tablebase>>,
+    engine: Option<Arc<Mutex<ExternalEngine>>>,
+    ponder: bool,
+    threads: usize,
+    eval: Eval,
+    personality: Personality,
+    // Captured figures the player may drop in Crazyhouse
+    reserve: Vec<Figure>,
+    // Positions of figures that were pawns before they were promoted
+    promoted: Vec<Position>,
+    /// Positions of the player's figures by name ('king', 'queen', ...)
     pub figures: HashMap<String, Vec<Position>>
 }
 
@@ -70,7 +101,11 @@ impl Player {
         // Rooks
         f.insert("rook".to_string(), vec![Position::new(0, 7), Position::new(7, 7)]);
 
-        Player { ptype: p, color: c, figures: f, castling: [true, true, true] }
+        Player { ptype: p, color: c, figures: f, castling: [true, true, true], limits: SearchLimits::default(),
+                 difficulty: Difficulty::Strongest, book: None, book_depth: DEFAULT_BOOK_DEPTH,
+                 tablebase: None, engine: None, ponder: false,
+                 threads: 1, eval: Eval::default(), personality: Personality::default(),
+                 reserve: Vec::new(), promoted: Vec::new() }
     }
 
     /// Create a new white player
@@ -93,7 +128,30 @@ impl Player {
         // Rooks
         f.insert("rook".to_string(), vec![Position::new(0, 0), Position::new(7, 0)]);
 
-        Player { ptype: p, color: c, figures: f, castling: [true, true, true] }
+        Player { ptype: p, color: c, figures: f, castling: [true, true, true], limits: SearchLimits::default(),
+                 difficulty: Difficulty::Strongest, book: None, book_depth: DEFAULT_BOOK_DEPTH,
+                 tablebase: None, engine: None, ponder: false,
+                 threads: 1, eval: Eval::default(), personality: Personality::default(),
+                 reserve: Vec::new(), promoted: Vec::new() }
+    }
+
+    /// Create a player owning all figures of color 'c' on the board, castling rights are lost
+    /// for every king or rook that is not on its start position
+    pub fn from_board(p: PlayerType, c: Color, board: &Board) -> Self {
+        let mut player = Player::new(p, c);
+        player.figures.clear();
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let pos = Position::new(x, y);
+                if board.get_figure_color(pos) == Some(c) {
+                    let name = board.get_figure(pos).unwrap().name();
+                    player.figures.entry(name).or_insert_with(Vec::new).push(pos);
+                }
+            }
+        }
+
+        player
     }
 
     /// Return player color
@@ -111,8 +169,201 @@ impl Player {
         self.ptype = p;
     }
 
+    /// Return the limits used when this player's AI searches for a move
+    pub fn search_limits(&self) -> SearchLimits {
+        self.limits
+    }
+
+    /// Set the limits used when this player's AI searches for a move
+    pub fn set_search_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    /// Return the difficulty of this player's smart AI
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Set the difficulty of this player's smart AI
+    pub fn set_difficulty(&mut self, d: Difficulty) {
+        self.difficulty = d;
+    }
+
+    /// Return the opening book of this player's AI
+    pub fn book(&self) -> Option<Arc<Book>> {
+        self.book.clone()
+    }
+
+    /// Set the opening book of this player's AI
+    pub fn set_book(&mut self, book: Option<Arc<Book>>) {
+        self.book = book;
+    }
+
+    /// Return the number of plies into the game for which the opening book is used
+    pub fn book_depth(&self) -> usize {
+        self.book_depth
+    }
+
+    /// Set the number of plies into the game for which the opening book is used
+    pub fn set_book_depth(&mut self, depth: usize) {
+        self.book_depth = depth;
+    }
+
+    /// Return the endgame tablebase of this player's AI
+    pub fn tablebase(&self) -> Option<Arc<Tablebase>> {
+        self.tablebase.clone()
+    }
+
+    /// Set the endgame tablebase of this player's AI
+    pub fn set_tablebase(&mut self, tb: Option<Arc<Tablebase>>) {
+        self.tablebase = tb;
+    }
+
+    /// Return the external engine used when the player type is Engine
+    pub fn engine(&self) -> Option<Arc<Mutex<ExternalEngine>>> {
+        self.engine.clone()
+    }
+
+    /// Set the external engine used when the player type is Engine
+    pub fn set_engine(&mut self, engine: Option<Arc<Mutex<ExternalEngine>>>) {
+        self.engine = engine;
+    }
+
+    /// Return whether this player's smart AI thinks on the opponent's time
+    pub fn ponder(&self) -> bool {
+        self.ponder
+    }
+
+    /// Set whether this player's smart AI thinks on the opponent's time
+    pub fn set_ponder(&mut self, ponder: bool) {
+        self.ponder = ponder;
+    }
+
+    /// Return the number of threads this player's smart AI searches with
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Set the number of threads this player's smart AI searches with
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Return the evaluation this player's smart AI uses
+    pub fn eval(&self) -> Eval {
+        self.eval
+    }
+
+    /// Set the evaluation this player's smart AI uses
+    pub fn set_eval(&mut self, eval: Eval) {
+        self.eval = eval;
+    }
+
+    /// Return the personality of this player's AI
+    pub fn personality(&self) -> &Personality {
+        &self.personality
+    }
+
+    /// Set the personality of this player's AI
+    pub fn set_personality(&mut self, personality: Personality) {
+        self.personality = personality;
+    }
+
+    /// Return the evaluation this player's smart AI searches with, changed by its personality
+    pub fn search_eval(&self) -> Eval {
+        self.personality.apply(&self.eval)
+    }
+
+    /// Return a context for a search of this player's smart AI
+    pub fn search_context(&self) -> SearchContext {
+        SearchContext{ threads: self.threads, eval: self.search_eval(), contempt: self.personality.contempt,
+                       .. SearchContext::new() }
+    }
+
+    /// Return the row the player's king and rooks start on
+    fn home_row(&self) -> u8 {
+        if self.color == Color::White { 0 } else { 7 }
+    }
+
+    /// Gives up castling if 'pos' is the start position of the king or one of the rooks
+    pub fn lose_castling(&mut self, pos: Position) {
+        if pos.y == self.home_row() {
+            match pos.x {
+                4 => self.castling[0] = false,
+                0 => self.castling[1] = false,
+                7 => self.castling[2] = false,
+                _ => ()
+            }
+        }
+    }
+
+    /// Takes over the figures, reserve and castling rights of another player, keeping our type and AI settings
+    pub fn set_figures_from(&mut self, source: &Player) {
+        self.figures = source.figures.clone();
+        self.castling = source.castling;
+        self.reserve = source.reserve.clone();
+        self.promoted = source.promoted.clone();
+    }
+
+    /// Returns the captured figures the player may drop
+    pub fn reserve(&self) -> &[Figure] {
+        &self.reserve
+    }
+
+    /// Puts a captured figure into the reserve
+    pub fn add_to_reserve(&mut self, fig: Figure) {
+        self.reserve.push(fig);
+    }
+
+    /// Takes a figure out of the reserve and puts it on the empty field 'pos'
+    pub fn drop_figure(&mut self, fig: Figure, pos: Position) {
+        if let Some(i) = self.reserve.iter().position(|&f| f == fig) {
+            self.reserve.remove(i);
+            self.reverse_capture(fig.name(), pos);
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Returns whether the figure at 'pos' was a pawn before it was promoted
+    pub fn is_promoted(&self, pos: Position) -> bool {
+        self.promoted.contains(&pos)
+    }
+
+    /// Remembers that the figure at 'pos' was a pawn before it was promoted
+    pub fn set_promoted(&mut self, pos: Position) {
+        if !self.is_promoted(pos) {
+            self.promoted.push(pos);
+        }
+    }
+
+    /// Forgets about the promotion of the figure at 'pos' when it is captured,
+    /// returns whether it was promoted
+    pub fn forget_promotion(&mut self, pos: Position) -> bool {
+        let before = self.promoted.len();
+        self.promoted.retain(|&p| p != pos);
+        self.promoted.len() != before
+    }
+
+    /// Sets whether the player may still castle kingside and queenside
+    pub fn set_castling_rights(&mut self, kingside: bool, queenside: bool) {
+        self.castling = [kingside || queenside, queenside, kingside];
+    }
+
+    /// Returns whether the player may still castle kingside and queenside
+    pub fn castling_rights(&self) -> (bool, bool) {
+        let row = self.home_row();
+        let at_home = |name: &str, x: u8| {
+            self.figures.get(name).map_or(false, |v| v.contains(&Position::new(x, row)))
+        };
+        let king = self.castling[0] && at_home("king", 4);
+
+        (king && self.castling[2] && at_home("rook", 7), king && self.castling[1] && at_home("rook", 0))
+    }
+
     pub fn upgrade_pawn(&mut self, pos: Position) {
         self.capture("pawn".to_string(), pos);
+        self.set_promoted(pos);
 
         let mut found = false;
         if let Some(mut positions) = self.figures.get_mut("queen") {
@@ -125,6 +376,11 @@ impl Player {
         }
     }
 
+    /// Returns whether the player still has a king, only in Atomic chess it can be lost
+    pub fn has_king(&self) -> bool {
+        self.figures.contains_key("king")
+    }
+
     /// Return the player's king which should always be there because one
     /// cannot actually 'capture' a king
     pub fn king(&self) -> Position {
@@ -134,13 +390,14 @@ impl Player {
     /// Returns a vector of possible moves for all figures of the player
     pub fn get_possible_moves(&mut self, board: &mut Board, opponent: &mut Player) -> Vec<(Position, Position)> {
         let mut moves = Vec::new();
+        let mut me = self.clone();
 
         for v in self.figures.values() {
             for i in 0..v.len() {
                 for outer in 0..8 {
                     for inner in 0..8 {
                         let try = Position::new(inner, outer);
-                        if board.is_move_valid(v[i], try, &mut self.clone(), opponent) {
+                        if board.is_move_valid(v[i], try, &mut me, opponent) {
                             moves.push((v[i], try));
                         }
                     }
@@ -150,13 +407,11 @@ impl Player {
         moves
     }
 
-    /// If the player is an AI this returns a valid move
-    pub fn get_ai_move(&self, board: &Board, other: &Player) -> (Position, Position) {
-        return super::ai::get_move(board, self, other);
-    }
-
     /// Move a figure from 'before' to 'after'
     pub fn move_figure(&mut self, before: Position, after: Position) {
+        if let Some(p) = self.promoted.iter_mut().find(|p| **p == before) {
+            *p = after;
+        }
         for mut v in self.figures.values_mut() {
             for i in 0..v.len() {
                 if v[i] == before {
@@ -226,13 +481,30 @@ impl ::std::clone::Clone for Player {
         for (name, pos) in self.figures.iter() {
             f.insert(name.clone(), pos.clone());
         }
-        Player{ figures: f, color: self.color, ptype: self.ptype, castling: self.castling }
+        Player{ figures: f, color: self.color, ptype: self.ptype, castling: self.castling,
+                limits: self.limits, difficulty: self.difficulty, book: self.book.clone(), book_depth: self.book_depth,
+                tablebase: self.tablebase.clone(), engine: self.engine.clone(),
+                ponder: self.ponder, threads: self.threads,
+                eval: self.eval, personality: self.personality.clone(),
+                reserve: self.reserve.clone(), promoted: self.promoted.clone() }
     }
 
     fn clone_from(&mut self, source: &Self) {
         self.figures.clear();
         self.color = source.color;
         self.ptype = source.ptype;
+        self.limits = source.limits;
+        self.difficulty = source.difficulty;
+        self.book = source.book.clone();
+        self.book_depth = source.book_depth;
+        self.tablebase = source.tablebase.clone();
+        self.engine = source.engine.clone();
+        self.ponder = source.ponder;
+        self.threads = source.threads;
+        self.eval = source.eval;
+        self.personality = source.personality.clone();
+        self.reserve = source.reserve.clone();
+        self.promoted = source.promoted.clone();
 
         for (name, pos) in source.figures.iter() {
             self.figures.insert(name.clone(), pos.clone());
@@ -0,0 +1,134 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chess::ChessGame;
+use chess::ai;
+use chess::logic::Position;
+use chess::search::{SearchContext, SearchLimits, SearchResult};
+
+/// A search running in the background on the opponent's time. It searches the position
+/// after the reply the AI expects, so if the opponent plays it the search is already done.
+#[derive(Debug)]
+pub struct Ponder {
+    expected: (Position, Position),
+    context: SearchContext,
+    start: Instant,
+    handle: JoinHandle<SearchResult>
+}
+
+impl Ponder {
+    /// Starts pondering for the AI of the player who is not to move in 'game', assuming the
+    /// player to move plays 'expected'. Returns None if 'expected' is not a valid move or
+    /// the variant hides figures, as the search would see the whole position.
+    pub fn start(game: &ChessGame, expected: (Position, Position)) -> Option<Self> {
+        if game.variant().hides_figures() {
+            return None
+        }
+        let mut after = game.clone();
+        if !after.play(expected.0, expected.1) || after.possible_moves().is_empty() {
+            return None
+        }
+
+        // Only the time limit is left out, it starts counting when the opponent moved
+        let limits = SearchLimits{ movetime: None, .. after.active_player().search_limits() };
+        let difficulty = after.active_player().difficulty();
+        let context = after.active_player().search_context();
+        let thread_context = context.clone();
+        let handle = thread::spawn(move || {
+            let result = ai::smart_search(&after, difficulty, limits, &thread_context, |_| ());
+            // Tell the waiting side that we are done
+            thread_context.stop();
+            result
+        });
+
+        Some(Ponder{ expected: expected, context: context, start: Instant::now(), handle: handle })
+    }
+
+    /// Returns the reply the search assumes
+    pub fn expected(&self) -> (Position, Position) {
+        self.expected
+    }
+
+    /// The opponent played the expected reply: the search may go on until it used as much
+    /// time as 'limits' allow for the move, counting the time it already pondered
+    pub fn hit(self, limits: &SearchLimits) -> Option<SearchResult> {
+        while !self.context.stopped() && limits.movetime.map_or(true, |t| self.start.elapsed() < t) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        self.context.stop();
+        self.handle.join().ok()
+    }
+
+    /// The opponent played another move, stop the search and throw it away
+    pub fn miss(self) {
+        self.context.stop();
+        self.handle.join().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(from: &str, to: &str) -> (Position, Position) {
+        (Position::from_algebraic(from).unwrap(), Position::from_algebraic(to).unwrap())
+    }
+
+    #[test]
+    fn hit_searches_the_expected_position() {
+        // After Kh1 black can mate with Ra1
+        let mut game = ChessGame::from_fen("r5k1/8/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        game.black_player.set_search_limits(SearchLimits::depth(3));
+
+        let ponder = Ponder::start(&game, mv("g1", "h1")).unwrap();
+        assert_eq!(ponder.expected(), mv("g1", "h1"));
+        let result = ponder.hit(&SearchLimits::movetime(Duration::from_millis(2000))).unwrap();
+        assert_eq!(result.best, Some(mv("a8", "a1")));
+        assert_eq!(result.mate_in(), Some(1));
+    }
+
+    #[test]
+    fn miss_stops_the_search() {
+        let mut game = ChessGame::new();
+        game.black_player.set_search_limits(SearchLimits::infinite());
+        let ponder = Ponder::start(&game, mv("e2", "e4")).unwrap();
+        let start = Instant::now();
+        ponder.miss();
+        assert!(start.elapsed() < Duration::from_millis(2000));
+    }
+
+    #[test]
+    fn invalid_reply() {
+        assert!(Ponder::start(&ChessGame::new(), mv("e2", "e5")).is_none());
+    }
+
+    // The search would see the figures the variant hides
+    #[test]
+    fn hidden_figures() {
+        let variant = ::chess::variant::find("darkchess").unwrap();
+        let game = ChessGame::from_fen_variant(::chess::START_FEN, variant).unwrap();
+        assert!(Ponder::start(&game, mv("e2", "e4")).is_none());
+    }
+}
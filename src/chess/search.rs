@@ -0,0 +1,738 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+extern crate rand;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use self::rand::Rng;
+
+use chess::ChessGame;
+use chess::ai::figure_value;
+use chess::logic::{Color, Figure, Position, RESERVE_FILE, RESERVE_FIGURES};
+use chess::eval::Eval;
+
+/// Score of a checkmate, reduced by the number of plies it takes to get there
+pub const MATE: i32 = 100000;
+
+/// Deepest search we will ever try, also used when no limit is given
+pub const MAX_DEPTH: u8 = 64;
+
+/// Moves we assume are left in the game when the clock has no move count
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Time kept on the clock so we do not lose on time due to overhead
+const CLOCK_RESERVE: u64 = 50;
+
+/// Limits of a search, the search stops as soon as one of them is reached
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>
+}
+
+impl SearchLimits {
+    /// Limits that never stop the search on their own
+    pub fn infinite() -> Self {
+        SearchLimits{ depth: None, nodes: None, movetime: None }
+    }
+
+    /// Search to a fixed depth
+    pub fn depth(depth: u8) -> Self {
+        SearchLimits{ depth: Some(depth), .. SearchLimits::infinite() }
+    }
+
+    /// Search a fixed number of nodes
+    pub fn nodes(nodes: u64) -> Self {
+        SearchLimits{ nodes: Some(nodes), .. SearchLimits::infinite() }
+    }
+
+    /// Search for a fixed amount of time
+    pub fn movetime(time: Duration) -> Self {
+        SearchLimits{ movetime: Some(time), .. SearchLimits::infinite() }
+    }
+
+    /// Allocate a time budget for one move from the remaining clock time
+    pub fn from_clock(remaining: Duration, increment: Duration, moves_to_go: Option<u32>) -> Self {
+        let remaining = millis(remaining);
+        let increment = millis(increment);
+        let moves = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1) as u64;
+
+        // Spend an equal share of the clock plus most of the increment,
+        // but never more than half of what is left
+        let usable = remaining.saturating_sub(CLOCK_RESERVE);
+        let budget = (usable / moves + increment * 3 / 4).min(usable / 2).max(1);
+
+        SearchLimits::movetime(Duration::from_millis(budget))
+    }
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits::movetime(Duration::from_millis(1000))
+    }
+}
+
+/// Result of a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub best: Option<(Position, Position)>,
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,
+    pub time: Duration,
+    // Principal variation, the line both sides are expected to play starting with 'best'
+    pub pv: Vec<(Position, Position)>
+}
+
+impl SearchResult {
+    /// Returns the number of moves until mate if the score is a mate score,
+    /// negative if the side to move gets mated
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score >= MATE - MAX_DEPTH as i32 {
+            Some((MATE - self.score + 1) / 2)
+        } else if self.score <= -MATE + MAX_DEPTH as i32 {
+            Some(-(MATE + self.score) / 2)
+        } else {
+            None
+        }
+    }
+}
+
+/// Shared state of a search, which lets other threads stop it
+#[derive(Debug, Clone)]
+pub struct SearchContext {
+    pub stop: Arc<AtomicBool>,
+    pub tt: Option<Arc<TranspositionTable>>,
+    // Number of threads searching the root together, only the first one reports
+    pub threads: usize,
+    pub eval: Eval,
+    // Tenths of a pawn a draw is worse than an equal position for the side to move at the root
+    pub contempt: i32
+}
+
+impl SearchContext {
+    /// A context without transposition table for one thread
+    pub fn new() -> Self {
+        SearchContext{ stop: Arc::new(AtomicBool::new(false)), tt: None, threads: 1, eval: Eval::default(), contempt: 0 }
+    }
+
+    /// A context using the given transposition table
+    pub fn with_table(tt: Arc<TranspositionTable>) -> Self {
+        SearchContext{ tt: Some(tt), .. SearchContext::new() }
+    }
+
+    /// The context of the helper threads of a search in this one: they score positions the same
+    /// way and share 'tt', but have their own stop flag as they stop once the main thread is done
+    fn helpers(&self, tt: Arc<TranspositionTable>) -> Self {
+        SearchContext{ stop: Arc::new(AtomicBool::new(false)), tt: Some(tt), threads: 1, .. self.clone() }
+    }
+
+    /// Tells the search to stop as soon as possible
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the search was told to stop
+    pub fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+/// How a score stored in the transposition table relates to the real score
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper
+}
+
+/// One entry of the transposition table
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct TtEntry {
+    best: Option<(Position, Position)>,
+    score: i32,
+    depth: u8,
+    bound: Bound
+}
+
+impl TtEntry {
+    /// Packs the entry into 64 bits: score, depth, bound, move and whether there is a move
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2
+        };
+        let mv = match self.best {
+            Some((from, to)) => 1 << 13 | square(from) << 6 | square(to),
+            None => 0
+        };
+        self.score as u32 as u64 | (self.depth as u64) << 32 | bound << 40 | mv << 42
+    }
+
+    /// Reverses 'pack'
+    fn unpack(data: u64) -> Self {
+        let mv = data >> 42;
+        let field = |sq: u64| if sq >= 64 {
+            Position::reserve(RESERVE_FIGURES[(sq - 64) as usize])
+        } else {
+            Position::new((sq & 7) as u8, (sq >> 3 & 7) as u8)
+        };
+        TtEntry{ best: if mv & 1 << 13 != 0 { Some((field(mv >> 6 & 127), field(mv & 63))) } else { None },
+                 score: data as u32 as i32,
+                 depth: (data >> 32) as u8,
+                 bound: match data >> 40 & 3 {
+                     0 => Bound::Exact,
+                     1 => Bound::Lower,
+                     _ => Bound::Upper
+                 } }
+    }
+}
+
+/// Returns the index 0 to 63 of a position, the fields of the reserve follow from 64 on
+fn square(pos: Position) -> u64 {
+    if pos.x == RESERVE_FILE { 64 + pos.y as u64 } else { pos.y as u64 * 8 + pos.x as u64 }
+}
+
+/// Default size of the transposition table in megabytes
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// Stores results of searched positions so they do not have to be searched again.
+/// Each entry is two atomic words, the key xor the data and the data, so that threads
+/// can share the table without locks and torn writes are detected as a key mismatch.
+pub struct TranspositionTable {
+    zobrist: Vec<u64>,
+    entries: Vec<(AtomicU64, AtomicU64)>
+}
+
+impl ::std::fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "TranspositionTable {{ {} entries }}", self.entries.len())
+    }
+}
+
+impl TranspositionTable {
+    /// Creates a table using about 'mb' megabytes
+    pub fn new(mb: usize) -> Self {
+        let count = (mb.max(1) * 1024 * 1024 / 16).next_power_of_two() / 2;
+        // Random numbers for every figure of each color on every field and for the turn,
+        // made with splitmix64 so that hashes are the same in every run
+        let mut state = 0x9e3779b97f4a7c15u64;
+        let zobrist = (0..12 * 64 + 1).map(|_| {
+                                          state = state.wrapping_add(0x9e3779b97f4a7c15);
+                                          let mut z = state;
+                                          z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                                          z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                                          z ^ (z >> 31)
+                                      })
+                                      .collect();
+
+        TranspositionTable{ zobrist: zobrist,
+                            entries: (0..count).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect() }
+    }
+
+    /// Removes all entries
+    pub fn clear(&self) {
+        for e in self.entries.iter() {
+            e.0.store(0, Ordering::Relaxed);
+            e.1.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the Zobrist hash of a game's position
+    pub fn hash(&self, game: &ChessGame) -> u64 {
+        let mut key = if game.turn { self.zobrist[12 * 64] } else { 0 };
+        for y in 0..8 {
+            for x in 0..8 {
+                let pos = Position::new(x, y);
+                if let (Some(fig), Some(color)) = (game.board.get_figure(pos), game.board.get_figure_color(pos)) {
+                    let kind = figure_index(fig) * 2 + if color == Color::White { 1 } else { 0 };
+                    key ^= self.zobrist[kind * 64 + square(pos) as usize];
+                }
+            }
+        }
+        // Reserves count as well, each number of figures of a kind gets its own key
+        for (side, player) in [&game.white_player, &game.black_player].iter().enumerate() {
+            for &fig in RESERVE_FIGURES.iter() {
+                let count = player.reserve().iter().filter(|&&f| f == fig).count();
+                if count > 0 {
+                    key ^= self.zobrist[(figure_index(fig) * 2 + side) * 64 + count].rotate_left(32);
+                }
+            }
+        }
+        // Variants counting checks need different keys for the same figures
+        key ^ (game.checks[0] as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (game.checks[1] as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+    }
+
+    /// Returns the entry stored for a key
+    fn probe(&self, key: u64) -> Option<TtEntry> {
+        let e = &self.entries[key as usize & (self.entries.len() - 1)];
+        let data = e.1.load(Ordering::Relaxed);
+        if data != 0 && e.0.load(Ordering::Relaxed) ^ data == key {
+            Some(TtEntry::unpack(data))
+        } else {
+            None
+        }
+    }
+
+    /// Stores an entry for a key, replacing whatever was there
+    fn store(&self, key: u64, entry: TtEntry) {
+        let e = &self.entries[key as usize & (self.entries.len() - 1)];
+        let data = entry.pack();
+        e.0.store(key ^ data, Ordering::Relaxed);
+        e.1.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Returns the index of a figure in the Zobrist table
+fn figure_index(fig: Figure) -> usize {
+    match fig {
+        Figure::Pawn => 0,
+        Figure::Knight => 1,
+        Figure::Bishop => 2,
+        Figure::Rook => 3,
+        Figure::Queen => 4,
+        Figure::King => 5
+    }
+}
+
+/// Mate scores are stored relative to the position instead of the root
+fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE - MAX_DEPTH as i32 {
+        score + ply as i32
+    } else if score <= -MATE + MAX_DEPTH as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Reverses 'score_to_tt'
+fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE - MAX_DEPTH as i32 {
+        score - ply as i32
+    } else if score <= -MATE + MAX_DEPTH as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// Converts a duration to milliseconds
+fn millis(d: Duration) -> u64 {
+    d.as_secs() * 1000 + (d.subsec_nanos() / 1000000) as u64
+}
+
+/// Search a game for the best move of the player whose turn it is, using iterative deepening
+pub fn search(game: &ChessGame, limits: &SearchLimits) -> SearchResult {
+    search_with_noise(game, limits, 0, &mut game.rng())
+}
+
+/// Search like 'search', but add random noise of up to 'noise' to the score of each move at the root
+pub fn search_with_noise<R: Rng>(game: &ChessGame, limits: &SearchLimits, noise: i32, rng: &mut R) -> SearchResult {
+    run(game, limits, noise, rng, &SearchContext::new(), |_| ())
+}
+
+/// Search like 'search_with_noise' within a context, 'report' is called after every finished iteration
+pub fn run<R, F>(game: &ChessGame, limits: &SearchLimits, noise: i32, rng: &mut R, context: &SearchContext, mut report: F) -> SearchResult
+    where R: Rng, F: FnMut(&SearchResult)
+{
+    analyse(game, limits, 1, noise, rng, context, |lines| report(&lines[0])).remove(0)
+}
+
+/// Search for the 'count' best moves, each with its own principal variation and score, best first.
+/// 'report' is called with the lines after every finished iteration.
+pub fn analyse<R, F>(game: &ChessGame, limits: &SearchLimits, count: usize, noise: i32, rng: &mut R, context: &SearchContext,
+                     report: F) -> Vec<SearchResult>
+    where R: Rng, F: FnMut(&[SearchResult])
+{
+    if context.threads <= 1 {
+        return iterate(game, limits, count, noise, rng, context, 1, report)
+    }
+
+    // Lazy SMP: helper threads search the same root and only help through the shared
+    // transposition table, every second one starts a ply deeper so they do not all do the same work
+    let tt = context.tt.clone().unwrap_or_else(|| Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)));
+    let main = SearchContext{ tt: Some(tt.clone()), .. context.clone() };
+    let helpers_context = context.helpers(tt);
+
+    let helpers: Vec<_> = (1..context.threads).map(|i| {
+        let game = game.clone();
+        let limits = *limits;
+        let helper_context = helpers_context.clone();
+        thread::spawn(move || {
+            let lines = iterate(&game, &limits, count, 0, &mut game.rng(), &helper_context, 1 + (i % 2) as u8, |_| ());
+            lines[0].nodes
+        })
+    }).collect();
+
+    let mut results = iterate(game, limits, count, noise, rng, &main, 1, report);
+    helpers_context.stop();
+    let helper_nodes: u64 = helpers.into_iter().map(|h| h.join().unwrap_or(0)).sum();
+    for r in results.iter_mut() {
+        r.nodes += helper_nodes;
+    }
+    results
+}
+
+/// Iterative deepening for 'analyse' in one thread, starting at 'first_depth'
+fn iterate<R, F>(game: &ChessGame, limits: &SearchLimits, count: usize, noise: i32, rng: &mut R, context: &SearchContext,
+                 first_depth: u8, mut report: F) -> Vec<SearchResult>
+    where R: Rng, F: FnMut(&[SearchResult])
+{
+    let mut searcher = Searcher{ limits: *limits, context: context, start: Instant::now(), nodes: 0, aborted: false };
+    let mut root = game.clone();
+    let mut moves = root.possible_moves();
+    let count = count.max(1).min(moves.len().max(1));
+
+    let mut results: Vec<SearchResult> = moves.iter()
+                                               .take(count)
+                                               .map(|&m| SearchResult{ best: Some(m), score: 0, depth: 0, nodes: 0,
+                                                                       time: Duration::from_millis(0), pv: vec![m] })
+                                               .collect();
+    if results.is_empty() {
+        results.push(SearchResult{ best: None, score: 0, depth: 0, nodes: 0, time: Duration::from_millis(0), pv: Vec::new() });
+    }
+    if moves.len() < 2 {
+        return results
+    }
+
+    let max_depth = limits.depth.unwrap_or(MAX_DEPTH).max(1).min(MAX_DEPTH);
+    order_moves(&root, &mut moves, None);
+
+    for depth in first_depth.min(max_depth)..max_depth + 1 {
+        // The best lines found in this iteration with their scores, best first
+        let mut lines: Vec<(i32, Vec<(Position, Position)>)> = Vec::with_capacity(count + 1);
+        let mut child_pv = Vec::new();
+
+        for &(from, to) in moves.iter() {
+            // A move only needs to be searched exactly if it can still make it into the lines
+            let alpha = if lines.len() < count { -MATE - 1 } else { lines[count - 1].0 };
+
+            let mut child = root.clone();
+            child.make_move(from, to);
+            let mut score = -searcher.negamax(&mut child, depth - 1, 1, -MATE - 1, -alpha, &mut child_pv);
+
+            if searcher.aborted {
+                break
+            }
+            if noise > 0 && score.abs() < MATE - MAX_DEPTH as i32 {
+                score += rng.gen_range(-noise, noise + 1);
+            }
+            if score > alpha {
+                let mut pv = vec![(from, to)];
+                pv.extend_from_slice(&child_pv);
+                let at = lines.iter().position(|l| l.0 < score).unwrap_or(lines.len());
+                lines.insert(at, (score, pv));
+                lines.truncate(count);
+            }
+        }
+
+        // Results of an unfinished iteration are only used if its first moves,
+        // the best ones of the last iteration, were searched completely
+        if lines.len() == count {
+            results = lines.into_iter()
+                           .map(|(score, pv)| SearchResult{ best: pv.first().cloned(), score: score, depth: depth,
+                                                            nodes: 0, time: Duration::from_millis(0), pv: pv })
+                           .collect();
+            order_moves(&root, &mut moves, None);
+            moves.sort_by_key(|m| results.iter().position(|r| r.best == Some(*m)).unwrap_or(count));
+        }
+        for r in results.iter_mut() {
+            r.nodes = searcher.nodes;
+            r.time = searcher.start.elapsed();
+        }
+        if searcher.aborted {
+            break
+        }
+
+        report(&results);
+        if results[0].score.abs() >= MATE - MAX_DEPTH as i32 {
+            break
+        }
+    }
+
+    results
+}
+
+/// Puts 'first' in front and captures of valuable figures before all other moves. Moves
+/// of equal value are sorted by their fields, so the order never depends on how the moves
+/// were generated and searches with the same limits always give the same result.
+fn order_moves(game: &ChessGame, moves: &mut Vec<(Position, Position)>, first: Option<(Position, Position)>) {
+    moves.sort_by_key(|&m| {
+        let fields = (square(m.0), square(m.1));
+        if Some(m) == first {
+            return (i32::min_value(), fields)
+        }
+        match game.board.get_figure(m.1) {
+            Some(victim) => (figure_value(&game.board.get_figure(m.0).unwrap()) - figure_value(&victim) * 10, fields),
+            None => (0, fields)
+        }
+    });
+}
+
+/// Holds the state of one running search
+struct Searcher<'a> {
+    limits: SearchLimits,
+    context: &'a SearchContext,
+    start: Instant,
+    nodes: u64,
+    aborted: bool
+}
+
+impl<'a> Searcher<'a> {
+    /// Returns whether a limit was reached or the search was stopped and stores that in 'aborted'
+    fn out_of_budget(&mut self) -> bool {
+        if let Some(n) = self.limits.nodes {
+            self.aborted = self.aborted || self.nodes >= n;
+        }
+        if let Some(t) = self.limits.movetime {
+            self.aborted = self.aborted || self.start.elapsed() >= t;
+        }
+        self.aborted = self.aborted || self.context.stopped();
+        self.aborted
+    }
+
+    /// Score of a draw for the side to move at 'ply', the root side pays the contempt
+    fn draw_score(&self, ply: u8) -> i32 {
+        if ply % 2 == 0 { -self.context.contempt } else { self.context.contempt }
+    }
+
+    /// Alpha-beta search in negamax form, the best line found is written to 'pv'
+    fn negamax(&mut self, game: &mut ChessGame, depth: u8, ply: u8, mut alpha: i32, beta: i32,
+               pv: &mut Vec<(Position, Position)>) -> i32 {
+        pv.clear();
+        self.nodes += 1;
+        if self.out_of_budget() {
+            return 0
+        }
+
+        let tt = self.context.tt.clone();
+        let key = tt.as_ref().map_or(0, |tt| tt.hash(game));
+        let mut tt_move = None;
+        if let Some(entry) = tt.as_ref().and_then(|tt| tt.probe(key)) {
+            tt_move = entry.best;
+            if entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => {
+                        pv.extend(entry.best);
+                        return score
+                    },
+                    Bound::Lower if score >= beta => return beta,
+                    Bound::Upper if score <= alpha => return alpha,
+                    _ => ()
+                }
+            }
+        }
+
+        // A variant may have been won by the last move
+        if let Some((winner, _)) = game.variant().winner(game) {
+            return if winner == game.turn_color() { MATE - ply as i32 } else { -MATE + ply as i32 }
+        }
+        let mut moves = game.possible_moves();
+        if moves.is_empty() {
+            return if game.in_check() { -MATE + ply as i32 } else { self.draw_score(ply) }
+        }
+        if depth == 0 {
+            return self.quiescence(game, moves, ply, alpha, beta)
+        }
+
+        let original_alpha = alpha;
+        let mut best = None;
+        let mut child_pv = Vec::new();
+        order_moves(game, &mut moves, tt_move);
+        for &(from, to) in moves.iter() {
+            let mut child = game.clone();
+            child.make_move(from, to);
+            let score = -self.negamax(&mut child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+
+            if self.aborted {
+                return 0
+            }
+            if score >= beta {
+                if let Some(ref tt) = tt {
+                    tt.store(key, TtEntry{ best: Some((from, to)), score: score_to_tt(beta, ply), depth: depth, bound: Bound::Lower });
+                }
+                return beta
+            }
+            if score > alpha {
+                alpha = score;
+                best = Some((from, to));
+                pv.clear();
+                pv.push((from, to));
+                pv.extend_from_slice(&child_pv);
+            }
+        }
+
+        if let Some(ref tt) = tt {
+            let bound = if alpha > original_alpha { Bound::Exact } else { Bound::Upper };
+            tt.store(key, TtEntry{ best: best, score: score_to_tt(alpha, ply), depth: depth, bound: bound });
+        }
+        alpha
+    }
+    /// Only follows captures so we do not stop in the middle of an exchange
+    fn quiescence(&mut self, game: &mut ChessGame, moves: Vec<(Position, Position)>, ply: u8, mut alpha: i32, beta: i32) -> i32 {
+        let stand_pat = self.context.eval.evaluate(game);
+        if stand_pat >= beta || ply >= MAX_DEPTH {
+            return stand_pat
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let mut captures: Vec<(Position, Position)> = moves.into_iter()
+                                                           .filter(|m| game.board.is_capture_move(m.0, m.1))
+                                                           .collect();
+        order_moves(game, &mut captures, None);
+
+        for &(from, to) in captures.iter() {
+            let mut child = game.clone();
+            child.make_move(from, to);
+
+            self.nodes += 1;
+            if self.out_of_budget() {
+                return 0
+            }
+
+            if let Some((winner, _)) = child.variant().winner(&child) {
+                let score = if winner == game.turn_color() { MATE - ply as i32 - 1 } else { -MATE + ply as i32 + 1 };
+                if score >= beta {
+                    return beta
+                }
+                alpha = alpha.max(score);
+                continue
+            }
+            let replies = child.possible_moves();
+            let score = if replies.is_empty() {
+                if child.in_check() { MATE - ply as i32 - 1 } else { self.draw_score(ply) }
+            } else {
+                -self.quiescence(&mut child, replies, ply + 1, -beta, -alpha)
+            };
+
+            if self.aborted {
+                return 0
+            }
+            if score >= beta {
+                return beta
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        alpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn clock_budget() {
+        // An equal share of the clock without the reserve plus three quarters of the increment
+        assert_eq!(SearchLimits::from_clock(ms(30050), ms(0), None).movetime, Some(ms(1000)));
+        assert_eq!(SearchLimits::from_clock(ms(10050), ms(2000), Some(10)).movetime, Some(ms(2500)));
+        // Never more than half of what is left, and always a little
+        assert_eq!(SearchLimits::from_clock(ms(1050), ms(10000), Some(1)).movetime, Some(ms(500)));
+        assert_eq!(SearchLimits::from_clock(ms(10), ms(0), Some(0)).movetime, Some(ms(1)));
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let game = ChessGame::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search(&game, &SearchLimits::depth(3));
+        let mate = (Position::from_algebraic("a1").unwrap(), Position::from_algebraic("a8").unwrap());
+        assert_eq!(result.best, Some(mate));
+        assert_eq!(result.pv.first(), Some(&mate));
+        assert_eq!(result.mate_in(), Some(1));
+    }
+
+    #[test]
+    fn respects_limits() {
+        let game = ChessGame::new();
+        assert!(search(&game, &SearchLimits::depth(2)).depth <= 2);
+        let result = search(&game, &SearchLimits::movetime(ms(100)));
+        assert!(result.best.is_some());
+        assert!(result.time < ms(1000));
+    }
+
+    #[test]
+    fn several_lines() {
+        let game = ChessGame::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let lines = analyse(&game, &SearchLimits::depth(2), 3, 0, &mut game.rng(), &SearchContext::new(), |_| ());
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].mate_in(), Some(1));
+        for (i, line) in lines.iter().enumerate() {
+            assert!(game.clone().possible_moves().contains(&line.best.unwrap()));
+            assert_eq!(line.pv.first(), line.best.as_ref());
+            assert!(lines[i + 1..].iter().all(|other| other.best != line.best && other.score <= line.score));
+        }
+
+        // Never more lines than moves
+        let game = ChessGame::from_fen("7k/8/8/8/8/8/6PP/7K w - - 0 1").unwrap();
+        let moves = game.clone().possible_moves().len();
+        assert_eq!(analyse(&game, &SearchLimits::depth(1), 10, 0, &mut game.rng(), &SearchContext::new(), |_| ()).len(), moves);
+    }
+
+    #[test]
+    fn several_threads() {
+        let game = ChessGame::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let context = SearchContext{ threads: 4, .. SearchContext::with_table(Arc::new(TranspositionTable::new(1))) };
+        let result = run(&game, &SearchLimits::depth(4), 0, &mut game.rng(), &context, |_| ());
+        assert_eq!(result.mate_in(), Some(1));
+        assert_eq!(result.best, Some((Position::from_algebraic("a1").unwrap(), Position::from_algebraic("a8").unwrap())));
+    }
+
+    #[test]
+    fn helpers_keep_scoring() {
+        let mut eval = Eval::default();
+        eval.weights[0] = 12;
+        let context = SearchContext{ threads: 4, eval: eval, contempt: 5, .. SearchContext::new() };
+        let helpers = context.helpers(Arc::new(TranspositionTable::new(1)));
+        assert_eq!((helpers.eval, helpers.contempt, helpers.threads), (eval, 5, 1));
+        assert!(helpers.tt.is_some());
+
+        helpers.stop();
+        assert!(!context.stopped());
+    }
+
+    #[test]
+    fn stopped_search_still_moves() {
+        let game = ChessGame::new();
+        let context = SearchContext::new();
+        context.stop();
+        let result = run(&game, &SearchLimits::infinite(), 0, &mut game.rng(), &context, |_| ());
+        assert!(result.best.is_some());
+    }
+}
@@ -0,0 +1,218 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Setting up a position figure by figure, e.g. in the editor of the window. A setup is
+//! checked to be a legal position before a game starts from it or it is exported as FEN.
+
+use chess::ChessGame;
+use chess::logic::{Board, Color, Figure, Position};
+
+/// Castling rights in the order of a FEN: the king's field, the rook's field and the letter
+const CASTLING: [((u8, u8), (u8, u8), char); 4] = [((4, 0), (7, 0), 'K'), ((4, 0), (0, 0), 'Q'),
+                                                   ((4, 7), (7, 7), 'k'), ((4, 7), (0, 7), 'q')];
+
+/// A position being set up: the figures, who moves first and who may still castle
+#[derive(Debug, Clone)]
+pub struct Setup {
+    /// The fields with the figures placed so far
+    pub board: Board,
+    /// True if white moves first
+    pub turn: bool,
+    // Castling rights of white kingside and queenside, then black, as in 'CASTLING'
+    castling: [bool; 4]
+}
+
+impl Setup {
+    /// Starts with an empty board, white to move
+    pub fn empty() -> Self {
+        Setup{ board: Board::empty(), turn: true, castling: [false; 4] }
+    }
+
+    /// Starts with the current position of the game
+    pub fn from_game(game: &ChessGame) -> Self {
+        let (white_short, white_long) = game.white_player.castling_rights();
+        let (black_short, black_long) = game.black_player.castling_rights();
+        Setup{ board: game.board.clone(), turn: game.turn, castling: [white_short, white_long, black_short, black_long] }
+    }
+
+    /// Puts a figure on the field, replacing the one standing there
+    pub fn place(&mut self, pos: Position, fig: Figure, color: Color) {
+        self.board.set_figure(pos, fig, color);
+    }
+
+    /// Takes the figure off the field and returns it with its color
+    pub fn remove(&mut self, pos: Position) -> Option<(Figure, Color)> {
+        let removed = self.board.get_figure(pos).map(|fig| (fig, self.board.get_figure_color(pos).unwrap()));
+        self.board[pos].set_empty(pos);
+        removed
+    }
+
+    /// Moves the figure at 'from' to 'to', replacing the one standing there.
+    /// Returns false if there is no figure at 'from'.
+    pub fn move_figure(&mut self, from: Position, to: Position) -> bool {
+        if self.board.is_empty(from) {
+            return false
+        }
+        if from != to {
+            self.board.move_figure(from, to);
+        }
+        true
+    }
+
+    /// Lets the other player move first
+    pub fn toggle_turn(&mut self) {
+        self.turn = !self.turn;
+    }
+
+    /// Returns whether white may castle kingside and queenside, then black
+    pub fn castling(&self) -> [bool; 4] {
+        self.castling
+    }
+
+    /// Gives or takes a castling right, 'index' as in 'castling'
+    pub fn toggle_castling(&mut self, index: usize) {
+        self.castling[index] = !self.castling[index];
+    }
+
+    /// Returns why the position is not legal: each side needs exactly one king, pawns cannot
+    /// stand on the first or last row and the side that does not move may not be in check
+    pub fn validate(&self) -> Result<(), String> {
+        let mut kings = [Vec::new(), Vec::new()];
+        for y in 0u8..8 {
+            for x in 0u8..8 {
+                let pos = Position::new(x, y);
+                match self.board.get_figure(pos) {
+                    Some(Figure::King) => kings[if self.board.get_figure_color(pos) == Some(Color::White) { 0 } else { 1 }].push(pos),
+                    Some(Figure::Pawn) if y == 0 || y == 7 => {
+                        return Err(format!("Pawns cannot stand on the first or last row like the one on {}", pos.to_algebraic()))
+                    },
+                    _ => ()
+                }
+            }
+        }
+        for (i, name) in ["White", "Black"].iter().enumerate() {
+            if kings[i].len() != 1 {
+                return Err(format!("{} needs exactly one king, not {}", name, kings[i].len()))
+            }
+        }
+
+        // The side to move could take the king that is in check
+        let mover = if self.turn { Color::White } else { Color::Black };
+        let king = kings[if self.turn { 1 } else { 0 }][0];
+        let attacked = (0..64).map(|i| Position::new(i % 8, i / 8)).any(|pos| {
+            self.board.get_figure_color(pos) == Some(mover) &&
+            self.board.get_figure(pos).map_or(false, |fig| fig.valid_move(&self.board, pos, king, &mover))
+        });
+        if attacked {
+            let (checked, moving) = if self.turn { ("Black", "White") } else { ("White", "Black") };
+            return Err(format!("{} is in check although {} is to move", checked, moving))
+        }
+        Ok(())
+    }
+
+    /// Returns the position in Forsyth-Edwards Notation. Castling rights are only written
+    /// if the king and the rook still stand on their fields.
+    pub fn to_fen(&self) -> String {
+        let at_home = |(x, y): (u8, u8), fig: Figure, color: Color| {
+            let pos = Position::new(x, y);
+            self.board.get_figure(pos) == Some(fig) && self.board.get_figure_color(pos) == Some(color)
+        };
+        let mut castling: String = CASTLING.iter().zip(self.castling.iter())
+            .filter(|&(&(king, rook, letter), &right)| {
+                let color = if letter.is_uppercase() { Color::White } else { Color::Black };
+                right && at_home(king, Figure::King, color) && at_home(rook, Figure::Rook, color)
+            })
+            .map(|(&(_, _, letter), _)| letter)
+            .collect();
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        format!("{} {} {} - 0 1", self.board.placement(|_| false), if self.turn { "w" } else { "b" }, castling)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::START_FEN;
+
+    fn pos(s: &str) -> Position {
+        Position::from_algebraic(s).unwrap()
+    }
+
+    fn kings() -> Setup {
+        let mut setup = Setup::empty();
+        setup.place(pos("e1"), Figure::King, Color::White);
+        setup.place(pos("e8"), Figure::King, Color::Black);
+        setup
+    }
+
+    #[test]
+    fn start_position() {
+        let setup = Setup::from_game(&ChessGame::new());
+        assert!(setup.validate().is_ok());
+        assert_eq!(setup.to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn validation_errors() {
+        let mut setup = Setup::empty();
+        assert_eq!(setup.validate(), Err(String::from("White needs exactly one king, not 0")));
+
+        setup = kings();
+        setup.place(pos("a1"), Figure::King, Color::Black);
+        assert_eq!(setup.validate(), Err(String::from("Black needs exactly one king, not 2")));
+
+        setup = kings();
+        setup.place(pos("c8"), Figure::Pawn, Color::Black);
+        assert_eq!(setup.validate(),
+                   Err(String::from("Pawns cannot stand on the first or last row like the one on c8")));
+
+        setup = kings();
+        setup.place(pos("e4"), Figure::Rook, Color::White);
+        assert_eq!(setup.validate(), Err(String::from("Black is in check although White is to move")));
+        setup.toggle_turn();
+        assert!(setup.validate().is_ok());
+    }
+
+    // Castling rights need the king and the rook on their fields
+    #[test]
+    fn fen_export() {
+        let mut setup = kings();
+        setup.place(pos("h1"), Figure::Rook, Color::White);
+        setup.place(pos("d5"), Figure::Knight, Color::Black);
+        setup.toggle_castling(0);
+        setup.toggle_castling(1);
+        setup.toggle_turn();
+        assert_eq!(setup.castling(), [true, true, false, false]);
+        assert_eq!(setup.to_fen(), "4k3/8/8/3n4/8/8/8/4K2R b K - 0 1");
+
+        assert!(setup.move_figure(pos("d5"), pos("h1")));
+        assert!(!setup.move_figure(pos("d5"), pos("d4")));
+        assert_eq!(setup.remove(pos("h1")), Some((Figure::Knight, Color::Black)));
+        assert_eq!(setup.remove(pos("h1")), None);
+        assert_eq!(setup.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert!(ChessGame::from_fen(&setup.to_fen()).is_ok());
+    }
+}
@@ -0,0 +1,1398 @@
+// Probing of Syzygy WDL (win/draw/loss) and DTZ (distance to zeroing move) tablebases.
+//
+// This file is a port of the probing code of Fathom (https://github.com/jdart1/Fathom) to the
+// rules of this game, which is based on the original probing code by Ronald de Man. Unlike the
+// rest of this project it keeps their copyright and license notices:
+//
+// Copyright (c) 2011-2015 Ronald de Man
+// This file may be redistributed and/or modified without restrictions.
+//
+// The MIT License (MIT)
+//
+// Copyright (c) 2015 basil00
+// Modifications Copyright (c) 2016-2019 by Jon Dart
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chess::ChessGame;
+use chess::logic::{Color, Figure, Position};
+
+/// Magic numbers at the start of WDL and DTZ files
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+/// Most pieces a table can have
+const MAX_PIECES: usize = 7;
+
+/// Squares are numbered from a1 = 0 to h8 = 63 as in the tables.
+/// Number of a square in the a1-d1-d4 triangle, the squares on the diagonal come last
+const TRIANGLE: [u8; 64] = [
+    6, 0, 1, 2, 2, 1, 0, 6,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    6, 0, 1, 2, 2, 1, 0, 6
+];
+
+/// Square of each 'TRIANGLE' number
+const INV_TRIANGLE: [usize; 10] = [1, 2, 3, 10, 11, 19, 0, 9, 18, 27];
+
+/// Number of a square on the a1-h8 diagonal, or on the h1-a8 diagonal plus 8
+const DIAG: [u64; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  8,
+     0,  1,  0,  0,  0,  0,  9,  0,
+     0,  0,  2,  0,  0, 10,  0,  0,
+     0,  0,  0,  3, 11,  0,  0,  0,
+     0,  0,  0, 12,  4,  0,  0,  0,
+     0,  0, 13,  0,  0,  5,  0,  0,
+     0, 14,  0,  0,  0,  0,  6,  0,
+    15,  0,  0,  0,  0,  0,  0,  7
+];
+
+/// Number of a square below the a1-h8 diagonal, squares above it have the number of their mirror image
+const LOWER: [u64; 64] = [
+    28,  0,  1,  2,  3,  4,  5,  6,
+     0, 29,  7,  8,  9, 10, 11, 12,
+     1,  7, 30, 13, 14, 15, 16, 17,
+     2,  8, 13, 31, 18, 19, 20, 21,
+     3,  9, 14, 18, 32, 22, 23, 24,
+     4, 10, 15, 19, 22, 33, 25, 26,
+     5, 11, 16, 20, 23, 25, 34, 27,
+     6, 12, 17, 21, 24, 26, 27, 35
+];
+
+/// Number of the leading pawn's square, by file mirrored to a to d and then by rank
+const FLAP: [u8; 64] = [
+    0,  0,  0,  0,  0,  0,  0,  0,
+    0,  6, 12, 18, 18, 12,  6,  0,
+    1,  7, 13, 19, 19, 13,  7,  1,
+    2,  8, 14, 20, 20, 14,  8,  2,
+    3,  9, 15, 21, 21, 15,  9,  3,
+    4, 10, 16, 22, 22, 16, 10,  4,
+    5, 11, 17, 23, 23, 17, 11,  5,
+    0,  0,  0,  0,  0,  0,  0,  0
+];
+
+/// Number of a pawn's square, highest near the edge and on low ranks
+const PTWIST: [u8; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    47, 35, 23, 11, 10, 22, 34, 46,
+    45, 33, 21,  9,  8, 20, 32, 44,
+    43, 31, 19,  7,  6, 18, 30, 42,
+    41, 29, 17,  5,  4, 16, 28, 40,
+    39, 27, 15,  3,  2, 14, 26, 38,
+    37, 25, 13,  1,  0, 12, 24, 36,
+     0,  0,  0,  0,  0,  0,  0,  0
+];
+
+/// Square of each 'FLAP' number
+const INV_FLAP: [usize; 24] = [8, 16, 24, 32, 40, 48, 9, 17, 25, 33, 41, 49,
+                               10, 18, 26, 34, 42, 50, 11, 19, 27, 35, 43, 51];
+
+/// File of the leading pawn mirrored to a to d
+const FILE_TO_FILE: [usize; 8] = [0, 1, 2, 3, 3, 2, 1, 0];
+
+/// Number of placements of the first group of each encoding type: three unique
+/// figures, a king and one unique figure (only used in suicide chess) and two kings
+const PIVOT_FACTOR: [u64; 3] = [31332, 28056, 462];
+
+/// Which of the four DTZ maps belongs to an outcome
+const WDL_TO_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+
+/// Flags telling that the DTZ values of an outcome are stored in plies instead of moves
+const PA_FLAGS: [u8; 5] = [8, 0, 0, 0, 4];
+
+/// Outcome of a position for the player whose turn it is
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub enum Wdl {
+    Loss,
+    // Loss that is a draw because of the fifty move rule
+    BlessedLoss,
+    Draw,
+    // Win that is a draw because of the fifty move rule
+    CursedWin,
+    Win
+}
+
+impl Wdl {
+    fn from_value(v: i32) -> Self {
+        match v {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win
+        }
+    }
+
+    fn value(&self) -> i32 {
+        match *self {
+            Wdl::Loss => -2,
+            Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 1,
+            Wdl::Win => 2
+        }
+    }
+}
+
+impl ::std::fmt::Display for Wdl {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            Wdl::Loss => write!(f, "loss"),
+            Wdl::BlessedLoss => write!(f, "blessed loss"),
+            Wdl::Draw => write!(f, "draw"),
+            Wdl::CursedWin => write!(f, "cursed win"),
+            Wdl::Win => write!(f, "win")
+        }
+    }
+}
+
+/// Why a table lookup did not give a value
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Probe {
+    // A table is missing or broken
+    Fail,
+    // The DTZ table only stores the other side to move
+    ChangeStm
+}
+
+/// Returns whether a square is above (1) or below (-1) the a1-h8 diagonal or on it (0)
+fn off_diag(sq: usize) -> i32 {
+    ((sq >> 3) as i32 - (sq & 7) as i32).signum()
+}
+
+/// Mirrors a square at the a1-h8 diagonal
+fn flip_diag(sq: usize) -> usize {
+    ((sq >> 3) | (sq << 3)) & 63
+}
+
+/// Returns the number of ways to choose 'k' of 'n' squares
+fn subfactor(k: usize, n: usize) -> u64 {
+    let (mut f, mut l) = (n as u64, 1);
+    for i in 1..k {
+        f *= (n - i) as u64;
+        l *= (i + 1) as u64;
+    }
+    f / l
+}
+
+/// Fathom's 'init_indices': binomial coefficients and the numbers of king and pawn placements
+struct Indices {
+    // binomial[k][n] is the number of ways to choose k + 1 of n squares
+    binomial: [[u64; 64]; MAX_PIECES - 1],
+    // Number of the second king's square for each 'TRIANGLE' number of the first one
+    kk_idx: [[u64; 64]; 10],
+    // Number of the leading pawns' placements before a 'FLAP' number, and of all placements on a file
+    pawn_idx: [[u64; 24]; MAX_PIECES - 1],
+    pawn_factor: [[u64; 4]; MAX_PIECES - 1]
+}
+
+impl Indices {
+    fn new() -> Self {
+        let mut ind = Indices{ binomial: [[0; 64]; MAX_PIECES - 1], kk_idx: [[0; 64]; 10],
+                               pawn_idx: [[0; 24]; MAX_PIECES - 1], pawn_factor: [[0; 4]; MAX_PIECES - 1] };
+
+        for i in 0..MAX_PIECES - 1 {
+            for j in 0..64 {
+                ind.binomial[i][j] = if j > i { subfactor(i + 1, j) } else { 0 };
+            }
+        }
+
+        // The 462 placements of two kings that do not touch with the first one in the a1-d1-d4
+        // triangle, if it is on the diagonal the second one is not above it. Placements with
+        // both kings on the diagonal come last.
+        let mut code = 0;
+        let mut both_on_diag = Vec::new();
+        for t in 0..10 {
+            let s1 = INV_TRIANGLE[t];
+            for s2 in 0..64 {
+                let touching = ((s1 & 7) as i32 - (s2 & 7) as i32).abs() <= 1 &&
+                               ((s1 >> 3) as i32 - (s2 >> 3) as i32).abs() <= 1;
+                if touching || (off_diag(s1) == 0 && off_diag(s2) > 0) {
+                    continue
+                }
+                if off_diag(s1) == 0 && off_diag(s2) == 0 {
+                    both_on_diag.push((t, s2));
+                } else {
+                    ind.kk_idx[t][s2] = code;
+                    code += 1;
+                }
+            }
+        }
+        for &(t, s2) in both_on_diag.iter() {
+            ind.kk_idx[t][s2] = code;
+            code += 1;
+        }
+
+        for i in 0..MAX_PIECES - 1 {
+            for file in 0..4 {
+                let mut s = 0;
+                for j in 6 * file..6 * file + 6 {
+                    ind.pawn_idx[i][j] = s;
+                    s += if i == 0 { 1 } else { ind.binomial[i - 1][PTWIST[INV_FLAP[j]] as usize] };
+                }
+                ind.pawn_factor[i][file] = s;
+            }
+        }
+
+        ind
+    }
+}
+
+/// How the figures of a table are turned into an index for one side to move and one file of
+/// the leading pawn: the figure codes in table order, the sizes of the groups that are encoded
+/// together and the factor of each group
+#[derive(Clone, Default)]
+struct Encoding {
+    pieces: [u8; MAX_PIECES],
+    norm: [usize; MAX_PIECES],
+    factor: [u64; MAX_PIECES]
+}
+
+/// Decompression data of one side to move and one file of the leading pawn
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    block_size: u32,
+    idx_bits: u32,
+    min_len: u8,
+    // Where the first symbol of each code length, the symbol pairs, the index table,
+    // the sizes of the blocks and the blocks themselves start in the file
+    offset: usize,
+    sympat: usize,
+    index_table: usize,
+    size_table: usize,
+    data: usize,
+    // Sizes of the index table, the size table and the data
+    sizes: [usize; 3],
+    // Number of values each symbol expands to minus one
+    symlen: Vec<u8>,
+    // Smallest code of each length, aligned to the left
+    base: Vec<u64>,
+    // Where the four DTZ maps start, for DTZ tables
+    map_idx: [usize; 4]
+}
+
+/// A loaded WDL or DTZ table
+struct Table {
+    bytes: Vec<u8>,
+    dtz: bool,
+    num: usize,
+    symmetric: bool,
+    has_pawns: bool,
+    enc_type: usize,
+    // Pawns of the leading color and of the other one
+    pawns: [usize; 2],
+    // Indexed by file of the leading pawn and side to move
+    parts: Vec<Vec<(Encoding, PairsData)>>,
+    map: usize
+}
+
+/// Reads little and big endian numbers, reads past the end give zeros
+fn byte(b: &[u8], at: usize) -> u8 {
+    if at < b.len() { b[at] } else { 0 }
+}
+
+fn u16_le(b: &[u8], at: usize) -> u64 {
+    byte(b, at) as u64 | (byte(b, at + 1) as u64) << 8
+}
+
+fn u32_le(b: &[u8], at: usize) -> u64 {
+    u16_le(b, at) | u16_le(b, at + 2) << 16
+}
+
+fn u32_be(b: &[u8], at: usize) -> u64 {
+    (0..4).fold(0, |acc, i| (acc << 8) | byte(b, at + i) as u64)
+}
+
+fn u64_be(b: &[u8], at: usize) -> u64 {
+    (u32_be(b, at) << 32) | u32_be(b, at + 4)
+}
+
+/// Material of one side in table file notation, for example 'KRP'
+fn material_name(counts: &[usize; 6]) -> String {
+    let letters = ['K', 'Q', 'R', 'B', 'N', 'P'];
+    let mut name = String::new();
+    for (i, &c) in counts.iter().enumerate() {
+        for _ in 0..c {
+            name.push(letters[i]);
+        }
+    }
+    name
+}
+
+/// Counts the figures of each type in the order of the file names
+fn material_counts(name: &str) -> [usize; 6] {
+    let mut counts = [0; 6];
+    for c in name.chars() {
+        match c {
+            'K' => counts[0] += 1,
+            'Q' => counts[1] += 1,
+            'R' => counts[2] += 1,
+            'B' => counts[3] += 1,
+            'N' => counts[4] += 1,
+            'P' => counts[5] += 1,
+            _ => ()
+        }
+    }
+    counts
+}
+
+/// Piece code as used in the tables: 1 to 6 for pawn to king, plus 8 for black
+fn piece_code(fig: Figure, color: Color) -> u8 {
+    let kind = match fig {
+        Figure::Pawn => 1,
+        Figure::Knight => 2,
+        Figure::Bishop => 3,
+        Figure::Rook => 4,
+        Figure::Queen => 5,
+        Figure::King => 6
+    };
+    if color == Color::Black { kind + 8 } else { kind }
+}
+
+/// Fathom's 'set_norm_piece': the first group holds the figures of the encoding type,
+/// then each group holds the figures of one kind
+fn set_norm_piece(e: &mut Encoding, num: usize, enc_type: usize) {
+    e.norm = [0; MAX_PIECES];
+    e.norm[0] = if enc_type == 0 { 3 } else { 2 };
+    let mut i = e.norm[0];
+    while i < num {
+        let mut j = i;
+        while j < num && e.pieces[j] == e.pieces[i] {
+            e.norm[i] += 1;
+            j += 1;
+        }
+        i += e.norm[i];
+    }
+}
+
+/// Fathom's 'set_norm_pawn': the leading pawns and the other color's pawns come first
+fn set_norm_pawn(e: &mut Encoding, num: usize, pawns: [usize; 2]) {
+    e.norm = [0; MAX_PIECES];
+    e.norm[0] = pawns[0];
+    if pawns[1] > 0 {
+        e.norm[pawns[0]] = pawns[1];
+    }
+    let mut i = pawns[0] + pawns[1];
+    while i < num {
+        let mut j = i;
+        while j < num && e.pieces[j] == e.pieces[i] {
+            e.norm[i] += 1;
+            j += 1;
+        }
+        i += e.norm[i];
+    }
+}
+
+/// Fathom's 'calc_factors_piece': the groups are multiplied in the order given by the table,
+/// 'order' tells where the first group goes. Returns the size of the table.
+fn calc_factors_piece(e: &mut Encoding, num: usize, order: usize, enc_type: usize) -> u64 {
+    let mut n = 64 - e.norm[0];
+    let mut f = 1;
+    let mut i = e.norm[0];
+    let mut k = 0;
+    while i < num || k == order {
+        if k == order {
+            e.factor[0] = f;
+            f *= PIVOT_FACTOR[enc_type];
+        } else {
+            e.factor[i] = f;
+            f *= subfactor(e.norm[i], n);
+            n -= e.norm[i];
+            i += e.norm[i];
+        }
+        k += 1;
+    }
+    f
+}
+
+/// Fathom's 'calc_factors_pawn', 'order2' tells where the other color's pawns go
+fn calc_factors_pawn(e: &mut Encoding, num: usize, order: usize, order2: usize, file: usize, ind: &Indices) -> u64 {
+    let mut i = e.norm[0];
+    if order2 < 0xf {
+        i += e.norm[i];
+    }
+    let mut n = 64 - i;
+    let mut f = 1;
+    let mut k = 0;
+    while i < num || k == order || k == order2 {
+        if k == order {
+            e.factor[0] = f;
+            f *= ind.pawn_factor[e.norm[0] - 1][file];
+        } else if k == order2 {
+            e.factor[e.norm[0]] = f;
+            f *= subfactor(e.norm[e.norm[0]], 48 - e.norm[0]);
+        } else {
+            e.factor[i] = f;
+            f *= subfactor(e.norm[i], n);
+            n -= e.norm[i];
+            i += e.norm[i];
+        }
+        k += 1;
+    }
+    f
+}
+
+/// Fathom's 'setup_pieces_piece' for one side: reads the order and the figures at 'at',
+/// 'side' picks the low or high half of each byte. Returns the encoding and the table size.
+fn setup_pieces_piece(bytes: &[u8], at: usize, side: usize, num: usize, enc_type: usize) -> (Encoding, u64) {
+    let shift = 4 * side;
+    let mut e = Encoding::default();
+    for i in 0..num {
+        e.pieces[i] = (byte(bytes, at + i + 1) >> shift) & 0xf;
+    }
+    let order = ((byte(bytes, at) >> shift) & 0xf) as usize;
+    set_norm_piece(&mut e, num, enc_type);
+    let size = calc_factors_piece(&mut e, num, order, enc_type);
+    (e, size)
+}
+
+/// Fathom's 'setup_pieces_pawn' for one side and one file of the leading pawn
+fn setup_pieces_pawn(bytes: &[u8], at: usize, side: usize, file: usize, num: usize, pawns: [usize; 2],
+                     ind: &Indices) -> (Encoding, u64) {
+    let shift = 4 * side;
+    let j = 1 + (pawns[1] > 0) as usize;
+    let mut e = Encoding::default();
+    for i in 0..num {
+        e.pieces[i] = (byte(bytes, at + i + j) >> shift) & 0xf;
+    }
+    let order = ((byte(bytes, at) >> shift) & 0xf) as usize;
+    let order2 = if pawns[1] > 0 { ((byte(bytes, at + 1) >> shift) & 0xf) as usize } else { 0xf };
+    set_norm_pawn(&mut e, num, pawns);
+    let size = calc_factors_pawn(&mut e, num, order, order2, file, ind);
+    (e, size)
+}
+
+/// Fathom's 'calc_symlen': how many values a symbol expands to, minus one
+fn calc_symlen(bytes: &[u8], d: &mut PairsData, s: usize, visited: &mut Vec<bool>) {
+    let w = d.sympat + 3 * s;
+    let s2 = ((byte(bytes, w + 2) as usize) << 4) | (byte(bytes, w + 1) as usize >> 4);
+    if s2 == 0xfff {
+        d.symlen[s] = 0;
+    } else {
+        let s1 = ((byte(bytes, w + 1) as usize & 0xf) << 8) | byte(bytes, w) as usize;
+        for &t in [s1, s2].iter() {
+            if t < visited.len() && !visited[t] {
+                calc_symlen(bytes, d, t, visited);
+            }
+        }
+        let len = |t: usize| if t < d.symlen.len() { d.symlen[t] } else { 0 };
+        d.symlen[s] = len(s1).wrapping_add(len(s2)).wrapping_add(1);
+    }
+    visited[s] = true;
+}
+
+/// Fathom's 'setup_pairs': reads the header of the compressed data at 'at' for a table part with
+/// 'tb_size' values. Returns the data and where the next header starts.
+fn setup_pairs(bytes: &[u8], at: usize, tb_size: u64, wdl: bool) -> (PairsData, usize) {
+    let mut d = PairsData::default();
+    d.flags = byte(bytes, at);
+    if d.flags & 0x80 != 0 {
+        // All values are the same, it is stored as the minimum code length
+        d.min_len = if wdl { byte(bytes, at + 1) } else { 0 };
+        return (d, at + 2)
+    }
+
+    d.block_size = byte(bytes, at + 1) as u32;
+    d.idx_bits = byte(bytes, at + 2) as u32;
+    let real_num_blocks = u32_le(bytes, at + 4) as usize;
+    let num_blocks = real_num_blocks + byte(bytes, at + 3) as usize;
+    let max_len = byte(bytes, at + 8) as usize;
+    d.min_len = byte(bytes, at + 9);
+    let h = (max_len + 1).saturating_sub(d.min_len as usize).max(1);
+    let num_syms = u16_le(bytes, at + 10 + 2 * h) as usize;
+    d.offset = at + 10;
+    d.sympat = at + 12 + 2 * h;
+    let next = at + 12 + 2 * h + 3 * num_syms + (num_syms & 1);
+
+    let num_indices = (tb_size + (1 << d.idx_bits) - 1) >> d.idx_bits;
+    d.sizes = [6 * num_indices as usize, 2 * num_blocks, (1 << d.block_size) * real_num_blocks];
+
+    d.symlen = vec![0; num_syms];
+    let mut visited = vec![false; num_syms];
+    for s in 0..num_syms {
+        if !visited[s] {
+            calc_symlen(bytes, &mut d, s, &mut visited);
+        }
+    }
+
+    // Longer codes have smaller values, base[i] is the smallest code of length min_len + i
+    d.base = vec![0; h];
+    for i in (0..h - 1).rev() {
+        d.base[i] = d.base[i + 1].wrapping_add(u16_le(bytes, d.offset + 2 * i))
+                                 .wrapping_sub(u16_le(bytes, d.offset + 2 * i + 2)) / 2;
+    }
+    for i in 0..h {
+        d.base[i] = d.base[i].checked_shl(64 - (d.min_len as u32 + i as u32)).unwrap_or(0);
+    }
+
+    (d, next)
+}
+
+/// Fathom's 'decompress_pairs': returns the value stored at 'idx'
+fn decompress_pairs(bytes: &[u8], d: &PairsData, idx: u64) -> u8 {
+    if d.idx_bits == 0 {
+        return d.min_len
+    }
+
+    // The index table points to the block and offset of the value in the middle of every span
+    let main_idx = (idx >> d.idx_bits) as usize;
+    let mut lit_idx = (idx & ((1 << d.idx_bits) - 1)) as i64 - (1i64 << (d.idx_bits - 1));
+    let mut block = u32_le(bytes, d.index_table + 6 * main_idx) as usize;
+    lit_idx += u16_le(bytes, d.index_table + 6 * main_idx + 4) as i64;
+
+    let block_len = |b: usize| u16_le(bytes, d.size_table + 2 * b) as i64;
+    if lit_idx < 0 {
+        while lit_idx < 0 && block > 0 {
+            block -= 1;
+            lit_idx += block_len(block) + 1;
+        }
+    } else {
+        while lit_idx > block_len(block) {
+            lit_idx -= block_len(block) + 1;
+            block += 1;
+        }
+    }
+
+    // Read codes until we reach the symbol that holds our value
+    let mut ptr = d.data + (block << d.block_size);
+    let m = d.min_len as usize;
+    let symlen = |s: usize| if s < d.symlen.len() { d.symlen[s] as i64 } else { 0 };
+    let mut code = u64_be(bytes, ptr);
+    ptr += 8;
+    let mut bit_count = 0;
+    let mut sym;
+    loop {
+        let mut l = 0;
+        while l + 1 < d.base.len() && code < d.base[l] {
+            l += 1;
+        }
+        sym = u16_le(bytes, d.offset + 2 * l) as usize +
+              (code - d.base[l]).checked_shr(64 - (m + l) as u32).unwrap_or(0) as usize;
+        if lit_idx < symlen(sym) + 1 {
+            break
+        }
+        lit_idx -= symlen(sym) + 1;
+        code = code.checked_shl((m + l) as u32).unwrap_or(0);
+        bit_count += m + l;
+        if bit_count >= 32 {
+            bit_count -= 32;
+            code |= u32_be(bytes, ptr) << bit_count;
+            ptr += 4;
+        }
+    }
+
+    // Each symbol stands for a pair of symbols, follow the one that holds our value
+    while symlen(sym) != 0 {
+        let w = d.sympat + 3 * sym;
+        let s1 = ((byte(bytes, w + 1) as usize & 0xf) << 8) | byte(bytes, w) as usize;
+        if lit_idx < symlen(s1) + 1 {
+            sym = s1;
+        } else {
+            lit_idx -= symlen(s1) + 1;
+            sym = ((byte(bytes, w + 2) as usize) << 4) | (byte(bytes, w + 1) as usize >> 4);
+        }
+    }
+
+    byte(bytes, d.sympat + 3 * sym)
+}
+
+/// Fathom's 'encode_piece': the index of the figures on the squares 'pos' in a table without pawns
+fn encode_piece(e: &Encoding, enc_type: usize, ind: &Indices, pos: &mut [usize]) -> u64 {
+    let n = pos.len();
+
+    // Mirror so the first figure is in the a1-d1-d4 triangle
+    if pos[0] & 0x04 != 0 {
+        for p in pos.iter_mut() {
+            *p ^= 0x07;
+        }
+    }
+    if pos[0] & 0x20 != 0 {
+        for p in pos.iter_mut() {
+            *p ^= 0x38;
+        }
+    }
+    let first = (0..n).position(|i| off_diag(pos[i]) != 0).unwrap_or(n);
+    if first < (if enc_type == 0 { 3 } else { 2 }) && off_diag(pos[first]) > 0 {
+        for p in pos.iter_mut() {
+            *p = flip_diag(*p);
+        }
+    }
+
+    let mut idx;
+    let mut i;
+    if enc_type == 0 {
+        let a = (pos[1] > pos[0]) as usize;
+        let b = (pos[2] > pos[0]) as usize + (pos[2] > pos[1]) as usize;
+        idx = if off_diag(pos[0]) != 0 {
+            TRIANGLE[pos[0]] as u64 * 63 * 62 + (pos[1] - a) as u64 * 62 + (pos[2] - b) as u64
+        } else if off_diag(pos[1]) != 0 {
+            6 * 63 * 62 + DIAG[pos[0]] * 28 * 62 + LOWER[pos[1]] * 62 + (pos[2] - b) as u64
+        } else if off_diag(pos[2]) != 0 {
+            6 * 63 * 62 + 4 * 28 * 62 + DIAG[pos[0]] * 7 * 28 + (DIAG[pos[1]] - a as u64) * 28 + LOWER[pos[2]]
+        } else {
+            6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + DIAG[pos[0]] * 7 * 6 + (DIAG[pos[1]] - a as u64) * 6 +
+            (DIAG[pos[2]] - b as u64)
+        };
+        i = 3;
+    } else {
+        idx = ind.kk_idx[TRIANGLE[pos[0]] as usize][pos[1]];
+        i = 2;
+    }
+    idx *= e.factor[0];
+
+    // Each further group is numbered by its squares, not counting those of earlier groups
+    while i < n {
+        let t = e.norm[i];
+        pos[i..i + t].sort();
+        let mut s = 0;
+        for m in i..i + t {
+            let p = pos[m];
+            let j = pos[..i].iter().filter(|&&q| p > q).count();
+            s += ind.binomial[m - i][p - j];
+        }
+        idx += s * e.factor[i];
+        i += t;
+    }
+
+    idx
+}
+
+/// Fathom's 'pawn_file': moves the leading pawn to the front and returns its file mirrored to a to d
+fn pawn_file(lead_pawns: usize, pos: &mut [usize]) -> usize {
+    for i in 1..lead_pawns {
+        if FLAP[pos[0]] > FLAP[pos[i]] {
+            pos.swap(0, i);
+        }
+    }
+    FILE_TO_FILE[pos[0] & 0x07]
+}
+
+/// Fathom's 'encode_pawn': the index of the figures on the squares 'pos' in a table with pawns
+fn encode_pawn(e: &Encoding, pawns: [usize; 2], ind: &Indices, pos: &mut [usize]) -> u64 {
+    let n = pos.len();
+
+    if pos[0] & 0x04 != 0 {
+        for p in pos.iter_mut() {
+            *p ^= 0x07;
+        }
+    }
+
+    // The other leading pawns, ordered from the highest 'PTWIST' number down
+    for i in 1..pawns[0] {
+        for j in i + 1..pawns[0] {
+            if PTWIST[pos[i]] < PTWIST[pos[j]] {
+                pos.swap(i, j);
+            }
+        }
+    }
+
+    let t = pawns[0] - 1;
+    let mut idx = ind.pawn_idx[t][FLAP[pos[0]] as usize];
+    for i in (1..t + 1).rev() {
+        idx += ind.binomial[t - i][PTWIST[pos[i]] as usize];
+    }
+    idx *= e.factor[0];
+
+    // The other color's pawns can only stand on 48 squares
+    let mut i = pawns[0];
+    let t = i + pawns[1];
+    if t > i {
+        pos[i..t].sort();
+        let mut s = 0;
+        for m in i..t {
+            let p = pos[m];
+            let j = pos[..i].iter().filter(|&&q| p > q).count();
+            s += ind.binomial[m - i][p - j - 8];
+        }
+        idx += s * e.factor[i];
+        i = t;
+    }
+
+    while i < n {
+        let t = e.norm[i];
+        pos[i..i + t].sort();
+        let mut s = 0;
+        for m in i..i + t {
+            let p = pos[m];
+            let j = pos[..i].iter().filter(|&&q| p > q).count();
+            s += ind.binomial[m - i][p - j];
+        }
+        idx += s * e.factor[i];
+        i += t;
+    }
+
+    idx
+}
+
+impl Table {
+    /// Fathom's 'init_table' and 'init_table_dtz' for a table named like 'KRvK'
+    fn load(bytes: Vec<u8>, name: &str, dtz: bool, ind: &Indices) -> Option<Self> {
+        let magic = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+        if bytes.len() < 5 || bytes[..4] != magic[..] {
+            return None
+        }
+
+        let mut parts = name.split('v');
+        let white = material_counts(parts.next().unwrap_or(""));
+        let black = material_counts(parts.next().unwrap_or(""));
+        let num = white.iter().sum::<usize>() + black.iter().sum::<usize>();
+        if num > MAX_PIECES || num < 3 {
+            return None
+        }
+
+        // Three or more figures that are the only ones of their kind and color are encoded together
+        let unique = white.iter().chain(black.iter()).filter(|&&c| c == 1).count();
+        // The color with less pawns leads, because that compresses better
+        let pawns = if black[5] == 0 || (white[5] > 0 && black[5] >= white[5]) {
+            [white[5], black[5]]
+        } else {
+            [black[5], white[5]]
+        };
+        let mut table = Table{ bytes: Vec::new(), dtz: dtz, num: num, symmetric: white == black,
+                               has_pawns: white[5] + black[5] > 0, enc_type: if unique >= 3 { 0 } else { 2 },
+                               pawns: pawns, parts: Vec::new(), map: 0 };
+
+        let split = !dtz && bytes[4] & 0x01 != 0;
+        let files = if table.has_pawns && bytes[4] & 0x02 != 0 { 4 } else { 1 };
+        let sides = if split { 2 } else { 1 };
+        let mut at = 5;
+
+        let mut encodings = Vec::new();
+        if table.has_pawns {
+            let s = 1 + (pawns[1] > 0) as usize;
+            for f in 0..4 {
+                encodings.push((0..sides).map(|side| setup_pieces_pawn(&bytes, at, side, f, num, pawns, ind))
+                                         .collect::<Vec<_>>());
+                at += num + s;
+            }
+        } else {
+            encodings.push((0..sides).map(|side| setup_pieces_piece(&bytes, at, side, num, table.enc_type))
+                                     .collect::<Vec<_>>());
+            at += num + 1;
+        }
+        at += at & 1;
+
+        for f in 0..files {
+            let mut part = Vec::new();
+            for side in 0..sides {
+                let (ref e, size) = encodings[f][side];
+                let (d, next) = setup_pairs(&bytes, at, size, !dtz);
+                part.push((e.clone(), d));
+                at = next;
+            }
+            table.parts.push(part);
+        }
+
+        // DTZ values can be stored as numbers into a map for each outcome
+        if dtz {
+            table.map = at;
+            for f in 0..files {
+                let d = &mut table.parts[f][0].1;
+                if d.flags & 0x02 == 0 {
+                    continue
+                }
+                if d.flags & 0x10 != 0 {
+                    at += at & 1;
+                    for i in 0..4 {
+                        d.map_idx[i] = (at - table.map) / 2 + 1;
+                        at += 2 * u16_le(&bytes, at) as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        d.map_idx[i] = at + 1 - table.map;
+                        at += 1 + byte(&bytes, at) as usize;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for part in table.parts.iter_mut() {
+            for &mut (_, ref mut d) in part.iter_mut() {
+                d.index_table = at;
+                at += d.sizes[0];
+            }
+        }
+        for part in table.parts.iter_mut() {
+            for &mut (_, ref mut d) in part.iter_mut() {
+                d.size_table = at;
+                at += d.sizes[1];
+            }
+        }
+        for part in table.parts.iter_mut() {
+            for &mut (_, ref mut d) in part.iter_mut() {
+                at = (at + 0x3f) & !0x3f;
+                d.data = at;
+                at += d.sizes[2];
+            }
+        }
+
+        table.bytes = bytes;
+        Some(table)
+    }
+}
+
+/// A directory of Syzygy tablebase files
+pub struct Tablebase {
+    dir: PathBuf,
+    files: HashSet<String>,
+    max_pieces: usize,
+    indices: Indices,
+    tables: Mutex<HashMap<String, Option<Arc<Table>>>>
+}
+
+impl ::std::fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "Tablebase {{ {:?}, {} files }}", self.dir, self.files.len())
+    }
+}
+
+impl Tablebase {
+    /// Looks for tablebase files in 'dir', the files are only read once they are needed
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let mut files = HashSet::new();
+        let mut max_pieces = 0;
+
+        for entry in try!(fs::read_dir(dir.as_ref())) {
+            let name = try!(entry).file_name().to_string_lossy().into_owned();
+            if name.ends_with(".rtbw") || name.ends_with(".rtbz") {
+                let pieces = name.len() - 6;
+                if pieces <= MAX_PIECES {
+                    max_pieces = max_pieces.max(pieces);
+                    files.insert(name);
+                }
+            }
+        }
+
+        if files.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no Syzygy files found"))
+        }
+
+        Ok(Tablebase{ dir: dir.as_ref().to_path_buf(), files: files, max_pieces: max_pieces,
+                      indices: Indices::new(), tables: Mutex::new(HashMap::new()) })
+    }
+
+    /// Returns the most pieces a position may have to be found in the tables
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Returns whether the game's position has few enough pieces to be probed
+    pub fn covers(&self, game: &ChessGame) -> bool {
+        let pieces: usize = [&game.white_player, &game.black_player].iter()
+                                                                    .flat_map(|p| p.figures.values())
+                                                                    .map(|v| v.len())
+                                                                    .sum();
+        pieces <= self.max_pieces
+    }
+
+    /// Returns the table with the given name, reading it the first time
+    fn table(&self, name: &str, dtz: bool) -> Option<Arc<Table>> {
+        let file = format!("{}.{}", name, if dtz { "rtbz" } else { "rtbw" });
+        let mut tables = self.tables.lock().unwrap();
+
+        if let Some(t) = tables.get(&file) {
+            return t.clone()
+        }
+
+        let table = if self.files.contains(&file) {
+            let mut bytes = Vec::new();
+            File::open(self.dir.join(&file)).and_then(|mut f| f.read_to_end(&mut bytes)).ok()
+                                            .and_then(|_| Table::load(bytes, name, dtz, &self.indices))
+                                            .map(Arc::new)
+        } else {
+            None
+        };
+        tables.insert(file, table.clone());
+        table
+    }
+
+    /// Fathom's 'probe_wdl_table' and 'probe_dtz_table': looks up the game's position in a WDL
+    /// table, or in a DTZ table for a position with the outcome 'wdl'
+    fn probe_table(&self, game: &ChessGame, dtz: bool, wdl: i32) -> Result<i32, Probe> {
+        // The squares of each piece code, numbered from a1 to h8
+        let mut squares = vec![Vec::new(); 16];
+        let mut counts = [[0; 6]; 2];
+        let mut num = 0;
+        for y in 0..8u8 {
+            for x in 0..8u8 {
+                let pos = Position::new(x, y);
+                if let (Some(fig), Some(col)) = (game.board.get_figure(pos), game.board.get_figure_color(pos)) {
+                    let code = piece_code(fig, col);
+                    squares[code as usize].push((y * 8 + x) as usize);
+                    counts[if col == Color::White { 0 } else { 1 }][6 - (code & 7) as usize] += 1;
+                    num += 1;
+                }
+            }
+        }
+        if num == 2 {
+            return Ok(0)
+        }
+
+        // Tables are stored with the stronger side as white, so we might have to swap colors
+        let (white, black) = (material_name(&counts[0]), material_name(&counts[1]));
+        let ext = if dtz { "rtbz" } else { "rtbw" };
+        let (name, mirrored) = if self.files.contains(&format!("{}v{}.{}", white, black, ext)) {
+            (format!("{}v{}", white, black), false)
+        } else {
+            (format!("{}v{}", black, white), true)
+        };
+        let table = match self.table(&name, dtz) {
+            Some(t) => t,
+            None => return Err(Probe::Fail)
+        };
+
+        let (cmirror, mirror, bside) = if !table.symmetric {
+            if mirrored { (8, 0x38, game.turn as usize) } else { (0, 0, !game.turn as usize) }
+        } else if game.turn {
+            (0, 0, 0)
+        } else {
+            (8, 0x38, 0)
+        };
+        let side = if table.parts[0].len() == 2 { bside } else { 0 };
+
+        // Adds the squares of the figures in the order of the table, returns false if they do not fit
+        let add = |p: &mut Vec<usize>, pieces: &[u8; MAX_PIECES], mirror: usize| {
+            while p.len() < table.num {
+                let code = (pieces[p.len()] ^ cmirror) as usize;
+                if code >= 16 || squares[code].is_empty() {
+                    return false
+                }
+                for &sq in squares[code].iter() {
+                    p.push(sq ^ mirror);
+                }
+            }
+            p.len() == table.num
+        };
+
+        let mut p = Vec::with_capacity(table.num);
+        let (idx, part, flags) = if !table.has_pawns {
+            let &(ref e, ref d) = &table.parts[0][side];
+            if dtz && (d.flags & 1) as usize != bside && !table.symmetric {
+                return Err(Probe::ChangeStm)
+            }
+            if !add(&mut p, &e.pieces, 0) {
+                return Err(Probe::Fail)
+            }
+            (encode_piece(e, table.enc_type, &self.indices, &mut p), d, d.flags)
+        } else {
+            let lead = (table.parts[0][0].0.pieces[0] ^ cmirror) as usize;
+            for &sq in squares[lead].iter() {
+                p.push(sq ^ mirror);
+            }
+            if p.is_empty() {
+                return Err(Probe::Fail)
+            }
+            let f = pawn_file(table.pawns[0], &mut p);
+            if f >= table.parts.len() {
+                return Err(Probe::Fail)
+            }
+            let &(ref e, ref d) = &table.parts[f][side];
+            if dtz && (d.flags & 1) as usize != bside {
+                return Err(Probe::ChangeStm)
+            }
+            if !add(&mut p, &e.pieces, mirror) {
+                return Err(Probe::Fail)
+            }
+            (encode_pawn(e, table.pawns, &self.indices, &mut p), d, d.flags)
+        };
+
+        let mut res = decompress_pairs(&table.bytes, part, idx) as i32;
+        if !dtz {
+            return Ok(res - 2)
+        }
+
+        if flags & 0x02 != 0 {
+            let at = part.map_idx[WDL_TO_MAP[(wdl + 2) as usize]] + res as usize;
+            res = if flags & 0x10 != 0 {
+                u16_le(&table.bytes, table.map + 2 * at) as i32
+            } else {
+                byte(&table.bytes, table.map + at) as i32
+            };
+        }
+        if flags & PA_FLAGS[(wdl + 2) as usize] == 0 || wdl & 1 != 0 {
+            res *= 2;
+        }
+        Ok(res)
+    }
+
+    /// Fathom's 'probe_ab': the outcome with all captures searched, because the tables may store
+    /// wrong values when a capture is best. Also returns whether a winning capture reaches it.
+    fn probe_ab(&self, game: &ChessGame, mut alpha: i32, beta: i32) -> Result<(i32, bool), Probe> {
+        let mut root = game.clone();
+        for (from, to) in root.possible_moves() {
+            if !game.board.is_capture_move(from, to) {
+                continue
+            }
+            let mut child = game.clone();
+            child.make_move(from, to);
+            let v = -try!(self.probe_ab(&child, -beta, -alpha)).0;
+            if v > alpha {
+                if v >= beta {
+                    return Ok((v, true))
+                }
+                alpha = v;
+            }
+        }
+
+        let v = try!(self.probe_table(game, false, 0));
+        if alpha >= v {
+            Ok((alpha, alpha > 0))
+        } else {
+            Ok((v, false))
+        }
+    }
+
+    /// Returns the outcome of the game's position for the player whose turn it is
+    pub fn probe_wdl(&self, game: &ChessGame) -> Option<Wdl> {
+        if !self.covers(game) {
+            return None
+        }
+        self.probe_ab(game, -2, 2).ok().map(|(v, _)| Wdl::from_value(v))
+    }
+
+    /// Returns the number of plies to the next capture or pawn move when playing perfectly,
+    /// positive if the player whose turn it is wins and negative if they lose, zero for draws
+    pub fn probe_dtz(&self, game: &ChessGame) -> Option<i32> {
+        if !self.covers(game) {
+            return None
+        }
+        self.dtz(game).ok()
+    }
+
+    /// Fathom's 'probe_dtz_no_ep', there is no en passant in our rules
+    fn dtz(&self, game: &ChessGame) -> Result<i32, Probe> {
+        let (wdl, capture_best) = try!(self.probe_ab(game, -2, 2));
+        if wdl == 0 {
+            return Ok(0)
+        }
+        if capture_best {
+            return Ok(if wdl == 2 { 1 } else { 101 })
+        }
+
+        let moves = game.clone().possible_moves();
+        let is_pawn = |from: Position| game.board.get_figure(from) == Some(Figure::Pawn);
+
+        // A pawn move that keeps the win is best
+        if wdl > 0 {
+            for &(from, to) in moves.iter().filter(|&&(f, t)| is_pawn(f) && !game.board.is_capture_move(f, t)) {
+                let mut child = game.clone();
+                child.make_move(from, to);
+                let v = -try!(self.probe_ab(&child, -2, -wdl + 1)).0;
+                if v == wdl {
+                    return Ok(if v == 2 { 1 } else { 101 })
+                }
+            }
+        }
+
+        match self.probe_table(game, true, wdl) {
+            Ok(dtz) => {
+                let dtz = 1 + dtz + if wdl & 1 != 0 { 100 } else { 0 };
+                return Ok(if wdl >= 0 { dtz } else { -dtz })
+            },
+            Err(Probe::Fail) => return Err(Probe::Fail),
+            Err(Probe::ChangeStm) => ()
+        }
+
+        // The table stores the other side to move, so look at the moves
+        if wdl > 0 {
+            let mut best = 0xffff;
+            for &(from, to) in moves.iter().filter(|&&(f, t)| !is_pawn(f) && !game.board.is_capture_move(f, t)) {
+                let mut child = game.clone();
+                child.make_move(from, to);
+                let v = -try!(self.dtz(&child));
+                if v > 0 && v + 1 < best {
+                    best = v + 1;
+                }
+            }
+            Ok(best)
+        } else {
+            let mut best = -1;
+            for &(from, to) in moves.iter() {
+                let zeroing = is_pawn(from) || game.board.is_capture_move(from, to);
+                let mut child = game.clone();
+                child.make_move(from, to);
+                let v = if !zeroing {
+                    -try!(self.dtz(&child)) - 1
+                } else if wdl == -2 {
+                    -1
+                } else if try!(self.probe_ab(&child, 1, 2)).0 == 2 {
+                    0
+                } else {
+                    -101
+                };
+                if v < best {
+                    best = v;
+                }
+            }
+            Ok(best)
+        }
+    }
+
+    /// Returns the move that keeps the best outcome and reaches it the fastest
+    pub fn best_move(&self, game: &ChessGame) -> Option<(Position, Position)> {
+        if !self.covers(game) {
+            return None
+        }
+
+        let mut root = game.clone();
+        let mut best = None;
+        let mut best_rank = (-3, 0);
+        for (from, to) in root.possible_moves() {
+            let zeroing = game.board.is_capture_move(from, to) || game.board.get_figure(from) == Some(Figure::Pawn);
+            let mut child = game.clone();
+            child.make_move(from, to);
+
+            // Mate is always best
+            let replies = child.clone().possible_moves();
+            if replies.is_empty() && child.in_check() {
+                return Some((from, to))
+            }
+
+            let wdl = match self.probe_wdl(&child) {
+                Some(w) => -w.value(),
+                None => return None
+            };
+            let dtz = match self.probe_dtz(&child) {
+                Some(d) => d.abs(),
+                None => return None
+            };
+
+            // When winning prefer captures and pawn moves and then the shortest way,
+            // when losing the longest way
+            let rank = match wdl.signum() {
+                1 => (wdl, if zeroing { 0 } else { -dtz }),
+                -1 => (wdl, dtz),
+                _ => (wdl, 0)
+            };
+            if best.is_none() || rank > best_rank {
+                best = Some((from, to));
+                best_rank = rank;
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The eight ways to mirror and rotate the board
+    fn symmetries(sq: usize, s: usize) -> usize {
+        let sq = if s & 1 != 0 { flip_diag(sq) } else { sq };
+        let sq = if s & 2 != 0 { sq ^ 0x07 } else { sq };
+        if s & 4 != 0 { sq ^ 0x38 } else { sq }
+    }
+
+    /// Checks that positions give the same index exactly if one is a mirror image of the other
+    fn check_classes(indices: &[(Vec<usize>, u64)], classes: &[Vec<usize>]) {
+        let mut by_class = HashMap::new();
+        let mut by_index = HashMap::new();
+        for (&(ref pos, idx), class) in indices.iter().zip(classes.iter()) {
+            assert_eq!(*by_class.entry(class.clone()).or_insert(idx), idx, "{:?} gives another index", pos);
+            assert_eq!(*by_index.entry(idx).or_insert(class.clone()), *class, "{:?} reuses index {}", pos, idx);
+        }
+    }
+
+    #[test]
+    fn decompress_literals_and_pairs() {
+        // Symbols 0 to 4 are the values themselves, symbol 5 is the pair of symbols 0 and 4,
+        // every code has three bits and a block holds twenty codes
+        let mut bytes = vec![0x00, 4, 3, 0, 0, 0, 0, 0, 3, 3, 0, 0, 6, 0];
+        for s in 0..5 {
+            bytes.extend_from_slice(&[s, 0xf0, 0xff]);
+        }
+        bytes.extend_from_slice(&[0x00, 0x40, 0x00]);
+
+        let mut blocks = Vec::new();
+        let mut values = Vec::new();
+        for b in 0..5 {
+            blocks.push(values.len());
+            let mut code = 0u128;
+            for i in 0..20 {
+                let sym = (b * 20 + i * 7 + 3) % 6;
+                code |= (sym as u128) << (125 - 3 * i);
+                if sym == 5 {
+                    values.extend_from_slice(&[0, 4]);
+                } else {
+                    values.push(sym as u8);
+                }
+            }
+            let mut data = [0u8; 16];
+            for i in 0..16 {
+                data[i] = (code >> (120 - 8 * i)) as u8;
+            }
+            bytes.extend_from_slice(&data);
+        }
+        let data: Vec<u8> = bytes.split_off(32);
+        bytes[4] = blocks.len() as u8;
+
+        let (mut d, next) = setup_pairs(&bytes, 0, values.len() as u64, true);
+        assert_eq!(next, 32);
+        assert_eq!(d.symlen, vec![0, 0, 0, 0, 0, 1]);
+
+        // The index table points to the middle of every span of eight values
+        d.index_table = bytes.len();
+        for main in 0..(values.len() + 7) / 8 {
+            let mid = (main * 8 + 4).min(values.len() - 1);
+            let block = blocks.iter().rposition(|&start| start <= mid).unwrap();
+            bytes.extend_from_slice(&[block as u8, 0, 0, 0, (mid - blocks[block]) as u8, 0]);
+        }
+        d.size_table = bytes.len();
+        for b in 0..blocks.len() {
+            let end = if b + 1 < blocks.len() { blocks[b + 1] } else { values.len() };
+            bytes.extend_from_slice(&[(end - blocks[b] - 1) as u8, 0]);
+        }
+        while bytes.len() % 64 != 0 {
+            bytes.push(0);
+        }
+        d.data = bytes.len();
+        bytes.extend_from_slice(&data);
+
+        for (idx, &v) in values.iter().enumerate() {
+            assert_eq!(decompress_pairs(&bytes, &d, idx as u64), v, "value {}", idx);
+        }
+    }
+
+    #[test]
+    fn king_pairs() {
+        let ind = Indices::new();
+        let mut e = Encoding::default();
+        e.factor[0] = 1;
+
+        let mut indices = Vec::new();
+        let mut classes = Vec::new();
+        for s1 in 0..64 {
+            for s2 in 0..64 {
+                let touching = ((s1 & 7) as i32 - (s2 & 7) as i32).abs() <= 1 &&
+                               ((s1 >> 3) as i32 - (s2 >> 3) as i32).abs() <= 1;
+                if touching {
+                    continue
+                }
+                let idx = encode_piece(&e, 2, &ind, &mut [s1, s2]);
+                assert!(idx < PIVOT_FACTOR[2]);
+                indices.push((vec![s1, s2], idx));
+                classes.push((0..8).map(|s| vec![symmetries(s1, s), symmetries(s2, s)]).min().unwrap());
+            }
+        }
+        check_classes(&indices, &classes);
+
+        let distinct: HashSet<u64> = indices.iter().map(|&(_, idx)| idx).collect();
+        assert_eq!(distinct.len(), 462);
+    }
+
+    #[test]
+    fn three_unique_pieces() {
+        let ind = Indices::new();
+        // KRvK: order, then white king, white rook and black king
+        let (e, size) = setup_pieces_piece(&[0x00, 0x06, 0x04, 0x0e], 0, 0, 3, 0);
+        assert_eq!(size, PIVOT_FACTOR[0]);
+
+        let mut indices = Vec::new();
+        let mut classes = Vec::new();
+        for s1 in 0..64 {
+            for s2 in (0..64).filter(|&s| s != s1) {
+                for s3 in (0..64).filter(|&s| s != s1 && s != s2) {
+                    let idx = encode_piece(&e, 0, &ind, &mut [s1, s2, s3]);
+                    assert!(idx < size);
+                    indices.push((vec![s1, s2, s3], idx));
+                    classes.push((0..8).map(|s| vec![symmetries(s1, s), symmetries(s2, s), symmetries(s3, s)])
+                                       .min().unwrap());
+                }
+            }
+        }
+        check_classes(&indices, &classes);
+    }
+
+    #[test]
+    fn pawn_files() {
+        let ind = Indices::new();
+        // KPvK: order, then white pawn, white king and black king
+        let bytes = [0x00, 0x01, 0x06, 0x0e];
+
+        for file in 0..4 {
+            let (e, size) = setup_pieces_pawn(&bytes, 0, 0, file, 3, [1, 0], &ind);
+            assert_eq!(size, 6 * 63 * 62);
+
+            let mut indices = Vec::new();
+            let mut classes = Vec::new();
+            for p in (8..56).filter(|&p| FILE_TO_FILE[p & 7] == file) {
+                for k1 in (0..64).filter(|&s| s != p) {
+                    for k2 in (0..64).filter(|&s| s != p && s != k1) {
+                        let mut pos = [p, k1, k2];
+                        assert_eq!(pawn_file(1, &mut pos), file);
+                        let idx = encode_pawn(&e, [1, 0], &ind, &mut pos);
+                        assert!(idx < size);
+                        indices.push((vec![p, k1, k2], idx));
+                        classes.push(vec![p, k1, k2].min(vec![p ^ 7, k1 ^ 7, k2 ^ 7]));
+                    }
+                }
+            }
+            check_classes(&indices, &classes);
+        }
+    }
+
+    /// Opens the tables in the directory given by SYZYGY_PATH, which should hold the tables
+    /// with up to four pieces
+    fn tablebase() -> Tablebase {
+        let dir = ::std::env::var("SYZYGY_PATH").expect("set SYZYGY_PATH to a directory with Syzygy tables");
+        Tablebase::open(dir).unwrap()
+    }
+
+    #[test]
+    #[ignore]
+    fn probe_known_positions() {
+        let tb = tablebase();
+        let probe = |fen: &str| {
+            let game = ChessGame::from_fen(fen).unwrap();
+            (tb.probe_wdl(&game).unwrap(), tb.probe_dtz(&game).unwrap())
+        };
+
+        // Mate in one and mated after one move
+        assert_eq!(probe("6k1/8/6K1/8/8/8/8/R7 w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe("7k/8/6K1/8/8/8/8/R7 b - - 0 1"), (Wdl::Loss, -2));
+        assert_eq!(probe("8/8/8/8/8/8/8/KB5k w - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe("r7/8/8/8/8/8/8/1K5k w - - 0 1").0, Wdl::Loss);
+        assert_eq!(probe("8/8/8/8/8/8/4P3/4K2k w - - 0 1").0, Wdl::Win);
+        assert_eq!(probe("8/8/8/8/8/8/4p3/4k2K b - - 0 1").0, Wdl::Win);
+    }
+
+    #[test]
+    #[ignore]
+    fn best_move_mates() {
+        let tb = tablebase();
+        let game = ChessGame::from_fen("6k1/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+        let mate = (Position::from_algebraic("a1").unwrap(), Position::from_algebraic("a8").unwrap());
+        assert_eq!(tb.best_move(&game), Some(mate));
+    }
+}
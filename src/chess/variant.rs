@@ -0,0 +1,687 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Rules of chess variants. Standard chess is the default of every rule, so a variant only
+//! says what is different: its start position, which moves are legal, how it is won and
+//! what happens after a move.
+
+use std::sync::Arc;
+
+use chess::{ChessGame, START_FEN};
+use chess::event::GameEvent;
+use chess::logic::{Board, Color, Figure, Position, RESERVE_FIGURES};
+
+/// Rules of a chess variant
+pub trait Variant: Send + Sync {
+    /// Name of the variant on the command line and in the 'Variant' tag of PGN files
+    fn name(&self) -> &'static str;
+
+    /// Whether the rules are those of standard chess, only then opening books,
+    /// endgame tablebases and external engines are used
+    fn is_standard(&self) -> bool {
+        false
+    }
+
+    /// Whether captured figures go into a reserve from which they can be dropped,
+    /// positions then show the reserves in their FEN
+    fn has_drops(&self) -> bool {
+        false
+    }
+
+    /// Whether players only see the fields their figures can move to, the AI then
+    /// plays with what its own figures see
+    fn hides_figures(&self) -> bool {
+        false
+    }
+
+    /// Position the games of this variant start from
+    fn start_fen(&self) -> &'static str {
+        START_FEN
+    }
+
+    /// Returns whether the player whose turn it is may move from 'from' to 'to'
+    fn is_legal(&self, game: &mut ChessGame, from: Position, to: Position) -> bool {
+        standard_is_legal(game, from, to)
+    }
+
+    /// Returns all moves the player whose turn it is may make
+    fn legal_moves(&self, game: &mut ChessGame) -> Vec<(Position, Position)> {
+        standard_moves(game)
+    }
+
+    /// Returns whether the king of the player whose turn it is is in check
+    fn in_check(&self, game: &ChessGame) -> bool {
+        standard_in_check(game)
+    }
+
+    /// Makes the special changes of the variant after the move from 'from' to 'to' was made
+    /// by the standard rules, 'captured' is the figure it took where a promoted figure counts
+    /// as the pawn it was
+    fn after_move(&self, _game: &mut ChessGame, _from: Position, _to: Position, _captured: Option<Figure>) {}
+
+    /// Returns the player who won by a rule of the variant with the reason, checkmate
+    /// and stalemate are found by the game itself. It is asked after every move, also
+    /// during the AI's search, so it has to be quick.
+    fn winner(&self, _game: &ChessGame) -> Option<(Color, &'static str)> {
+        None
+    }
+}
+
+/// Returns whether the move is valid by the standard rules for the player whose turn it is
+pub fn standard_is_legal(game: &mut ChessGame, from: Position, to: Position) -> bool {
+    // Dropping figures is not part of the standard rules
+    if from.reserve_figure().is_some() || game.board.get_figure_color(from) != Some(game.turn_color()) {
+        return false
+    }
+
+    let valid = if game.turn {
+        game.board.is_move_valid(from, to, &mut game.white_player, &mut game.black_player)
+    } else {
+        game.board.is_move_valid(from, to, &mut game.black_player, &mut game.white_player)
+    };
+    valid || special_moves(game).contains(&(from, to))
+}
+
+/// Returns whether the king of the player whose turn it is is attacked by the standard rules
+pub fn standard_in_check(game: &ChessGame) -> bool {
+    if game.turn {
+        game.board.in_check(game.white_player.king(), &game.black_player)
+    } else {
+        game.board.in_check(game.black_player.king(), &game.white_player)
+    }
+}
+
+/// Returns all valid moves by the standard rules for the player whose turn it is
+pub fn standard_moves(game: &mut ChessGame) -> Vec<(Position, Position)> {
+    let mut moves = if game.turn {
+        game.white_player.get_possible_moves(&mut game.board, &mut game.black_player)
+    } else {
+        game.black_player.get_possible_moves(&mut game.board, &mut game.white_player)
+    };
+    moves.extend(special_moves(game));
+    moves
+}
+
+/// Returns the valid castling moves and en passant captures of the player whose turn it is.
+/// The figures cannot find them on their own, as they depend on the moves made before.
+/// Castling is written as the king's move of two fields.
+pub fn special_moves(game: &ChessGame) -> Vec<(Position, Position)> {
+    let color = game.turn_color();
+    let (me, opponent) = if game.turn { (&game.white_player, &game.black_player) } else { (&game.black_player, &game.white_player) };
+    let mut moves = Vec::new();
+
+    // The king may not castle out of, through or into check
+    let (kingside, queenside) = me.castling_rights();
+    if (kingside || queenside) && !standard_in_check(game) {
+        let row = me.king().y;
+        let safe = |x: u8| {
+            let mut board = game.board.clone();
+            board.move_figure(Position::new(4, row), Position::new(x, row));
+            !board.in_check(Position::new(x, row), opponent)
+        };
+        let empty = |xs: &[u8]| xs.iter().all(|&x| game.board.is_empty(Position::new(x, row)));
+        if kingside && empty(&[5, 6]) && safe(5) && safe(6) {
+            moves.push((Position::new(4, row), Position::new(6, row)));
+        }
+        if queenside && empty(&[1, 2, 3]) && safe(3) && safe(2) {
+            moves.push((Position::new(4, row), Position::new(2, row)));
+        }
+    }
+
+    // A pawn that moved two fields can be taken as if it had moved one
+    if let Some(target) = game.en_passant.filter(|&pos| game.board.is_empty(pos)) {
+        let (row, forward) = if color == Color::White { (4, 1) } else { (3, -1) };
+        let victim = Position::new(target.x, row);
+        let is_pawn = |pos: Position, c: Color| game.board.get_figure(pos) == Some(Figure::Pawn) && game.board.get_figure_color(pos) == Some(c);
+        if target.y as i8 == row as i8 + forward && is_pawn(victim, !color) {
+            for x in [target.x.wrapping_sub(1), target.x + 1].iter().cloned().filter(|&x| x < 8) {
+                let from = Position::new(x, row);
+                if !is_pawn(from, color) {
+                    continue
+                }
+                let mut board = game.board.clone();
+                let mut rest = opponent.clone();
+                board.move_figure(from, target);
+                board[victim].set_empty(victim);
+                rest.capture(Figure::Pawn.name(), victim);
+                if !board.in_check(me.king(), &rest) {
+                    moves.push((from, target));
+                }
+            }
+        }
+    }
+    moves
+}
+
+/// Standard chess
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "standard"
+    }
+
+    fn is_standard(&self) -> bool {
+        true
+    }
+}
+
+/// King of the Hill: a player also wins by bringing the king to one of the four center fields
+pub struct KingOfTheHill;
+
+/// Fields a king has to reach in King of the Hill
+const HILL: [(u8, u8); 4] = [(3, 3), (4, 3), (3, 4), (4, 4)];
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "kingofthehill"
+    }
+
+    fn winner(&self, game: &ChessGame) -> Option<(Color, &'static str)> {
+        let on_hill = |pos: Position| HILL.iter().any(|&(x, y)| pos.x == x && pos.y == y);
+        // Only the player who just moved can have reached the hill
+        let mover = if game.turn { &game.black_player } else { &game.white_player };
+        if on_hill(mover.king()) {
+            Some((mover.color(), "king of the hill"))
+        } else {
+            None
+        }
+    }
+}
+
+/// Three-check: a player also wins by giving check for the third time
+pub struct ThreeCheck;
+
+/// Checks a player has to give in Three-check
+const CHECKS_TO_WIN: u32 = 3;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "threecheck"
+    }
+
+    fn after_move(&self, game: &mut ChessGame, _from: Position, _to: Position, _captured: Option<Figure>) {
+        if game.in_check() {
+            // The player who moved gave the check
+            let mover = if game.turn { 1 } else { 0 };
+            game.checks[mover] += 1;
+        }
+    }
+
+    fn winner(&self, game: &ChessGame) -> Option<(Color, &'static str)> {
+        if game.checks[0] >= CHECKS_TO_WIN {
+            Some((Color::White, "three checks"))
+        } else if game.checks[1] >= CHECKS_TO_WIN {
+            Some((Color::Black, "three checks"))
+        } else {
+            None
+        }
+    }
+}
+
+/// Crazyhouse: captured figures join the reserve of the capturing player, who may drop one
+/// of them on an empty field instead of moving. Promoted figures go back as pawns.
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "crazyhouse"
+    }
+
+    fn has_drops(&self) -> bool {
+        true
+    }
+
+    fn is_legal(&self, game: &mut ChessGame, from: Position, to: Position) -> bool {
+        match from.reserve_figure() {
+            Some(fig) => {
+                let check = game.in_check();
+                game.active_player().reserve().contains(&fig) && drop_is_legal(game, fig, to, check)
+            },
+            None => standard_is_legal(game, from, to)
+        }
+    }
+
+    fn legal_moves(&self, game: &mut ChessGame) -> Vec<(Position, Position)> {
+        let mut moves = standard_moves(game);
+        let check = game.in_check();
+        let kinds: Vec<Figure> = RESERVE_FIGURES.iter().cloned().filter(|f| game.active_player().reserve().contains(f)).collect();
+        for fig in kinds {
+            for y in 0..8 {
+                for x in 0..8 {
+                    let to = Position::new(x, y);
+                    if drop_is_legal(game, fig, to, check) {
+                        moves.push((Position::reserve(fig), to));
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn after_move(&self, game: &mut ChessGame, _from: Position, _to: Position, captured: Option<Figure>) {
+        if let Some(fig) = captured {
+            if game.turn {
+                game.black_player.add_to_reserve(fig);
+            } else {
+                game.white_player.add_to_reserve(fig);
+            }
+        }
+    }
+}
+
+/// Returns whether the player whose turn it is may drop 'fig' from the reserve at 'to',
+/// 'check' tells whether the player is in check
+fn drop_is_legal(game: &mut ChessGame, fig: Figure, to: Position, check: bool) -> bool {
+    // Pawns may not be dropped on the first or last row
+    if !game.board.is_empty(to) || fig == Figure::Pawn && (to.y == 0 || to.y == 7) {
+        return false
+    }
+    // Another figure on the board never puts the own king in check, but it has to block a check
+    if !check {
+        return true
+    }
+
+    let color = game.turn_color();
+    game.board.set_figure(to, fig, color);
+    let blocks = if game.turn {
+        !game.board.in_check(game.white_player.king(), &game.black_player)
+    } else {
+        !game.board.in_check(game.black_player.king(), &game.white_player)
+    };
+    game.board[to].set_empty(to);
+    blocks
+}
+
+/// Atomic chess: a capture explodes, removing the capturing and the captured figure together
+/// with all figures but pawns around them. Kings may not capture, and blowing up the opponent's
+/// king wins, even out of check. Kings standing next to each other cannot give check.
+pub struct Atomic;
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "atomic"
+    }
+
+    fn is_legal(&self, game: &mut ChessGame, from: Position, to: Position) -> bool {
+        from.reserve_figure().is_none() && atomic_is_legal(game, from, to)
+    }
+
+    fn legal_moves(&self, game: &mut ChessGame) -> Vec<(Position, Position)> {
+        let mut moves = Vec::new();
+        let froms: Vec<Position> = game.active_player().figures.values().flat_map(|v| v.iter().cloned()).collect();
+        for from in froms {
+            for y in 0..8 {
+                for x in 0..8 {
+                    let to = Position::new(x, y);
+                    if atomic_is_legal(game, from, to) {
+                        moves.push((from, to));
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn in_check(&self, game: &ChessGame) -> bool {
+        atomic_in_check(&game.board, game.turn_color())
+    }
+
+    fn after_move(&self, game: &mut ChessGame, _from: Position, to: Position, captured: Option<Figure>) {
+        if captured.is_none() {
+            return
+        }
+
+        let mut figures = Vec::new();
+        for pos in blast(&game.board, to) {
+            let fig = game.board.get_figure(pos).unwrap();
+            let color = game.board.get_figure_color(pos).unwrap();
+            let owner = if color == Color::White { &mut game.white_player } else { &mut game.black_player };
+            owner.forget_promotion(pos);
+            owner.capture(fig.name(), pos);
+            game.board[pos].set_empty(pos);
+            figures.push((color, fig, pos));
+        }
+        game.emit(GameEvent::Exploded{ at: to, figures: figures });
+    }
+
+    fn winner(&self, game: &ChessGame) -> Option<(Color, &'static str)> {
+        match (game.white_player.has_king(), game.black_player.has_king()) {
+            (true, false) => Some((Color::White, "explosion")),
+            (false, true) => Some((Color::Black, "explosion")),
+            _ => None
+        }
+    }
+}
+
+/// Returns the fields whose figures an explosion at 'at' removes: the capturing figure
+/// at 'at' and all figures but pawns on the fields around it
+fn blast(board: &Board, at: Position) -> Vec<Position> {
+    let mut fields = Vec::new();
+    for y in at.y.saturating_sub(1)..(at.y + 2).min(8) {
+        for x in at.x.saturating_sub(1)..(at.x + 2).min(8) {
+            let pos = Position::new(x, y);
+            match board.get_figure(pos) {
+                Some(fig) if pos == at || fig != Figure::Pawn => fields.push(pos),
+                _ => ()
+            }
+        }
+    }
+    fields
+}
+
+/// Returns the field of the king of 'color' on the board, if it was not blown up
+fn find_king(board: &Board, color: Color) -> Option<Position> {
+    (0..64).map(|i| Position::new(i % 8, i / 8))
+           .find(|&pos| board.get_figure(pos) == Some(Figure::King) && board.get_figure_color(pos) == Some(color))
+}
+
+/// Returns whether the king of 'color' is attacked in Atomic chess. Kings next to each
+/// other are never in check, as the king taking the other would blow up both.
+fn atomic_in_check(board: &Board, color: Color) -> bool {
+    let (king, other) = match (find_king(board, color), find_king(board, !color)) {
+        (Some(king), Some(other)) => (king, other),
+        _ => return false
+    };
+    if (king.x as i8 - other.x as i8).abs() <= 1 && (king.y as i8 - other.y as i8).abs() <= 1 {
+        return false
+    }
+
+    (0..64).map(|i| Position::new(i % 8, i / 8)).any(|pos| {
+        board.get_figure_color(pos) == Some(!color) &&
+        board.get_figure(pos).map_or(false, |fig| fig != Figure::King && fig.valid_move(board, pos, king, &!color))
+    })
+}
+
+/// Returns whether the player whose turn it is may move from 'from' to 'to' in Atomic chess
+fn atomic_is_legal(game: &ChessGame, from: Position, to: Position) -> bool {
+    let color = game.turn_color();
+    let fig = match game.board.get_figure(from) {
+        Some(fig) if game.board.get_figure_color(from) == Some(color) => fig,
+        _ => return false
+    };
+    if !fig.valid_move(&game.board, from, to, &color) {
+        return false
+    }
+    let capture = !game.board.is_empty(to);
+    // A king taking a figure would blow itself up
+    if capture && fig == Figure::King {
+        return false
+    }
+
+    let mut board = game.board.clone();
+    board.move_figure(from, to);
+    if capture {
+        for pos in blast(&board, to) {
+            board[pos].set_empty(pos);
+        }
+    }
+    match (find_king(&board, color), find_king(&board, !color)) {
+        // Blowing up the own king is never allowed
+        (None, _) => false,
+        // Blowing up the opponent's king wins at once, even if the own king is attacked
+        (Some(_), None) => true,
+        _ => !atomic_in_check(&board, color)
+    }
+}
+
+/// Dark chess: each player only sees the fields their figures can move to. There is no check,
+/// a king may move into danger and capturing it wins.
+pub struct DarkChess;
+
+impl Variant for DarkChess {
+    fn name(&self) -> &'static str {
+        "darkchess"
+    }
+
+    fn hides_figures(&self) -> bool {
+        true
+    }
+
+    fn is_legal(&self, game: &mut ChessGame, from: Position, to: Position) -> bool {
+        let color = game.turn_color();
+        from.reserve_figure().is_none() && game.board.get_figure_color(from) == Some(color) &&
+        game.board.get_figure(from).map_or(false, |fig| fig.valid_move(&game.board, from, to, &color))
+    }
+
+    fn legal_moves(&self, game: &mut ChessGame) -> Vec<(Position, Position)> {
+        let color = game.turn_color();
+        let mut moves = Vec::new();
+        for (name, positions) in game.active_player().figures.iter() {
+            let fig = Figure::from_name(name);
+            for &from in positions.iter() {
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let to = Position::new(x, y);
+                        if fig.valid_move(&game.board, from, to, &color) {
+                            moves.push((from, to));
+                        }
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn in_check(&self, _game: &ChessGame) -> bool {
+        false
+    }
+
+    fn after_move(&self, game: &mut ChessGame, _from: Position, _to: Position, captured: Option<Figure>) {
+        if captured == Some(Figure::King) {
+            let mover = if game.turn { 1 } else { 0 };
+            game.kings_captured[mover] = true;
+        }
+    }
+
+    fn winner(&self, game: &ChessGame) -> Option<(Color, &'static str)> {
+        if game.kings_captured[0] {
+            Some((Color::White, "king captured"))
+        } else if game.kings_captured[1] {
+            Some((Color::Black, "king captured"))
+        } else {
+            None
+        }
+    }
+}
+
+/// Names of all variants
+pub const NAMES: [&'static str; 6] = ["standard", "kingofthehill", "threecheck", "crazyhouse", "atomic", "darkchess"];
+
+/// Returns the variant with the name, also accepting names with spaces or dashes
+/// like 'King of the Hill' or 'three-check'
+pub fn find(name: &str) -> Option<Arc<Variant>> {
+    let name: String = name.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+    match name.as_str() {
+        "standard" | "chess" => Some(Arc::new(Standard)),
+        "kingofthehill" | "koth" => Some(Arc::new(KingOfTheHill)),
+        "threecheck" | "3check" => Some(Arc::new(ThreeCheck)),
+        "crazyhouse" | "zh" => Some(Arc::new(Crazyhouse)),
+        "atomic" => Some(Arc::new(Atomic)),
+        "darkchess" | "dark" | "fogofwar" => Some(Arc::new(DarkChess)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(s: &str) -> Position {
+        Position::from_algebraic(s).unwrap()
+    }
+
+    fn game(variant: &str, fen: &str) -> ChessGame {
+        ChessGame::from_fen_variant(fen, find(variant).unwrap()).unwrap()
+    }
+
+    fn outcome(game: &ChessGame) -> Option<(&'static str, &'static str)> {
+        game.outcome.as_ref().map(|o| (o.result, o.reason))
+    }
+
+    #[test]
+    fn find_names() {
+        for name in NAMES.iter() {
+            assert_eq!(find(name).unwrap().name(), *name);
+        }
+        assert_eq!(find("King of the Hill").unwrap().name(), "kingofthehill");
+        assert_eq!(find("three-check").unwrap().name(), "threecheck");
+        assert!(find("losers").is_none());
+    }
+
+    #[test]
+    fn king_of_the_hill() {
+        let mut game = game("kingofthehill", "4k3/8/8/8/8/4K3/8/8 w - - 0 1");
+        assert_eq!(game.do_turn(pos("e3"), pos("f4")), 0);
+        assert!(!game.gameover);
+        assert_eq!(game.do_turn(pos("e8"), pos("e7")), 0);
+        assert_eq!(game.do_turn(pos("f4"), pos("e4")), 0);
+        assert_eq!(outcome(&game), Some(("1-0", "king of the hill")));
+    }
+
+    #[test]
+    fn three_checks() {
+        let mut game = game("threecheck", "4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        let moves = [("a1", "a8"), ("e8", "e7"), ("a8", "a7"), ("e7", "e6")];
+        for &(from, to) in moves.iter() {
+            assert_eq!(game.do_turn(pos(from), pos(to)), 0);
+        }
+        assert_eq!(game.checks, [2, 0]);
+        assert!(!game.gameover);
+
+        assert_eq!(game.do_turn(pos("a7"), pos("a6")), 0);
+        assert_eq!(game.checks, [3, 0]);
+        assert_eq!(outcome(&game), Some(("1-0", "three checks")));
+    }
+
+    fn drops(game: &mut ChessGame, fig: Figure) -> Vec<Position> {
+        game.possible_moves().into_iter().filter(|m| m.0 == Position::reserve(fig)).map(|m| m.1).collect()
+    }
+
+    #[test]
+    fn crazyhouse_drops() {
+        let mut game = game("crazyhouse", START_FEN);
+        for &(from, to) in [("e2", "e4"), ("d7", "d5"), ("e4", "d5"), ("d8", "d5")].iter() {
+            assert!(game.play(pos(from), pos(to)));
+        }
+        assert_eq!(game.white_player.reserve(), &[Figure::Pawn]);
+        assert_eq!(game.black_player.reserve(), &[Figure::Pawn]);
+        assert_eq!(game.to_fen(), "rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR[Pp] w KQkq - 0 3");
+
+        // Pawns may only be dropped on empty fields of the rows in between
+        let pawn = Position::reserve(Figure::Pawn);
+        let fields = drops(&mut game, Figure::Pawn);
+        assert!(fields.contains(&pos("e4")));
+        assert!(!fields.contains(&pos("d5")));
+        assert!(!fields.contains(&pos("e1")) && !fields.contains(&pos("a8")));
+        assert!(drops(&mut game, Figure::Knight).is_empty());
+
+        assert!(game.play(pawn, pos("e4")));
+        assert!(game.white_player.reserve().is_empty());
+        assert_eq!(game.board.get_figure(pos("e4")), Some(Figure::Pawn));
+    }
+
+    #[test]
+    fn crazyhouse_drops_block_checks() {
+        let mut game = game("crazyhouse", "4k3/8/8/8/8/8/8/4K2r[N] w - - 0 1");
+        assert!(game.in_check());
+        let mut fields = drops(&mut game, Figure::Knight);
+        fields.sort_by_key(|p| p.x);
+        assert_eq!(fields, vec![pos("f1"), pos("g1")]);
+    }
+
+    // A promoted figure goes back into the reserve as a pawn
+    #[test]
+    fn crazyhouse_demotes_promoted_figures() {
+        let mut game = game("crazyhouse", "3qk3/8/8/8/8/8/8/3Q~K3[] b - - 0 1");
+        assert!(game.play(pos("d8"), pos("d1")));
+        assert_eq!(game.black_player.reserve(), &[Figure::Pawn]);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/3qK3[p] w - - 0 2");
+    }
+
+    #[test]
+    fn atomic_explosions() {
+        let mut game = game("atomic", "4k3/8/2n1p3/3r4/4p3/1B6/8/4K3 w - - 0 1");
+        let events = game.events();
+        assert_eq!(game.do_turn(pos("b3"), pos("d5")), 2);
+        // The pawns next to the capture survive it
+        for field in ["b3", "d5", "c6"].iter() {
+            assert!(game.board.is_empty(pos(field)), "{} is not empty", field);
+        }
+        assert_eq!(game.board.get_figure(pos("e6")), Some(Figure::Pawn));
+        assert_eq!(game.board.get_figure(pos("e4")), Some(Figure::Pawn));
+        // The captured rook is reported by its own event
+        let exploded = GameEvent::Exploded{ at: pos("d5"), figures: vec![(Color::White, Figure::Bishop, pos("d5")),
+                                                                         (Color::Black, Figure::Knight, pos("c6"))] };
+        assert!(events.try_iter().any(|e| e == exploded));
+        assert!(!game.gameover);
+    }
+
+    #[test]
+    fn atomic_king_explosion_wins() {
+        let mut game = game("atomic", "4k3/3r4/8/8/8/8/8/3RK3 w - - 0 1");
+        assert_eq!(game.do_turn(pos("d1"), pos("d7")), 2);
+        assert!(!game.black_player.has_king());
+        assert_eq!(outcome(&game), Some(("1-0", "explosion")));
+    }
+
+    #[test]
+    fn atomic_kings() {
+        // Kings may not capture
+        let mut capture = game("atomic", "4k3/8/8/8/8/8/4n3/4K3 w - - 0 1");
+        assert!(!capture.possible_moves().contains(&(pos("e1"), pos("e2"))));
+
+        // Kings next to each other are never in check, the rook only gives check from afar
+        let mut next = game("atomic", "4r3/8/8/8/8/8/3k4/4K3 w - - 0 1");
+        assert!(!next.in_check());
+        assert!(next.possible_moves().contains(&(pos("e1"), pos("e2"))));
+        assert!(game("atomic", "4r3/8/8/8/8/3k4/8/4K3 w - - 0 1").in_check());
+    }
+
+    #[test]
+    fn dark_chess_visibility() {
+        let mut game = game("darkchess", START_FEN);
+        let visible = game.board.visible(Color::White);
+        assert!(visible[3][4] && visible[2][5] && visible[0][3]);
+        assert!(!visible[4][4] && !visible[7][4]);
+        // Everybody knows the start position
+        assert_eq!(game.seen_by(Color::White).to_fen(), game.to_fen());
+
+        assert!(game.play(pos("e2"), pos("e4")));
+        assert!(game.play(pos("g8"), pos("f6")));
+        // White did not see the knight leave, but the knight sees the pawn
+        let seen = game.seen_by(Color::White);
+        assert_eq!(seen.board.get_figure(pos("g8")), Some(Figure::Knight));
+        assert!(seen.board.is_empty(pos("f6")));
+        let seen = game.seen_by(Color::Black);
+        assert_eq!(seen.board.get_figure(pos("e4")), Some(Figure::Pawn));
+    }
+
+    #[test]
+    fn dark_chess_king_capture() {
+        let mut game = game("darkchess", "4k3/8/8/8/8/8/8/4R1K1 w - - 0 1");
+        // There is no check, the king may even stay in danger
+        assert!(!game.in_check());
+        assert_eq!(game.do_turn(pos("e1"), pos("e8")), 2);
+        assert_eq!(outcome(&game), Some(("1-0", "king captured")));
+    }
+}
@@ -34,7 +34,7 @@ use pipeline_layout;
 use renderpass;
 use system::System;
 
-use chess::logic::{Color, Position};
+use chess::logic::{Board, Color, Figure, Position, RESERVE_FIGURES};
 use model::Model;
 use data::{Vertex, Normal, pawn, king, queen, bishop, knight, rook};
 
@@ -57,9 +57,18 @@ pub struct GraphicsEngine {
     pub uniform: Matrices,
     pub screenwidth: u32,
     pub screenheight: u32,
-    pub camera: Point3<f32>
+    pub camera: Point3<f32>,
+    // Fields drawn in the highlight color, e.g. the move suggested by a hint
+    pub highlighted: Vec<Position>,
+    // Figures in the Crazyhouse reserves beside the board
+    pub reserve_figures: Vec<(Model, Point3<f32>, Color, Figure)>,
+    // In Dark chess the player whose view is drawn and the fields they see
+    pub fog: Option<(Color, [[bool; 8]; 8])>
 }
 
+/// Distance between figures of the same kind in a reserve
+const RESERVE_SPACING: f32 = 0.6;
+
 impl GraphicsEngine {
 
     /* Not sure if this will ever be needed
@@ -72,6 +81,40 @@ impl GraphicsEngine {
         Arc::get_mut(&mut self.field_positions).unwrap().push(centers);
     }
 
+    /// Set the fields which are drawn in the highlight color
+    pub fn set_highlighted(&mut self, fields: Vec<Position>) {
+        self.highlighted = fields;
+    }
+
+    /// Returns whether the field at 'index' of the field centers in group 'i' is highlighted
+    fn is_highlighted(&self, i: usize, index: usize) -> bool {
+        let (x, y) = self.map_field_positions(i, index);
+        self.highlighted.iter().any(|p| p.x == x && p.y == y)
+    }
+
+    /// Set the view drawn in Dark chess, None shows the whole board
+    pub fn set_fog(&mut self, fog: Option<(Color, [[bool; 8]; 8])>) {
+        self.fog = fog;
+    }
+
+    /// Returns whether the field at 'index' of the field centers in group 'i' is hidden by the fog
+    fn is_hidden(&self, i: usize, index: usize) -> bool {
+        let (x, y) = self.map_field_positions(i, index);
+        self.fog.map_or(false, |(_, visible)| !visible[y as usize][x as usize])
+    }
+
+    /// Returns whether a figure of 'color' standing at 'at' is hidden by the fog,
+    /// the viewer's own figures are always shown
+    fn hides_figure(&self, color: Color, at: Point3<f32>) -> bool {
+        match self.fog {
+            Some((viewer, visible)) if viewer != color => {
+                let (x, y) = ((3.5 - at.x).round() as usize, (3.5 - at.z).round() as usize);
+                !visible[y][x]
+            },
+            _ => false
+        }
+    }
+
     /// Set camera position
     pub fn set_camera_position(&mut self, pos: Point3<f32>) {
         self.camera = pos;
@@ -133,6 +176,76 @@ impl GraphicsEngine {
         }
     }
 
+    /// Returns the model of a figure at the world's origin, knights look to the opponent
+    fn figure_model(color: Color, fig: Figure) -> Model {
+        let mut model = match fig {
+            Figure::Pawn => Model::from_data(&pawn::VERTICES, &pawn::NORMALS, &pawn::INDICES),
+            Figure::Knight => Model::from_data(&knight::VERTICES, &knight::NORMALS, &knight::INDICES),
+            Figure::Bishop => Model::from_data(&bishop::VERTICES, &bishop::NORMALS, &bishop::INDICES),
+            Figure::Rook => Model::from_data(&rook::VERTICES, &rook::NORMALS, &rook::INDICES),
+            Figure::Queen => Model::from_data(&queen::VERTICES, &queen::NORMALS, &queen::INDICES),
+            Figure::King => Model::from_data(&king::VERTICES, &king::NORMALS, &king::INDICES)
+        };
+        if fig == Figure::Knight {
+            model.rotate_around_y(if color == Color::White { -90.0 } else { 90.0 });
+        }
+        model
+    }
+
+    /// Add a figure, e.g. one dropped from a reserve
+    pub fn add_figure(&mut self, color: Color, fig: Figure, pos: Position) {
+        let at = System::from_position(&pos);
+        let mut model = GraphicsEngine::figure_model(color, fig);
+        model.translate((at.x, at.y, at.z));
+
+        if color == Color::White {
+            Arc::get_mut(&mut self.white_figures).unwrap().push((model, at));
+        } else {
+            Arc::get_mut(&mut self.black_figures).unwrap().push((model, at));
+        }
+    }
+
+    /// Replace all figures on the board by those of 'board', e.g. of a position set up in the editor
+    pub fn set_figures(&mut self, board: &Board) {
+        Arc::get_mut(&mut self.white_figures).unwrap().clear();
+        Arc::get_mut(&mut self.black_figures).unwrap().clear();
+        for y in 0..8 {
+            for x in 0..8 {
+                let pos = Position::new(x, y);
+                if let (Some(fig), Some(color)) = (board.get_figure(pos), board.get_figure_color(pos)) {
+                    self.add_figure(color, fig, pos);
+                }
+            }
+        }
+    }
+
+    /// Returns where the 'n'th figure of a kind in a reserve stands: white's reserve is beyond
+    /// the h-file starting at white's first row, black's beyond the a-file starting at black's
+    fn reserve_position(color: Color, fig: Figure, n: usize) -> Point3<f32> {
+        let row = RESERVE_FIGURES.iter().position(|&f| f == fig).unwrap_or(0) as f32;
+        if color == Color::White {
+            Point3::new(-4.5 - n as f32 * RESERVE_SPACING, 0.1, 3.5 - row)
+        } else {
+            Point3::new(4.5 + n as f32 * RESERVE_SPACING, 0.1, -3.5 + row)
+        }
+    }
+
+    /// Show the figures of both reserves
+    pub fn set_reserves(&mut self, white: &[Figure], black: &[Figure]) {
+        self.reserve_figures.clear();
+        for &(color, reserve) in [(Color::White, white), (Color::Black, black)].iter() {
+            for &fig in RESERVE_FIGURES.iter() {
+                let count = reserve.iter().filter(|&&f| f == fig).count();
+                for n in 0..count {
+                    let at = GraphicsEngine::reserve_position(color, fig, n);
+                    let mut model = GraphicsEngine::figure_model(color, fig);
+                    model.translate((at.x, at.y, at.z));
+                    self.reserve_figures.push((model, at, color, fig));
+                }
+            }
+        }
+    }
+
     /// Delete a figure
     pub fn delete_figure(&mut self, color: Color, pos: Position) {
         let at = System::from_position(&pos);
@@ -148,6 +261,19 @@ impl GraphicsEngine {
         }
     }
 
+    /// Delete several figures at once, like the ones an explosion in Atomic chess removes
+    pub fn delete_figures(&mut self, figures: &[(Color, Figure, Position)]) {
+        let white: Vec<_> = figures.iter().filter(|f| f.0 == Color::White).map(|f| System::from_position(&f.2)).collect();
+        let black: Vec<_> = figures.iter().filter(|f| f.0 == Color::Black).map(|f| System::from_position(&f.2)).collect();
+
+        Arc::get_mut(&mut self.white_figures)
+            .unwrap()
+            .retain(|f| !white.contains(&f.1));
+        Arc::get_mut(&mut self.black_figures)
+            .unwrap()
+            .retain(|f| !black.contains(&f.1));
+    }
+
     /// Initialize all figures at start positions
     pub fn init_figures(&mut self) {
         for i in 0..8 {
@@ -290,6 +416,10 @@ impl GraphicsEngine {
         let field_white = ::vs::ty::FigureColor{ col: Vector3::new(1.0, 1.0, 1.0).into() };
         let white = ::vs::ty::FigureColor{ col: Vector3::new(0.9, 0.9, 0.9).into() };
         let black = ::vs::ty::FigureColor{ col: Vector3::new(0.15, 0.15, 0.15).into() };
+        let highlight = ::vs::ty::FigureColor{ col: Vector3::new(0.2, 0.7, 0.3).into() };
+        // Fields hidden in Dark chess, darker and a little blue
+        let hidden_white = ::vs::ty::FigureColor{ col: Vector3::new(0.35, 0.35, 0.42).into() };
+        let hidden_black = ::vs::ty::FigureColor{ col: Vector3::new(0.08, 0.08, 0.14).into() };
 
         // For all command buffers record drawing commands for all fields and figures
         // TODO: optimize so we don't have to construct every buffer anew
@@ -299,15 +429,22 @@ impl GraphicsEngine {
                 buf = buf.draw_indexed(pipeline, (&whites[index].vertex_buffer(&self.device, &self.queue),
                                                   &whites[index].normal_buffer(&self.device, &self.queue)),
                                                   &whites[index].index_buffer(&self.device, &self.queue),
-                                                  &::vulkano::command_buffer::DynamicState::none(), set, &field_white);
+                                                  &::vulkano::command_buffer::DynamicState::none(), set,
+                                                  if self.is_highlighted(0, index) { &highlight }
+                                                  else if self.is_hidden(0, index) { &hidden_white } else { &field_white });
             }
             for index in 0..blacks.len() {
                 buf = buf.draw_indexed(pipeline, (&blacks[index].vertex_buffer(&self.device, &self.queue),
                                                   &blacks[index].normal_buffer(&self.device, &self.queue)),
                                                   &blacks[index].index_buffer(&self.device, &self.queue),
-                                                  &::vulkano::command_buffer::DynamicState::none(), set, &field_black);
+                                                  &::vulkano::command_buffer::DynamicState::none(), set,
+                                                  if self.is_highlighted(1, index) { &highlight }
+                                                  else if self.is_hidden(1, index) { &hidden_black } else { &field_black });
             }
             for index in 0..self.white_figures.len() {
+                if self.hides_figure(Color::White, self.white_figures[index].1) {
+                    continue
+                }
                 buf = buf.draw_indexed(pipeline, (&self.white_figures[index].0.vertex_buffer(&self.device, &self.queue),
                                                   &self.white_figures[index].0.normal_buffer(&self.device, &self.queue)),
                                                   &self.white_figures[index].0.index_buffer(&self.device, &self.queue),
@@ -315,12 +452,22 @@ impl GraphicsEngine {
 
             }
             for index in 0..self.black_figures.len() {
+                if self.hides_figure(Color::Black, self.black_figures[index].1) {
+                    continue
+                }
                 buf = buf.draw_indexed(pipeline, (&self.black_figures[index].0.vertex_buffer(&self.device, &self.queue),
                                                   &self.black_figures[index].0.normal_buffer(&self.device, &self.queue)),
                                                   &self.black_figures[index].0.index_buffer(&self.device, &self.queue),
                                                   &::vulkano::command_buffer::DynamicState::none(), set, &black);
 
             }
+            for &(ref model, _, color, _) in self.reserve_figures.iter() {
+                buf = buf.draw_indexed(pipeline, (&model.vertex_buffer(&self.device, &self.queue),
+                                                  &model.normal_buffer(&self.device, &self.queue)),
+                                                  &model.index_buffer(&self.device, &self.queue),
+                                                  &::vulkano::command_buffer::DynamicState::none(), set,
+                                                  if color == Color::White { &white } else { &black });
+            }
             fields.push(buf.draw_end().build());
         }
         let cmd = Arc::get_mut(&mut self.command_buffers).unwrap();
@@ -330,6 +477,33 @@ impl GraphicsEngine {
 
     /// Get the field in board coordinates that the player selected using a ray intersection test
     pub fn get_field(&self, mouse: (i32, i32)) -> Option<(u8, u8)> {
+        if let Some(direction) = self.mouse_direction(mouse) {
+            for i in 0..self.field_positions.len() {
+                for index in 0..self.field_positions[i].len() {
+                    // If we intersect return field coordinates in board coordinates
+                    if self.hits(&direction, self.field_positions[i][index]) {
+                        return Some(self.map_field_positions(i, index))
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Get the reserve figure that the player selected, the first one of each kind can be picked
+    pub fn get_reserve(&self, mouse: (i32, i32)) -> Option<(Color, Figure)> {
+        let direction = match self.mouse_direction(mouse) {
+            Some(direction) => direction,
+            None => return None
+        };
+
+        self.reserve_figures.iter()
+            .find(|&&(_, at, color, fig)| at == GraphicsEngine::reserve_position(color, fig, 0) && self.hits(&direction, at))
+            .map(|&(_, _, color, fig)| (color, fig))
+    }
+
+    /// Returns the direction of the ray from the camera through the mouse position
+    fn mouse_direction(&self, mouse: (i32, i32)) -> Option<Vector3<f32>> {
         // Transform mouse coordinates
         let mut x = (( 2.0 * mouse.0 as f32) / self.screenwidth as f32) - 1.0;
         let mut y = (((2.0 * mouse.1 as f32) / self.screenheight as f32) - 1.0) * -1.0;
@@ -338,39 +512,28 @@ impl GraphicsEngine {
         y = y / self.uniform.proj.y.y;
 
         if let Some(inverse) = self.uniform.view.invert() {
-            let direction = Vector3{ x: (x * inverse.x.x) + (y * inverse.y.x) + inverse.z.x,
-                                             y: (x * inverse.x.y) + (y * inverse.y.y) + inverse.z.y,
-                                             z: (x * inverse.x.z) + (y * inverse.y.z) + inverse.z.z };
-
-            for i in 0..self.field_positions.len() {
-                for index in 0..self.field_positions[i].len() {
-                    let mut world = self.uniform.world.clone();
-                    // Translate to field's position
-                    let translation = Matrix4::from_translation(Vector3 {
-                                                            x: self.field_positions[i][index].x,
-                                                            y: self.field_positions[i][index].y,
-                                                            z: self.field_positions[i][index].z
-                                                          });
-                    world = world * translation;
-
-                    let inverse_world = world.invert().unwrap();
-                    // Calculate ray direction
-                    let mut ray_direction = inverse_world.transform_vector(direction.normalize());
-                    ray_direction = ray_direction.normalize();
-                    // Calculate ray origin
-                    let ray_origin = inverse_world.transform_point(self.camera);
-
-                    // If we intersect return field coordinates in board coordinates
-                    if GraphicsEngine::ray_intersect(&ray_origin, &ray_direction) {
-                        return Some(self.map_field_positions(i, index))
-                    }
-                }
-            }
+            Some(Vector3{ x: (x * inverse.x.x) + (y * inverse.y.x) + inverse.z.x,
+                          y: (x * inverse.x.y) + (y * inverse.y.y) + inverse.z.y,
+                          z: (x * inverse.x.z) + (y * inverse.y.z) + inverse.z.z })
         } else {
             println!("Could not invert view matrix");
-            return None
+            None
         }
-        None
+    }
+
+    /// Returns whether the ray from the camera in 'direction' hits the sphere around 'center'
+    fn hits(&self, direction: &Vector3<f32>, center: Point3<f32>) -> bool {
+        // Translate to the center
+        let world = self.uniform.world.clone() * Matrix4::from_translation(Vector3{ x: center.x, y: center.y, z: center.z });
+
+        let inverse_world = world.invert().unwrap();
+        // Calculate ray direction
+        let mut ray_direction = inverse_world.transform_vector(direction.normalize());
+        ray_direction = ray_direction.normalize();
+        // Calculate ray origin
+        let ray_origin = inverse_world.transform_point(self.camera);
+
+        GraphicsEngine::ray_intersect(&ray_origin, &ray_direction)
     }
 
     /// Maps a field position in the 3D world to a board position
@@ -0,0 +1,57 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Rules, AI and game management of RustChess without any graphics.
+//!
+//! The Vulkan client is built on top of this library when the `graphics` feature is on (the default),
+//! other programs can depend on the crate with `default-features = false` and only get the chess core:
+//!
+//! - `chess::ChessGame` holds the board, both players and the history and checks and makes moves
+//! - `chess::logic` has the board, figures, positions and move validation
+//! - `chess::player` describes who plays a side: a human, the Dumb AI, the Smart AI or an external engine
+//! - `chess::ai` picks moves for AI players and explains them
+//! - `system::System` connects a game to a user interface: selections, hints, clocks and saving
+//!
+//! ```no_run
+//! extern crate rust_chess;
+//!
+//! use rust_chess::chess::ChessGame;
+//! use rust_chess::chess::logic::Position;
+//! use rust_chess::chess::player::PlayerType;
+//!
+//! fn main() {
+//!     let mut game = ChessGame::new();
+//!     // 1. e4, then let the AI answer
+//!     game.do_turn(Position::new(4, 1), Position::new(4, 3));
+//!     game.black_player.set_ptype(PlayerType::Smart);
+//!     if let Some(((from, to), _)) = game.do_ai_turn() {
+//!         println!("{}{}", from.to_algebraic(), to.to_algebraic());
+//!     }
+//!     println!("{}", game.to_fen());
+//! }
+//! ```
+
+extern crate cgmath;
+extern crate time;
+
+pub mod chess;
+pub mod system;
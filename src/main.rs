@@ -31,7 +31,7 @@
 
 extern crate cgmath;
 extern crate winit;
-extern crate time;
+extern crate rust_chess;
 
 #[macro_use]
 extern crate vulkano;
@@ -47,12 +47,17 @@ mod fs { include!{concat!(env!("OUT_DIR"), "/shaders/src/bin/chess_fs.glsl")} }
 
 mod data;
 mod model;
-mod chess;
-mod system;
 mod graphics;
+mod tui;
+
+use rust_chess::{chess, system};
 
 use model::Model;
 use system::System;
+use chess::ai::Difficulty;
+use chess::logic::Figure;
+use chess::player::PlayerType;
+use chess::event::GameEvent;
 use graphics::{GraphicsEngine, Matrices};
 
 mod renderpass {
@@ -88,7 +93,231 @@ mod pipeline_layout {
     }
 }
 
+/// File the game is saved to
+const SAVE_FILE: &'static str = "game.pgn";
+
+/// File the editor saves positions to
+const POSITION_FILE: &'static str = "position.fen";
+
+/// Handles a key while the editor is open, returns false if the key does nothing there
+fn editor_key(system: &mut System, key: winit::VirtualKeyCode) -> bool {
+    use winit::VirtualKeyCode::*;
+
+    match key {
+        K => system.choose_figure(Figure::King),
+        Q => system.choose_figure(Figure::Queen),
+        R => system.choose_figure(Figure::Rook),
+        B => system.choose_figure(Figure::Bishop),
+        N => system.choose_figure(Figure::Knight),
+        P => system.choose_figure(Figure::Pawn),
+        Tab => system.toggle_figure_color(),
+        T => system.toggle_setup_turn(),
+        F5 => system.toggle_setup_castling(0),
+        F6 => system.toggle_setup_castling(1),
+        F7 => system.toggle_setup_castling(2),
+        F8 => system.toggle_setup_castling(3),
+        F => system.export_setup(POSITION_FILE),
+        Return => {
+            system.start_setup();
+        },
+        F2 => system.toggle_editor(),
+        Escape => system.reset_selection(),
+        _ => return false
+    }
+    true
+}
+
+/// Shows the figures of the position in the editor or, once it is closed, those of the game
+fn show_position(graphics: &mut GraphicsEngine, system: &System) {
+    match system.setup() {
+        Some(setup) => {
+            graphics.set_figures(&setup.board);
+            graphics.set_highlighted(system.picked().into_iter().collect());
+            graphics.set_fog(None);
+        },
+        None => {
+            graphics.set_figures(&system.game().board);
+            graphics.set_highlighted(Vec::new());
+            graphics.set_fog(system.fog());
+        }
+    }
+    graphics.set_reserves(system.game().white_player.reserve(), system.game().black_player.reserve());
+}
+
+/// Applies the command line arguments to the system
+fn apply_arguments(system: &mut System) {
+    let mut args = std::env::args().skip(1);
+    let mut book = None;
+    let mut personalities = Vec::new();
+    let mut time_control = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            // Minimum time between two AI moves in milliseconds
+            "--ai-delay" => {
+                match args.next().and_then(|v| v.parse::<u64>().ok()) {
+                    Some(ms) => system.set_min_ai_delay(Duration::from_millis(ms)),
+                    None => println!("--ai-delay expects a number of milliseconds")
+                }
+            },
+            // Player type or difficulty of the white or black player
+            "--white" | "--black" => {
+                let which = arg == "--white";
+                match args.next() {
+                    Some(ref p) if p == "human" => system.set_player(which, PlayerType::Human, Difficulty::Strongest),
+                    Some(ref p) if p == "dumb" => system.set_player(which, PlayerType::Dumb, Difficulty::Strongest),
+                    Some(ref p) if p == "smart" => system.set_player(which, PlayerType::Smart, Difficulty::Strongest),
+                    Some(p) => {
+                        match Difficulty::from_name(&p) {
+                            Some(d) => system.set_player(which, PlayerType::Smart, d),
+                            None => println!("Unknown player type or difficulty: {}", p)
+                        }
+                    },
+                    None => println!("{} expects a player type or difficulty", arg)
+                }
+            },
+            // Let the AI of one or both players think on the human's time
+            "--ponder" => {
+                match args.next() {
+                    Some(ref p) if p == "white" => system.toggle_ponder(true),
+                    Some(ref p) if p == "black" => system.toggle_ponder(false),
+                    Some(ref p) if p == "both" => {
+                        system.toggle_ponder(true);
+                        system.toggle_ponder(false);
+                    },
+                    _ => println!("--ponder expects white, black or both")
+                }
+            },
+            // Seed of the AI's random decisions, to replay a game
+            "--seed" => {
+                match args.next().and_then(|v| v.parse::<u64>().ok()) {
+                    Some(seed) => system.set_seed(seed),
+                    None => println!("--seed expects a number")
+                }
+            },
+            // Number of threads the smart AI searches with
+            "--threads" => {
+                match args.next().and_then(|v| v.parse::<usize>().ok()) {
+                    Some(n) => system.set_threads(n),
+                    None => println!("--threads expects a number of threads")
+                }
+            },
+            // File with more personalities for the AI
+            "--personalities" => {
+                match args.next() {
+                    Some(path) => system.load_personalities(&path),
+                    None => println!("--personalities expects a file")
+                }
+            },
+            // Personality of the white or black AI, set once all files are loaded
+            "--white-personality" | "--black-personality" => {
+                match args.next() {
+                    Some(name) => personalities.push((arg == "--white-personality", name)),
+                    None => println!("{} expects the name of a personality", arg)
+                }
+            },
+            // Time control for both players, e.g. '300+2' or '40/5400:1800'
+            "--time" => {
+                match args.next().map(|tc| chess::clock::TimeControl::parse(&tc)) {
+                    Some(Ok(control)) => time_control = Some(control),
+                    Some(Err(e)) => println!("Invalid time control: {}", e),
+                    None => println!("--time expects a time control")
+                }
+            },
+            // Evaluation weights written by the tuning tool
+            "--eval" => {
+                match args.next() {
+                    Some(path) => {
+                        match chess::eval::Eval::load(&path) {
+                            Ok(eval) => system.set_eval(eval),
+                            Err(e) => println!("Could not load evaluation weights {}: {}", path, e)
+                        }
+                    },
+                    None => println!("--eval expects a file with evaluation weights")
+                }
+            },
+            // Number of candidate moves shown by a hint
+            "--hint-lines" => {
+                match args.next().and_then(|v| v.parse::<usize>().ok()) {
+                    Some(n) => system.set_hint_lines(n),
+                    None => println!("--hint-lines expects a number of moves")
+                }
+            },
+            // External UCI engine playing for the white or black player
+            "--white-engine" | "--black-engine" => {
+                let which = arg == "--white-engine";
+                match args.next() {
+                    Some(path) => {
+                        match chess::engine::ExternalEngine::start(&path) {
+                            Ok(engine) => system.set_engine(which, engine),
+                            Err(e) => println!("Could not start engine {}: {}", path, e)
+                        }
+                    },
+                    None => println!("{} expects the path of an engine", arg)
+                }
+            },
+            // Polyglot opening book
+            "--book" => book = args.next(),
+            // Number of plies for which the opening book is used
+            "--book-depth" => {
+                match args.next().and_then(|v| v.parse::<usize>().ok()) {
+                    Some(depth) => system.set_book_depth(depth),
+                    None => println!("--book-depth expects a number of plies")
+                }
+            },
+            // Directory with Syzygy endgame tablebase files
+            "--syzygy" => {
+                match args.next() {
+                    Some(dir) => {
+                        match chess::syzygy::Tablebase::open(&dir) {
+                            Ok(tb) => system.set_tablebase(Some(Arc::new(tb))),
+                            Err(e) => println!("Could not open tablebases in {}: {}", dir, e)
+                        }
+                    },
+                    None => println!("--syzygy expects a directory")
+                }
+            },
+            // Variant played, e.g. 'kingofthehill' or 'threecheck'
+            "--variant" => {
+                match args.next() {
+                    Some(name) => system.set_variant(&name),
+                    None => println!("--variant expects the name of a variant")
+                }
+            },
+            // Checked before the window is opened
+            "--tui" => (),
+            _ => println!("Unknown argument: {}", arg)
+        }
+    }
+
+    if let Some(path) = book {
+        match chess::polyglot::Book::open(&path) {
+            Ok(b) => system.set_book(Some(Arc::new(b))),
+            Err(e) => println!("Could not load opening book {}: {}", path, e)
+        }
+    }
+
+    for (which, name) in personalities {
+        system.set_personality(which, &name);
+    }
+
+    println!("AI seed: {}", system.seed());
+
+    // The clocks start last, so loading files does not cost time
+    if let Some(control) = time_control {
+        system.set_time_control(control);
+    }
+}
+
 fn main() {
+    // The terminal mode does not need Vulkan at all
+    if std::env::args().any(|arg| arg == "--tui") {
+        let mut system = System::new();
+        apply_arguments(&mut system);
+        tui::run(system);
+        return
+    }
+
     // Set up lots of stuff ... see vulkano examples
     let extensions = vulkano_win::required_extensions();
     let instance = vulkano::instance::Instance::new(None, &extensions, None).expect("failed to create instance");
@@ -234,7 +463,10 @@ fn main() {
                                        screenheight: images[0].dimensions()[1],
                                        white_figures: Arc::new(Vec::new()),
                                        black_figures: Arc::new(Vec::new()),
-                                       camera: camera };
+                                       camera: camera,
+                                       highlighted: Vec::new(),
+                                       reserve_figures: Vec::new(),
+                                       fog: None };
 
     graphics.add_field_centers(white_centers);
     graphics.add_field_centers(black_centers);
@@ -243,8 +475,15 @@ fn main() {
 
     // Construct communicator between game and graphics
     let mut system = System::new();
+    apply_arguments(&mut system);
+    if system.fog().is_some() {
+        graphics.set_fog(system.fog());
+        graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
+    }
 
-    // Render loop
+    // Render loop, the window title shows the clocks and the last message
+    let mut title = String::new();
+    let mut message = String::new();
     loop {
         submissions.retain(|s| s.destroying_would_block());
 
@@ -255,19 +494,19 @@ fn main() {
         }
         graphics.swapchain.present(&graphics.queue, image_num).unwrap();
 
-        // If there is an AI, let it make a move and update figures
+        // End the game when a flag falls
+        system.check_clock();
+
+        // If there is an AI, let it make a move
         if system.has_ai() {
-            if let Some(result) = system.execute_ai_turn() {
-                graphics.move_figure((result.0).0, (result.0).1, (result.0).2);
-                if result.1 {
-                    graphics.delete_figure(!((result.0).0), (result.0).2);
-                }
-                if system.upgrade_needed() {
-                    graphics.upgrade_pawn(system.upgrade().unwrap());
-                }
-                graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
-                std::thread::sleep(std::time::Duration::from_millis(250));
-            }
+            system.execute_ai_turn();
+        }
+
+        // Highlight the candidate moves of a hint once the AI found them
+        if system.poll_hint() {
+            let fields = system.hint_moves().iter().flat_map(|&(from, to)| vec![from, to]).collect();
+            graphics.set_highlighted(fields);
+            graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
         }
 
         // Window events
@@ -277,6 +516,12 @@ fn main() {
                 winit::Event::Closed => return,
                 // Keyboard input
                 winit::Event::KeyboardInput(winit::ElementState::Pressed, _, Some(the_key)) => {
+                    // While the editor is open its keys come first
+                    if system.setup().is_some() && editor_key(&mut system, the_key) {
+                        show_position(&mut graphics, &system);
+                        graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
+                        continue
+                    }
                     match the_key {
                         // On escape, reset the selection in system
                         winit::VirtualKeyCode::Escape => system.reset_selection(),
@@ -284,6 +529,40 @@ fn main() {
                         winit::VirtualKeyCode::Q => system.toggle_player_ai(false),
                         // Toggle white player AI
                         winit::VirtualKeyCode::W => system.toggle_player_ai(true),
+                        // Change black player AI difficulty
+                        winit::VirtualKeyCode::A => system.cycle_difficulty(false),
+                        // Change white player AI difficulty
+                        winit::VirtualKeyCode::S => system.cycle_difficulty(true),
+                        // Toggle black player AI pondering
+                        winit::VirtualKeyCode::Z => system.toggle_ponder(false),
+                        // Toggle white player AI pondering
+                        winit::VirtualKeyCode::X => system.toggle_ponder(true),
+                        // Save the game as PGN
+                        winit::VirtualKeyCode::P => system.save_game(SAVE_FILE),
+                        // Continue the saved game
+                        winit::VirtualKeyCode::L => {
+                            system.load_game(SAVE_FILE);
+                            show_position(&mut graphics, &system);
+                            graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
+                        },
+                        // Change black player AI personality
+                        winit::VirtualKeyCode::E => system.cycle_personality(false),
+                        // Change white player AI personality
+                        winit::VirtualKeyCode::R => system.cycle_personality(true),
+                        // Resign, offer a draw, accept or decline the opponent's offer or claim a draw
+                        winit::VirtualKeyCode::G => system.resign(),
+                        winit::VirtualKeyCode::D => system.offer_draw(),
+                        winit::VirtualKeyCode::Y => system.answer_draw(true),
+                        winit::VirtualKeyCode::N => system.answer_draw(false),
+                        winit::VirtualKeyCode::C => system.claim_draw(),
+                        // Open the editor to set up a position
+                        winit::VirtualKeyCode::F2 => {
+                            system.toggle_editor();
+                            show_position(&mut graphics, &system);
+                            graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
+                        },
+                        // Let the AI look for a hint for the player whose turn it is
+                        winit::VirtualKeyCode::H => system.show_hint(),
                         // Set camera position and update view matrix
                         _ =>
                         if the_key == winit::VirtualKeyCode::Key1 || the_key == winit::VirtualKeyCode::Key2 {
@@ -306,25 +585,66 @@ fn main() {
                 },
                 // Update mouse coordinates in System
                 winit::Event::MouseMoved(x, y) => system.set_mouse_coordinates(x, y),
+                // In the editor the left button places, picks up and puts down figures, the right one removes them
+                winit::Event::MouseInput(winit::ElementState::Pressed, button) if system.setup().is_some() => {
+                    if let Some(field) = graphics.get_field(system.mouse()) {
+                        match button {
+                            winit::MouseButton::Left => system.edit_field(field),
+                            winit::MouseButton::Right => system.remove_figure(field),
+                            _ => ()
+                        }
+                        show_position(&mut graphics, &system);
+                        graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
+                    }
+                },
                 // If a figure was selected, set position as selected in System
                 winit::Event::MouseInput(winit::ElementState::Pressed, winit::MouseButton::Left) => {
-                    if let Some(selection) = graphics.get_field(system.mouse()) {
+                    if let Some((color, figure)) = graphics.get_reserve(system.mouse()) {
+                        system.select_reserve(color, figure);
+                    } else if let Some(selection) = graphics.get_field(system.mouse()) {
                         system.set_selected(selection);
-                        // If two selections were made try to execute a turn and update graphics according to the turn
-                        if let Some(result) = system.check_ready_and_play() {
-                            graphics.move_figure((result.0).0, (result.0).1, (result.0).2);
-                            if result.1 {
-                                graphics.delete_figure(!((result.0).0), (result.0).2);
-                            }
-                            if system.upgrade_needed() {
-                                graphics.upgrade_pawn(system.upgrade().unwrap());
-                            }
-                            graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
-                        }
+                        // If two selections were made try to execute a turn
+                        system.check_ready_and_play();
                     }
                 },
                 _ => ()
             }
         }
+
+        // Update the figures according to what happened in the game
+        let events = system.take_events();
+        // The end of a game also lifts the fog of Dark chess
+        if events.iter().any(|e| match *e { GameEvent::MoveMade{ .. } | GameEvent::GameEnded{ .. } => true, _ => false }) {
+            for event in events {
+                match event {
+                    GameEvent::MoveMade{ color, from, to, .. } => graphics.move_figure(color, from, to),
+                    GameEvent::Castled{ color, rook, .. } => graphics.move_figure(color, rook.0, rook.1),
+                    GameEvent::PieceCaptured{ color, at, .. } => graphics.delete_figure(color, at),
+                    GameEvent::PawnPromoted{ color, at, .. } => graphics.upgrade_pawn((color, at)),
+                    GameEvent::PieceDropped{ color, figure, at } => graphics.add_figure(color, figure, at),
+                    GameEvent::Exploded{ figures, .. } => graphics.delete_figures(&figures),
+                    _ => ()
+                }
+            }
+            graphics.set_reserves(system.game().white_player.reserve(), system.game().black_player.reserve());
+            graphics.set_highlighted(Vec::new());
+            graphics.set_fog(system.fog());
+            graphics.update_command_buffers(&white_fields, &black_fields, &pipeline, &set, &framebuffers, &renderpass);
+        }
+
+        // Tell the user what the system has to say, the last message stays in the window title
+        for text in system.take_messages() {
+            println!("{}", text);
+            message = text;
+        }
+        let text = match system.clock_text() {
+            Some(clock) if !message.is_empty() => format!("{} - {}", clock, message),
+            Some(clock) => clock,
+            None => message.clone()
+        };
+        if text != title {
+            window.window().set_title(&format!("RustChess - {}", text));
+            title = text;
+        }
     }
 }
@@ -0,0 +1,428 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Headless match between two AI configurations: plays games from a set of
+//! opening positions with alternating colours, writes them to a PGN file and
+//! prints the score with an Elo estimate. It does not touch Vulkan.
+
+extern crate time;
+extern crate rust_chess;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rust_chess::chess;
+use chess::ChessGame;
+use chess::clock::TimeControl;
+use chess::ai::Difficulty;
+use chess::engine::ExternalEngine;
+use chess::eval::Eval;
+use chess::personality::Personality;
+use chess::pgn::PgnGame;
+use chess::player::{Player, PlayerType};
+use chess::search::SearchLimits;
+use chess::variant::{self, Variant};
+
+/// Plies after which a game is adjudicated as a draw
+const DEFAULT_MAX_PLIES: usize = 300;
+
+/// One side of the match
+struct Config {
+    name: String,
+    ptype: PlayerType,
+    difficulty: Difficulty,
+    limits: SearchLimits,
+    threads: usize,
+    eval: Eval,
+    personality: Personality,
+    engine: Option<Arc<Mutex<ExternalEngine>>>
+}
+
+impl Config {
+    /// Parses a list like 'name=tuned,depth=4,eval=eval.txt', unknown keys are errors.
+    /// Personalities are looked up in 'personalities' and the built-in ones.
+    fn parse(spec: &str, name: &str, personalities: &[Personality]) -> Result<Self, String> {
+        let mut config = Config{ name: String::from(name),
+                                 ptype: PlayerType::Smart,
+                                 difficulty: Difficulty::Strongest,
+                                 limits: SearchLimits::default(),
+                                 threads: 1,
+                                 eval: Eval::default(),
+                                 personality: Personality::default(),
+                                 engine: None };
+        let mut limits = SearchLimits::infinite();
+
+        for item in spec.split(',').filter(|i| !i.is_empty()) {
+            let mut parts = item.splitn(2, '=');
+            let (key, value) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+            let number = value.parse::<u64>().map_err(|_| format!("'{}' expects a number", key));
+            match key {
+                "name" => config.name = String::from(value),
+                "type" => {
+                    config.ptype = match value {
+                        "smart" => PlayerType::Smart,
+                        "dumb" => PlayerType::Dumb,
+                        _ => return Err(format!("unknown player type '{}'", value))
+                    }
+                },
+                "personality" => {
+                    config.personality = try!(Personality::find(personalities, value)
+                                                  .ok_or(format!("unknown personality '{}'", value)))
+                },
+                "difficulty" => {
+                    config.difficulty = try!(Difficulty::from_name(value).ok_or(format!("unknown difficulty '{}'", value)))
+                },
+                "depth" => limits.depth = Some(try!(number) as u8),
+                "nodes" => limits.nodes = Some(try!(number)),
+                "movetime" => limits.movetime = Some(Duration::from_millis(try!(number))),
+                "threads" => config.threads = (try!(number) as usize).max(1),
+                "eval" => {
+                    config.eval = try!(Eval::load(value).map_err(|e| format!("could not load {}: {}", value, e)))
+                },
+                "engine" => {
+                    let engine = try!(ExternalEngine::start(value).map_err(|e| format!("could not start {}: {}", value, e)));
+                    if config.name == name {
+                        config.name = engine.name().to_string();
+                    }
+                    config.engine = Some(Arc::new(Mutex::new(engine)));
+                    config.ptype = PlayerType::Engine;
+                },
+                _ => return Err(format!("unknown key '{}'", key))
+            }
+        }
+
+        // Without any limit the AI thinks for the usual time
+        if limits.depth.is_some() || limits.nodes.is_some() || limits.movetime.is_some() {
+            config.limits = limits;
+        }
+        Ok(config)
+    }
+
+    /// Lets the player use this configuration
+    fn apply(&self, player: &mut Player) {
+        player.set_ptype(self.ptype);
+        player.set_difficulty(self.difficulty);
+        player.set_search_limits(self.limits);
+        player.set_threads(self.threads);
+        player.set_eval(self.eval);
+        player.set_personality(self.personality.clone());
+        player.set_engine(self.engine.clone());
+    }
+}
+
+/// Plays one game to its end, returns the result and why the game ended
+fn play_game(mut game: ChessGame, white: &Config, black: &Config, max_plies: usize,
+             control: &Option<TimeControl>) -> (ChessGame, &'static str, &'static str) {
+    white.apply(&mut game.white_player);
+    black.apply(&mut game.black_player);
+    game.set_time_control(control.clone());
+
+    let mut seen = HashMap::new();
+    loop {
+        // Positions are the same if figures, side to move, castling and en passant are
+        let key = game.to_fen().split_whitespace().take(4).collect::<Vec<_>>().join(" ");
+        let count = {
+            let count = seen.entry(key).or_insert(0);
+            *count += 1;
+            *count
+        };
+
+        if game.check_clock() {
+            let outcome = game.outcome.unwrap();
+            return (game, outcome.result, outcome.reason)
+        }
+        if game.possible_moves().is_empty() {
+            return match (game.in_check(), game.turn) {
+                (true, true) => (game, "0-1", "checkmate"),
+                (true, false) => (game, "1-0", "checkmate"),
+                (false, _) => (game, "1/2-1/2", "stalemate")
+            }
+        }
+        if game.insufficient_material() {
+            return (game, "1/2-1/2", "insufficient material")
+        }
+        if count >= 3 {
+            return (game, "1/2-1/2", "threefold repetition")
+        }
+        if game.halfmove_clock >= 100 {
+            return (game, "1/2-1/2", "fifty move rule")
+        }
+        if game.history.len() >= max_plies {
+            return (game, "1/2-1/2", "move limit")
+        }
+
+        // The AI's explanation of the move ends up in the PGN comments
+        // The AI may also resign, claim a draw or accept the opponent's draw offer
+        if game.do_ai_turn().is_none() {
+            return match game.outcome {
+                Some(outcome) => (game, outcome.result, outcome.reason),
+                None if game.turn => (game, "0-1", "no move"),
+                None => (game, "1-0", "no move")
+            }
+        }
+    }
+}
+
+/// Reads one position per line as FEN or EPD, empty lines and lines starting with '#' are skipped
+fn read_openings(path: &str) -> Result<Vec<String>, String> {
+    let mut text = String::new();
+    try!(File::open(path).and_then(|mut f| f.read_to_string(&mut text)).map_err(|e| e.to_string()));
+
+    let mut openings = Vec::new();
+    for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        // EPD has operations instead of the move counters
+        let epd = line.split_whitespace().take(4).collect::<Vec<_>>().join(" ");
+        match (ChessGame::from_fen(line), ChessGame::from_fen(&epd)) {
+            (Ok(_), _) => openings.push(String::from(line)),
+            (_, Ok(_)) => openings.push(epd),
+            (Err(e), _) => return Err(format!("invalid position '{}': {}", line, e))
+        }
+    }
+    Ok(openings)
+}
+
+/// Returns the Elo difference for a score between 0 and 1
+fn elo(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+/// Formats an Elo difference, a score of 0 or 1 has none
+fn format_elo(elo: f64) -> String {
+    if elo.is_finite() { format!("{:.1}", elo) } else { String::from("inf") }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut first = None;
+    let mut second = None;
+    let mut personalities = Vec::new();
+    let mut games = 2;
+    let mut openings = Vec::new();
+    let mut variant: Arc<Variant> = Arc::new(variant::Standard);
+    let mut out = String::from("match.pgn");
+    let mut max_plies = DEFAULT_MAX_PLIES;
+    let mut seed = None;
+    let mut control = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            // The two AI configurations, e.g. 'depth=4,eval=tuned.txt'
+            "--first" => first = args.next(),
+            "--second" => second = args.next(),
+            // File with more personalities for the configurations
+            "--personalities" => {
+                match args.next().map(|path| (Personality::load(&path), path)) {
+                    Some((Ok(list), _)) => personalities.extend(list),
+                    Some((Err(e), path)) => println!("Could not load personalities from {}: {}", path, e),
+                    None => println!("--personalities expects a file")
+                }
+            },
+            "--games" => {
+                match args.next().and_then(|v| v.parse::<usize>().ok()) {
+                    Some(n) => games = n,
+                    None => println!("--games expects a number of games")
+                }
+            },
+            // File with start positions, each one is played with both colours
+            "--openings" => {
+                match args.next().map(|path| (read_openings(&path), path)) {
+                    Some((Ok(ref o), _)) if !o.is_empty() => openings = o.clone(),
+                    Some((Ok(_), path)) => println!("No positions in {}", path),
+                    Some((Err(e), path)) => println!("Could not read openings {}: {}", path, e),
+                    None => println!("--openings expects a file")
+                }
+            },
+            // Variant of every game, e.g. 'kingofthehill'
+            "--variant" => {
+                match args.next().map(|name| (variant::find(&name), name)) {
+                    Some((Some(v), _)) => variant = v,
+                    Some((None, name)) => println!("Unknown variant '{}', known are: {}", name, variant::NAMES.join(", ")),
+                    None => println!("--variant expects the name of a variant")
+                }
+            },
+            // File the games are written to
+            "--pgn" => {
+                match args.next() {
+                    Some(path) => out = path,
+                    None => println!("--pgn expects a file")
+                }
+            },
+            // Seed of the first game, the following games count up from it
+            "--seed" => {
+                match args.next().and_then(|v| v.parse::<u64>().ok()) {
+                    Some(s) => seed = Some(s),
+                    None => println!("--seed expects a number")
+                }
+            },
+            // Time control of every game, e.g. '60+1', the AIs then plan their time on the clock
+            "--tc" => {
+                match args.next().map(|tc| TimeControl::parse(&tc)) {
+                    Some(Ok(tc)) => control = Some(tc),
+                    Some(Err(e)) => println!("Invalid time control: {}", e),
+                    None => println!("--tc expects a time control")
+                }
+            },
+            // Plies after which a game is a draw
+            "--max-plies" => {
+                match args.next().and_then(|v| v.parse::<usize>().ok()) {
+                    Some(n) => max_plies = n,
+                    None => println!("--max-plies expects a number of plies")
+                }
+            },
+            _ => println!("Unknown argument: {}", arg)
+        }
+    }
+
+    if openings.is_empty() {
+        openings.push(String::from(variant.start_fen()));
+    }
+
+    let first = first.ok_or(String::from("no configuration for the first AI, use --first"))
+                     .and_then(|spec| Config::parse(&spec, "first", &personalities));
+    let second = second.ok_or(String::from("no configuration for the second AI, use --second"))
+                       .and_then(|spec| Config::parse(&spec, "second", &personalities));
+    let (first, second) = match (first, second) {
+        (Ok(f), Ok(s)) => (f, s),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut file = match File::create(&out) {
+        Ok(file) => file,
+        Err(e) => {
+            println!("Could not create {}: {}", out, e);
+            std::process::exit(1);
+        }
+    };
+
+    let date = time::strftime("%Y.%m.%d", &time::now()).unwrap_or(String::from("????.??.??"));
+    // Wins, losses and draws of the first AI
+    let (mut wins, mut losses, mut draws) = (0, 0, 0);
+
+    for round in 0..games {
+        // Every opening is played twice, the first AI is white in the first game
+        let opening = &openings[(round / 2) % openings.len()];
+        let first_white = round % 2 == 0;
+        let (white, black) = if first_white { (&first, &second) } else { (&second, &first) };
+
+        let game = match ChessGame::from_fen_variant(opening, variant.clone()) {
+            Ok(mut game) => {
+                game.seed = seed.map_or(game.seed, |s| s.wrapping_add(round as u64));
+                game
+            },
+            Err(e) => {
+                println!("Invalid opening {}: {}", opening, e);
+                continue
+            }
+        };
+        let (game, result, reason) = play_game(game, white, black, max_plies, &control);
+
+        match (result, first_white) {
+            ("1-0", true) | ("0-1", false) => wins += 1,
+            ("0-1", true) | ("1-0", false) => losses += 1,
+            _ => draws += 1
+        }
+        println!("Game {} of {}: {} - {} {} ({}, {} plies)",
+                 round + 1, games, white.name, black.name, result, reason, game.history.len());
+
+        let termination = match reason {
+            "move limit" => "adjudication",
+            "time forfeit" => "time forfeit",
+            "no move" => "abandoned",
+            _ => "normal"
+        };
+        let tags = vec![(String::from("Event"), String::from("RustChess match")),
+                        (String::from("Site"), String::from("?")),
+                        (String::from("Date"), date.clone()),
+                        (String::from("Round"), (round + 1).to_string()),
+                        (String::from("White"), white.name.clone()),
+                        (String::from("Black"), black.name.clone()),
+                        (String::from("Termination"), String::from(termination)),
+                        (String::from("TimeControl"), control.as_ref().map_or(String::from("-"), |c| c.to_string())),
+                        (String::from("Seed"), game.seed.to_string())];
+        let record = PgnGame::from_game(&game, tags, result);
+        if let Err(e) = write!(file, "{}", record) {
+            println!("Could not write to {}: {}", out, e);
+        }
+    }
+
+    let played = wins + losses + draws;
+    if played == 0 {
+        println!("No games were played");
+        return
+    }
+    let n = played as f64;
+    let score = (wins as f64 + draws as f64 / 2.0) / n;
+    // Standard error of the mean score, the error bar covers 95%
+    let variance = (wins as f64 * (1.0 - score).powi(2) + draws as f64 * (0.5 - score).powi(2) +
+                    losses as f64 * score.powi(2)) / n;
+    let margin = 1.96 * (variance / n).sqrt();
+    let error = (elo((score + margin).min(1.0)) - elo((score - margin).max(0.0))) / 2.0;
+
+    println!("Score of {} vs {}: {} - {} - {} [{:.3}] {}", first.name, second.name, wins, losses, draws, score, played);
+    println!("Elo difference: {} +/- {}", format_elo(elo(score)), format_elo(error));
+    println!("Games written to {}", out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_difference() {
+        assert_eq!(elo(0.5), 0.0);
+        assert!((elo(0.75) - 190.8).abs() < 0.1);
+        assert!((elo(0.25) + 190.8).abs() < 0.1);
+        assert_eq!(format_elo(elo(1.0)), "inf");
+        assert_eq!(format_elo(elo(0.64)), "100.0");
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse("name=fast,depth=2,type=dumb,threads=0", "A", &[]).unwrap();
+        assert_eq!(config.name, "fast");
+        assert_eq!(config.ptype, PlayerType::Dumb);
+        assert_eq!(config.limits, SearchLimits::depth(2));
+        assert_eq!(config.threads, 1);
+
+        assert_eq!(Config::parse("", "A", &[]).unwrap().limits, SearchLimits::default());
+        assert!(Config::parse("depth=deep", "A", &[]).is_err());
+        assert!(Config::parse("colour=red", "A", &[]).is_err());
+    }
+
+    #[test]
+    fn game_ends() {
+        let config = Config::parse("depth=1", "A", &[]).unwrap();
+        let mate = ChessGame::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let (game, result, reason) = play_game(mate, &config, &config, 10, &None);
+        assert_eq!((result, reason), ("1-0", "checkmate"));
+        assert_eq!(game.history.len(), 1);
+
+        let (game, result, reason) = play_game(ChessGame::new(), &config, &config, 4, &None);
+        assert_eq!((result, reason), ("1/2-1/2", "move limit"));
+        assert_eq!(game.history.len(), 4);
+    }
+}
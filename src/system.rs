@@ -20,8 +20,28 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use chess::logic::{Color, Position};
-use chess::player::PlayerType;
+//! Connects a game to a user interface: figure selection, AI turns, hints, clocks and saving.
+
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chess::logic::{parse_move, Color, Figure, Position};
+use chess::ai::{self, Difficulty};
+use chess::player::{Player, PlayerType};
+use chess::polyglot::Book;
+use chess::syzygy::Tablebase;
+use chess::engine::{ExternalEngine, PendingMove};
+use chess::search::{SearchLimits, SearchResult};
+use chess::ponder::Ponder;
+use chess::eval::Eval;
+use chess::pgn::{self, PgnGame};
+use chess::personality::Personality;
+use chess::clock::{self, TimeControl};
+use chess::event::GameEvent;
+use chess::variant;
+use chess::setup::Setup;
 use chess::ChessGame;
 
 /// Handles interaction between game logic and visualization
@@ -34,20 +54,75 @@ pub struct System {
     to: Option<Position>,
     // Holds Board and Players
     game: ChessGame,
+    // What the user should be told since the user interface last asked
+    messages: Vec<String>,
+    // Everything that happened in the game since the user interface last looked
+    events: Receiver<GameEvent>,
     ai: bool,
-    upgrade: Option<(Color, Position)>
+    // Minimum time between two AI moves and when the last one was made
+    min_ai_delay: Duration,
+    last_ai_move: Option<Instant>,
+    // Endgame tablebase used to show the outcome of the current position
+    tablebase: Option<Arc<Tablebase>>,
+    // Number of candidate moves a hint shows, the moves it suggests with the best one first
+    // and the analysis for it running in another thread with the position it is for
+    hint_lines: usize,
+    hint: Vec<(Position, Position)>,
+    hint_search: Option<(String, Receiver<Vec<SearchResult>>)>,
+    // Search of an AI on the human's time and whether the human played the move it expected
+    ponder: Option<Ponder>,
+    ponder_hit: bool,
+    // Move an external engine is looking for in another thread
+    engine_move: Option<PendingMove>,
+    // Personalities the AI can take on, the built-in ones and those loaded from files
+    personalities: Vec<Personality>,
+    // Position being set up while the editor is open and the figure it places
+    editor: Option<Setup>,
+    editor_figure: (Color, Figure)
 }
 
+/// Number of candidate moves shown by a hint by default
+pub const DEFAULT_HINT_LINES: usize = 3;
+
+/// Time the AI thinks about a hint in milliseconds
+const HINT_TIME: u64 = 1000;
+
+/// Default minimum time between two AI moves in milliseconds
+pub const DEFAULT_AI_DELAY: u64 = 250;
+
+/// Shown when the editor is opened
+const EDITOR_HELP: &'static str = "\
+Editor: left click puts the chosen figure on an empty field, or picks up a figure and puts it on the
+next field clicked, right click removes a figure. K, Q, R, B, N and P choose the figure, Tab its color,
+T the side to move, F5 to F8 the castling rights (white kingside, white queenside, black kingside,
+black queenside). F saves the position as FEN, Enter starts playing from it and F2 leaves the editor.";
+
 impl System {
+    /// Returns a system with a new game between two human players
     pub fn new() -> Self {
+        let mut game = ChessGame::new();
+        let events = game.events();
         System {
             mouse_x: 0,
             mouse_y: 0,
             from: None,
             to: None,
-            game: ChessGame::new(),
+            game: game,
+            messages: Vec::new(),
+            events: events,
             ai: false,
-            upgrade: None
+            min_ai_delay: Duration::from_millis(DEFAULT_AI_DELAY),
+            last_ai_move: None,
+            tablebase: None,
+            hint_lines: DEFAULT_HINT_LINES,
+            hint: Vec::new(),
+            hint_search: None,
+            ponder: None,
+            ponder_hit: false,
+            engine_move: None,
+            personalities: Personality::builtin(),
+            editor: None,
+            editor_figure: (Color::White, Figure::Pawn)
         }
     }
 
@@ -62,6 +137,16 @@ impl System {
         self.mouse_y = y;
     }
 
+    /// Returns what the user should be told since the last call, the user interface shows it
+    pub fn take_messages(&mut self) -> Vec<String> {
+        ::std::mem::replace(&mut self.messages, Vec::new())
+    }
+
+    /// Leaves a message for the user interface to show
+    fn tell(&mut self, message: String) {
+        self.messages.push(message);
+    }
+
     /// Updates player's figure selection
     pub fn set_selected(&mut self, pos: (u8, u8)) {
         let at = if Position::is_pos(pos.0, pos.1) {Position::new(pos.0, pos.1)} else {return};
@@ -92,30 +177,106 @@ impl System {
         }
     }
 
-    /// If two fields have been selected execute a turn an return the positions which need to be updates visually
-    pub fn check_ready_and_play(&mut self) -> Option<((Color, Position, Position), bool)> {
+    /// Selects a figure of 'color' in the reserve beside the board to drop it, if that player
+    /// has one and it is their turn
+    pub fn select_reserve(&mut self, color: Color, fig: Figure) {
+        if color == self.game.turn_color() && self.game.active_player().reserve().contains(&fig) {
+            self.from = Some(Position::reserve(fig));
+            self.to = None;
+        }
+    }
+
+    /// If two fields have been selected execute a turn and return whether it was made,
+    /// the game's events tell what changed
+    pub fn check_ready_and_play(&mut self) -> bool {
         if self.from.is_some() && self.to.is_some() {
-            self.reset_upgrade();
             let result = self.game.do_turn(self.from.unwrap(), self.to.unwrap());
             if result >= 0 {
-                // We need to take the opposite color of the one who's turn it is now
-                // because our turn has already been made
-                let turn_color = !self.game.turn_color();
-
                 let before = self.from.unwrap();
                 let after = self.to.unwrap();
                 self.reset_selection();
 
-                if result == 1 || result == 3 {
-                    self.upgrade = Some((turn_color, after));
+                self.hint.clear();
+                // Keep the AI's search if it expected this move
+                if let Some(p) = self.ponder.take() {
+                    if p.expected() == (before, after) {
+                        self.ponder = Some(p);
+                        self.ponder_hit = true;
+                    } else {
+                        p.miss();
+                    }
+                }
+                self.print_tablebase_verdict();
+                if self.game.gameover {
+                    self.print_outcome();
                 }
 
-                return Some(((turn_color, before, after), result >= 2))
-            } else {
-                return None
+                return true
             }
         }
-        None
+        false
+    }
+
+    /// Plays a move of the human player typed in standard algebraic notation like 'Nf3'
+    /// or in long algebraic notation like 'g1f3'
+    pub fn play_typed(&mut self, text: &str) -> Result<(), String> {
+        if self.game.gameover {
+            return Err(String::from("The game is over"))
+        }
+        if self.game.active_player().ptype() != PlayerType::Human {
+            return Err(String::from("It is the AI's turn"))
+        }
+
+        let text = text.trim();
+        match parse_move(text).or_else(|| pgn::parse_san(&mut self.game, text)) {
+            Some((from, to)) => {
+                self.from = Some(from);
+                self.to = Some(to);
+                if self.check_ready_and_play() {
+                    Ok(())
+                } else {
+                    self.reset_selection();
+                    Err(format!("{} is not a valid move", text))
+                }
+            },
+            None => Err(format!("{} is not a valid move", text))
+        }
+    }
+
+    /// Takes back the last move. If a human plays against the AI, the AI's moves are
+    /// taken back as well until it is the human's turn again.
+    pub fn undo(&mut self) -> bool {
+        self.stop_pondering();
+        self.reset_selection();
+        self.hint.clear();
+        if !self.game.undo() {
+            return false
+        }
+
+        let human = self.game.white_player.ptype() == PlayerType::Human ||
+                    self.game.black_player.ptype() == PlayerType::Human;
+        while human && self.game.active_player().ptype() != PlayerType::Human && self.game.undo() {}
+        true
+    }
+
+    /// Returns the events of the game since the last call, in the order they happened
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        self.events.try_iter().collect()
+    }
+
+    /// Returns the game
+    pub fn game(&self) -> &ChessGame {
+        &self.game
+    }
+
+    /// Returns the moves made so far in standard algebraic notation
+    pub fn move_list(&self) -> Vec<String> {
+        PgnGame::from_game(&self.game, Vec::new(), "*").moves
+    }
+
+    /// Returns whether an AI has to make the next move
+    pub fn ai_to_move(&self) -> bool {
+        !self.game.gameover && self.game.active_player().ptype() != PlayerType::Human
     }
 
     /// Reset field selections
@@ -124,30 +285,148 @@ impl System {
         self.to = None;
     }
 
-    /// Reset field selections
-    pub fn reset_upgrade(&mut self) {
-        self.upgrade = None;
+    /// Returns the minimum time between two AI moves
+    pub fn min_ai_delay(&self) -> Duration {
+        self.min_ai_delay
     }
 
-    pub fn upgrade(&self) -> Option<(Color, Position)> {
-        self.upgrade
+    /// Sets the minimum time between two AI moves
+    pub fn set_min_ai_delay(&mut self, delay: Duration) {
+        self.min_ai_delay = delay;
     }
 
-    /// Execute a turn for the AI
-    pub fn execute_ai_turn(&mut self) -> Option<((Color, Position, Position), bool)> {
-        self.reset_upgrade();
-        if let Some(((before, after), (captured, upgrade))) = self.game.do_ai_turn() {
+    /// Execute a turn for the AI, unless the last AI move was made too recently,
+    /// returns whether a move was made
+    pub fn execute_ai_turn(&mut self) -> bool {
+        if self.editor.is_some() {
+            return false
+        }
+        if let Some(last) = self.last_ai_move {
+            if last.elapsed() < self.min_ai_delay {
+                return false
+            }
+        }
+
+        if self.game.active_player().ptype() == PlayerType::Human {
+            return false
+        }
+
+        // An external engine thinks in another thread, so the window keeps responding until its
+        // move arrives. Moves for an earlier position or a player that is no engine any more are dropped.
+        let mut engine_result = None;
+        if let Some(p) = self.engine_move.take() {
+            if p.is_for(&self.game) && self.game.active_player().ptype() == PlayerType::Engine {
+                match p.poll() {
+                    Some(result) => engine_result = Some(result),
+                    None => {
+                        self.engine_move = Some(p);
+                        return false
+                    }
+                }
+            }
+        }
+        if engine_result.is_none() && !self.game.gameover {
+            if let Some(p) = ai::start_engine_move(&self.game) {
+                self.engine_move = Some(p);
+                return false
+            }
+        }
+
+        // Reuse the search done while the human was thinking if it expected the right move
+        let prepared = match self.ponder.take() {
+            Some(p) => if self.ponder_hit {
+                p.hit(&self.game.active_player().search_limits())
+            } else {
+                p.miss();
+                None
+            },
+            None => None
+        };
+        self.ponder_hit = false;
+
+        // The AI answers a draw offer of its opponent before it moves, it may also resign or claim a draw
+        let offered = self.game.draw_offer.map_or(false, |c| c != self.game.turn_color());
+        let ended = self.game.gameover;
+        let moved = match engine_result {
+            Some(Ok(m)) => self.game.do_ai_turn_with_move((m, None)),
+            Some(Err(e)) => {
+                self.tell(format!("External engine failed: {}, the smart AI moves instead", e));
+                let chosen = ai::get_smart_move_and_search(&self.game);
+                self.game.do_ai_turn_with_move(chosen)
+            },
+            None => self.game.do_ai_turn_with(prepared)
+        };
+        if offered && self.game.draw_offer.is_none() && !self.game.gameover {
+            self.tell(String::from("The AI declines the draw offer"));
+        }
+        if !ended && self.game.gameover {
+            self.print_outcome();
+        }
+
+        if moved.is_some() {
+            self.last_ai_move = Some(Instant::now());
+
             // We need to take the opposite color of the one who's turn it is now
             // because our turn has already been made
             let turn_color = !self.game.turn_color();
 
-            if upgrade {
-                self.upgrade = Some((turn_color, after));
+            self.hint.clear();
+            // Tell why the AI played the move, unless the human must not know what it did
+            let side = if turn_color == Color::White { "White" } else { "Black" };
+            let explained = match self.game.explanations.last() {
+                Some(&Some(ref explanation)) => {
+                    Some(format!("{} AI played {}: {}", side, explanation.pv.first().cloned().unwrap_or_default(), explanation))
+                },
+                _ => None
+            };
+            if self.fog().is_some() {
+                self.tell(format!("{} AI moved", side));
+            } else if let Some(text) = explained {
+                self.tell(text);
+            }
+            if self.game.draw_offer == Some(turn_color) {
+                self.tell(String::from("The AI offers a draw, press Y to accept or N to decline"));
+            }
+            self.print_tablebase_verdict();
+            self.start_pondering();
+
+            return true
+        }
+        false
+    }
+
+    /// Lets the AI that just moved think on the time of the human player whose turn it is now,
+    /// if pondering is on for it and it knows which reply to expect
+    fn start_pondering(&mut self) {
+        self.stop_pondering();
+
+        let ai = if self.game.turn { &self.game.black_player } else { &self.game.white_player };
+        if !ai.ponder() || ai.ptype() != PlayerType::Smart || self.game.active_player().ptype() != PlayerType::Human {
+            return
+        }
+        if let Some(expected) = self.game.expected_reply {
+            let color = ai.color();
+            self.ponder = Ponder::start(&self.game, expected);
+            if self.ponder.is_some() {
+                self.tell(format!("{:?} AI is pondering on {}-{}", color, expected.0.to_algebraic(), expected.1.to_algebraic()));
             }
+        }
+    }
 
-            return Some(((turn_color, before, after), captured))
+    /// Stops and discards the search on the human's time
+    fn stop_pondering(&mut self) {
+        if let Some(p) = self.ponder.take() {
+            p.miss();
         }
-        None
+        self.ponder_hit = false;
+    }
+
+    /// Switches pondering of the white ('which' is true) or black player's smart AI on or off
+    pub fn toggle_ponder(&mut self, which: bool) {
+        self.stop_pondering();
+        let ponder = !self.player_mut(which).ponder();
+        self.player_mut(which).set_ponder(ponder);
+        self.tell(format!("{} AI pondering: {}", if which { "White" } else { "Black" }, if ponder { "on" } else { "off" }));
     }
 
     /// Returns whether an AI is active
@@ -155,35 +434,642 @@ impl System {
         self.ai
     }
 
-    /// Returns whether an pawn needs to be updated to a queen
-    pub fn upgrade_needed(&self) -> bool {
-        self.upgrade.is_some()
-    }
-
     /// Transforms a board position to a field position in the world
     pub fn from_position(pos: &Position) -> ::cgmath::Point3<f32> {
         ::cgmath::Point3::new(3.5 - pos.x as f32, 0.1, 3.5 - pos.y as f32)
     }
 
-    /// Toggles player type from AI to Human or the other way round and resets the field selections
-    pub fn toggle_player_ai(&mut self, which: bool) {
+    /// Returns the white player if 'which' is true and the black player otherwise
+    fn player_mut(&mut self, which: bool) -> &mut Player {
         if which {
-            if self.game.white_player.ptype() == PlayerType::Human {
-                self.game.white_player.set_ptype(PlayerType::Dumb);
-                self.ai = true;
-            } else {
-                self.game.white_player.set_ptype(PlayerType::Human);
-                self.ai = self.game.black_player.ptype() != PlayerType::Human;
-            }
+            &mut self.game.white_player
         } else {
-            if self.game.black_player.ptype() == PlayerType::Human {
-                self.game.black_player.set_ptype(PlayerType::Dumb);
-                self.ai = true;
+            &mut self.game.black_player
+        }
+    }
+
+    /// Sets the player type and difficulty of the white ('which' is true) or black player
+    pub fn set_player(&mut self, which: bool, ptype: PlayerType, difficulty: Difficulty) {
+        {
+            let player = self.player_mut(which);
+            player.set_ptype(ptype);
+            player.set_difficulty(difficulty);
+        }
+        self.ai = self.game.white_player.ptype() != PlayerType::Human ||
+                  self.game.black_player.ptype() != PlayerType::Human;
+        self.reset_selection();
+        self.stop_pondering();
+    }
+
+    /// Cycles the player type from Human to Dumb AI to Smart AI to the external engine, if the player
+    /// has one, and back to Human and resets the field selections
+    pub fn toggle_player_ai(&mut self, which: bool) {
+        let (ptype, difficulty) = {
+            let player = self.player_mut(which);
+            let next = match player.ptype() {
+                PlayerType::Human => PlayerType::Dumb,
+                PlayerType::Dumb => PlayerType::Smart,
+                PlayerType::Smart if player.engine().is_some() => PlayerType::Engine,
+                PlayerType::Smart | PlayerType::Engine => PlayerType::Human
+            };
+            (next, player.difficulty())
+        };
+        self.set_player(which, ptype, difficulty);
+        self.print_player(which);
+    }
+
+    /// Sets the external engine of the white ('which' is true) or black player and lets it play
+    pub fn set_engine(&mut self, which: bool, engine: ExternalEngine) {
+        let difficulty = self.player_mut(which).difficulty();
+        self.player_mut(which).set_engine(Some(Arc::new(Mutex::new(engine))));
+        self.set_player(which, PlayerType::Engine, difficulty);
+        self.print_player(which);
+    }
+
+    /// Sets the seed of the AI's random decisions, the same seed replays the same game
+    pub fn set_seed(&mut self, seed: u64) {
+        self.stop_pondering();
+        self.game.seed = seed;
+    }
+
+    /// Switches to the variant with the given name, only possible before the first move
+    pub fn set_variant(&mut self, name: &str) {
+        self.stop_pondering();
+        let text = match variant::find(name) {
+            Some(variant) => if self.game.set_variant(variant) {
+                format!("Playing {}", self.game.variant().name())
             } else {
-                self.game.black_player.set_ptype(PlayerType::Human);
-                self.ai = self.game.white_player.ptype() != PlayerType::Human;
+                String::from("The variant can only be changed before the first move")
+            },
+            None => format!("Unknown variant '{}', known are: {}", name, variant::NAMES.join(", "))
+        };
+        self.tell(text);
+    }
+
+    /// Returns the seed of the AI's random decisions
+    pub fn seed(&self) -> u64 {
+        self.game.seed
+    }
+
+    /// Writes the game so far to a PGN file, the seed of the AI is kept in a tag
+    pub fn save_game(&mut self, path: &str) {
+        let result = if let Some(outcome) = self.game.outcome {
+            outcome.result
+        } else if !self.game.possible_moves().is_empty() {
+            "*"
+        } else {
+            match (self.game.in_check(), self.game.turn) {
+                (true, true) => "0-1",
+                (true, false) => "1-0",
+                (false, _) => "1/2-1/2"
+            }
+        };
+        let date = ::time::strftime("%Y.%m.%d", &::time::now()).unwrap_or(String::from("????.??.??"));
+        let tags = vec![(String::from("Event"), String::from("RustChess game")),
+                        (String::from("Site"), String::from("?")),
+                        (String::from("Date"), date),
+                        (String::from("Round"), String::from("-")),
+                        (String::from("White"), format!("{:?}", self.game.white_player.ptype())),
+                        (String::from("Black"), format!("{:?}", self.game.black_player.ptype())),
+                        (String::from("Seed"), self.game.seed.to_string())];
+
+        let record = PgnGame::from_game(&self.game, tags, result);
+        let text = match ::std::fs::File::create(path).and_then(|mut f| ::std::io::Write::write_all(&mut f, record.to_string().as_bytes())) {
+            Ok(()) => format!("Saved game to {}", path),
+            Err(e) => format!("Could not save game to {}: {}", path, e)
+        };
+        self.tell(text);
+    }
+
+    /// Continues the first game of a PGN file with the seed of the AI it was saved with
+    pub fn load_game(&mut self, path: &str) {
+        let mut text = String::new();
+        if let Err(e) = ::std::fs::File::open(path).and_then(|mut f| ::std::io::Read::read_to_string(&mut f, &mut text)) {
+            self.tell(format!("Could not load game from {}: {}", path, e));
+            return
+        }
+
+        self.stop_pondering();
+        self.engine_move = None;
+        self.reset_selection();
+        self.hint.clear();
+        let text = match pgn::read_games(&text).first().map_or(Err(String::from("no game found")), |g| g.load_into(&mut self.game)) {
+            Ok(()) => format!("Loaded game from {}, AI seed: {}", path, self.game.seed),
+            Err(e) => format!("Could not load game from {}: {}", path, e)
+        };
+        self.tell(text);
+    }
+
+    /// Sets the number of threads the smart AI of both players searches with
+    pub fn set_threads(&mut self, threads: usize) {
+        self.stop_pondering();
+        self.game.white_player.set_threads(threads);
+        self.game.black_player.set_threads(threads);
+    }
+
+    /// Sets the evaluation weights the smart AI of both players uses
+    pub fn set_eval(&mut self, eval: Eval) {
+        self.stop_pondering();
+        self.game.white_player.set_eval(eval);
+        self.game.black_player.set_eval(eval);
+    }
+
+    /// Sets the opening book used by the AI of both players
+    pub fn set_book(&mut self, book: Option<Arc<Book>>) {
+        self.game.white_player.set_book(book.clone());
+        self.game.black_player.set_book(book);
+    }
+
+    /// Sets the number of plies for which both players' AI use the opening book
+    pub fn set_book_depth(&mut self, depth: usize) {
+        self.game.white_player.set_book_depth(depth);
+        self.game.black_player.set_book_depth(depth);
+    }
+
+    /// Sets the endgame tablebase used by the AI of both players and for showing the outcome
+    pub fn set_tablebase(&mut self, tb: Option<Arc<Tablebase>>) {
+        self.game.white_player.set_tablebase(tb.clone());
+        self.game.black_player.set_tablebase(tb.clone());
+        self.tablebase = tb;
+    }
+
+    /// Tells the user the tablebase's outcome for the current position, if it has one
+    pub fn print_tablebase_verdict(&mut self) {
+        if !self.game.variant().is_standard() {
+            return
+        }
+        let verdict = self.tablebase.as_ref().and_then(|tb| tb.probe_wdl(&self.game).map(|wdl| {
+            let side = if self.game.turn { "White" } else { "Black" };
+            match tb.probe_dtz(&self.game) {
+                Some(dtz) if dtz != 0 => format!("Tablebase: {} to move, {} (DTZ {})", side, wdl, dtz.abs()),
+                _ => format!("Tablebase: {} to move, {}", side, wdl)
             }
+        }));
+        if let Some(text) = verdict {
+            self.tell(text);
+        }
+    }
+
+    /// Sets the number of candidate moves a hint shows
+    pub fn set_hint_lines(&mut self, lines: usize) {
+        self.hint_lines = lines.max(1);
+    }
+
+    /// Returns the move suggested by the last hint, until the next move is made
+    pub fn hint(&self) -> Option<(Position, Position)> {
+        self.hint.first().cloned()
+    }
+
+    /// Returns the candidate moves of the last hint with the best one first, until the next move is made
+    pub fn hint_moves(&self) -> &[(Position, Position)] {
+        &self.hint
+    }
+
+    /// Returns the player whose view is shown in variants that hide figures with the fields
+    /// they see: the human player, or the one whose turn it is if both are human. Games of
+    /// two AIs and finished games are shown completely.
+    pub fn fog(&self) -> Option<(Color, [[bool; 8]; 8])> {
+        if !self.game.variant().hides_figures() || self.game.gameover {
+            return None
         }
+
+        let human = |p: &Player| p.ptype() == PlayerType::Human;
+        let viewer = match (human(&self.game.white_player), human(&self.game.black_player)) {
+            (true, true) => self.game.turn_color(),
+            (true, false) => Color::White,
+            (false, true) => Color::Black,
+            (false, false) => return None
+        };
+        Some((viewer, self.game.board.visible(viewer)))
+    }
+
+    /// Returns the position being set up while the editor is open
+    pub fn setup(&self) -> Option<&Setup> {
+        self.editor.as_ref()
+    }
+
+    /// Returns the field of the figure picked up in the editor
+    pub fn picked(&self) -> Option<Position> {
+        self.editor.as_ref().and(self.from)
+    }
+
+    /// Opens the editor with the current position, or closes it and goes on with the game
+    pub fn toggle_editor(&mut self) {
         self.reset_selection();
+        self.hint.clear();
+        if self.editor.take().is_some() {
+            self.tell(String::from("Left the editor, the game goes on"));
+            return
+        }
+
+        self.stop_pondering();
+        self.editor = Some(Setup::from_game(&self.game));
+        self.tell(String::from(EDITOR_HELP));
+        self.print_editor_figure();
+    }
+
+    /// Chooses the kind of figure the editor places
+    pub fn choose_figure(&mut self, fig: Figure) {
+        self.editor_figure.1 = fig;
+        self.print_editor_figure();
+    }
+
+    /// Switches the color of the figures the editor places
+    pub fn toggle_figure_color(&mut self) {
+        self.editor_figure.0 = !self.editor_figure.0;
+        self.print_editor_figure();
+    }
+
+    /// Tells the user which figure the editor places
+    fn print_editor_figure(&mut self) {
+        let (color, fig) = self.editor_figure;
+        self.tell(format!("Placing {} {}s", if color == Color::White { "white" } else { "black" }, fig.name()));
+    }
+
+    /// Handles a left click on a field in the editor: a picked up figure is put there, otherwise
+    /// the figure on it is picked up or the chosen figure is placed on the empty field
+    pub fn edit_field(&mut self, pos: (u8, u8)) {
+        let at = if Position::is_pos(pos.0, pos.1) { Position::new(pos.0, pos.1) } else { return };
+        let (color, fig) = self.editor_figure;
+        if let Some(ref mut setup) = self.editor {
+            match self.from.take() {
+                Some(from) => {
+                    setup.move_figure(from, at);
+                },
+                None if !setup.board.is_empty(at) => self.from = Some(at),
+                None => setup.place(at, fig, color)
+            }
+        }
+    }
+
+    /// Removes the figure on a field in the editor
+    pub fn remove_figure(&mut self, pos: (u8, u8)) {
+        let at = if Position::is_pos(pos.0, pos.1) { Position::new(pos.0, pos.1) } else { return };
+        if let Some(ref mut setup) = self.editor {
+            setup.remove(at);
+            self.from = None;
+        }
+    }
+
+    /// Lets the other side move first in the position of the editor
+    pub fn toggle_setup_turn(&mut self) {
+        let text = match self.editor {
+            Some(ref mut setup) => {
+                setup.toggle_turn();
+                format!("{} moves first", if setup.turn { "White" } else { "Black" })
+            },
+            None => return
+        };
+        self.tell(text);
+    }
+
+    /// Gives or takes a castling right in the editor, 'index' counts white kingside,
+    /// white queenside, black kingside and black queenside
+    pub fn toggle_setup_castling(&mut self, index: usize) {
+        let text = match self.editor {
+            Some(ref mut setup) => {
+                setup.toggle_castling(index);
+                let names = ["white kingside", "white queenside", "black kingside", "black queenside"];
+                let rights: Vec<&str> = names.iter().zip(setup.castling().iter()).filter(|r| *r.1).map(|r| *r.0).collect();
+                format!("Castling: {}", if rights.is_empty() { String::from("none") } else { rights.join(", ") })
+            },
+            None => return
+        };
+        self.tell(text);
+    }
+
+    /// Writes the position of the editor as FEN to 'path' if it is legal
+    pub fn export_setup(&mut self, path: &str) {
+        let text = match self.editor {
+            Some(ref setup) => match setup.validate() {
+                Ok(_) => {
+                    let fen = setup.to_fen();
+                    match ::std::fs::File::create(path).and_then(|mut f| ::std::io::Write::write_all(&mut f, format!("{}\n", fen).as_bytes())) {
+                        Ok(()) => format!("Saved position {} to {}", fen, path),
+                        Err(e) => format!("Could not save position to {}: {}", path, e)
+                    }
+                },
+                Err(e) => format!("The position is not legal: {}", e)
+            },
+            None => return
+        };
+        self.tell(text);
+    }
+
+    /// Starts a new game from the position of the editor if it is legal and closes the editor,
+    /// the players keep their settings. Returns whether the game was started.
+    pub fn start_setup(&mut self) -> bool {
+        let fen = match self.editor {
+            Some(ref setup) => match setup.validate() {
+                Ok(_) => setup.to_fen(),
+                Err(e) => {
+                    self.tell(format!("The position is not legal: {}", e));
+                    return false
+                }
+            },
+            None => return false
+        };
+
+        match self.game.set_position(&fen) {
+            Ok(_) => {
+                self.tell(format!("Playing from {}", fen));
+                self.editor = None;
+                self.reset_selection();
+                self.last_ai_move = None;
+                true
+            },
+            Err(e) => {
+                self.tell(format!("Could not start from {}: {}", fen, e));
+                false
+            }
+        }
+    }
+
+    /// Lets the AI analyse the position for the player whose turn it is in another thread,
+    /// 'poll_hint' picks up the best candidate moves when it is done
+    pub fn show_hint(&mut self) {
+        if self.game.gameover || self.editor.is_some() || self.hint_search.is_some() {
+            return
+        }
+
+        let limits = SearchLimits::movetime(Duration::from_millis(HINT_TIME));
+        // A hint knows no more than the player who asks for it
+        let seen = self.game.seen_by(self.game.turn_color());
+        let lines = self.hint_lines;
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            sender.send(ai::analyse(&seen, lines, &limits)).unwrap_or(());
+        });
+        self.hint_search = Some((self.game.to_fen(), receiver));
+    }
+
+    /// Returns whether the AI is still analysing for a hint
+    pub fn hint_pending(&self) -> bool {
+        self.hint_search.is_some()
+    }
+
+    /// Takes the result of the hint's analysis if it is done, tells the user the candidate
+    /// moves with their scores and returns whether there is a new hint. A hint for a
+    /// position that is gone by now is dropped.
+    pub fn poll_hint(&mut self) -> bool {
+        let lines = match self.hint_search.take() {
+            Some((fen, receiver)) => match receiver.try_recv() {
+                Ok(lines) => if fen == self.game.to_fen() { lines } else { return false },
+                Err(TryRecvError::Empty) => {
+                    self.hint_search = Some((fen, receiver));
+                    return false
+                },
+                Err(TryRecvError::Disconnected) => return false
+            },
+            None => return false
+        };
+        self.hint = lines.iter().filter_map(|l| l.best).collect();
+
+        let side = if self.game.turn { "White" } else { "Black" };
+        let candidates: Vec<String> = lines.iter().enumerate().map(|(i, line)| {
+            // Scores are in tenths of a pawn
+            let score = match line.mate_in() {
+                Some(n) => format!("mate in {}", n),
+                None => format!("{:+.1}", line.score as f32 / 10.0)
+            };
+            let first = line.pv.first().map_or(String::new(), |m| format!("{}-{}", m.0.to_algebraic(), m.1.to_algebraic()));
+            format!("{}. {} ({})", i + 1, first, score)
+        }).collect();
+        let text = format!("Hint for {} (depth {}): {}", side, lines.first().map_or(0, |l| l.depth), candidates.join(", "));
+        self.tell(text);
+        true
+    }
+
+    /// Returns the human player who acts on a key press: the one whose turn it is,
+    /// or the other one if an AI is to move. True is white.
+    fn human_side(&self) -> Option<bool> {
+        let human = |p: &Player| p.ptype() == PlayerType::Human;
+        if human(self.game.active_player()) {
+            Some(self.game.turn)
+        } else if human(if self.game.turn { &self.game.black_player } else { &self.game.white_player }) {
+            Some(!self.game.turn)
+        } else {
+            None
+        }
+    }
+
+    /// Tells the user how the game ended
+    fn print_outcome(&mut self) {
+        if let Some(outcome) = self.game.outcome {
+            self.tell(format!("Game over by {}: {}", outcome.reason, outcome.result));
+        }
+    }
+
+    /// Lets the human player resign
+    pub fn resign(&mut self) {
+        if let Some(white) = self.human_side() {
+            if self.game.resign(white) {
+                self.stop_pondering();
+                self.print_outcome();
+            }
+        }
+    }
+
+    /// Lets the human player offer a draw, an AI opponent answers before its next move
+    pub fn offer_draw(&mut self) {
+        if let Some(white) = self.human_side() {
+            if self.game.offer_draw(white) {
+                let side = if white { "White" } else { "Black" };
+                self.tell(format!("{} offers a draw", side));
+            }
+        }
+    }
+
+    /// Lets the human player accept ('accept' is true) or decline the opponent's draw offer
+    pub fn answer_draw(&mut self, accept: bool) {
+        let white = match self.game.draw_offer {
+            Some(Color::White) => false,
+            Some(Color::Black) => true,
+            None => return
+        };
+        if (if white { &self.game.white_player } else { &self.game.black_player }).ptype() != PlayerType::Human {
+            return
+        }
+
+        if accept && self.game.accept_draw(white) {
+            self.stop_pondering();
+            self.print_outcome();
+        } else if !accept && self.game.decline_draw(white) {
+            self.tell(String::from("Draw offer declined"));
+        }
+    }
+
+    /// Lets the player whose turn it is claim a draw by threefold repetition or the fifty move rule
+    pub fn claim_draw(&mut self) {
+        if self.game.claim_draw() {
+            self.stop_pondering();
+            self.print_outcome();
+        } else if !self.game.gameover {
+            self.tell(String::from("No draw to claim: the position did not occur three times and a capture or pawn \
+                                    move was made in the last fifty moves"));
+        }
+    }
+
+    /// Gives both players clocks with the time control, they start right away
+    pub fn set_time_control(&mut self, control: TimeControl) {
+        self.tell(format!("Time control: {}", control));
+        self.game.set_time_control(Some(control));
+    }
+
+    /// Ends the game if the player whose turn it is ran out of time, returns whether that happened
+    pub fn check_clock(&mut self) -> bool {
+        if self.editor.is_some() || !self.game.check_clock() {
+            return false
+        }
+
+        self.stop_pondering();
+        let side = if self.game.turn { "White" } else { "Black" };
+        match self.game.outcome {
+            Some(outcome) if outcome.result == "1/2-1/2" => {
+                self.tell(format!("{} ran out of time, but the opponent can not checkmate: draw", side))
+            },
+            Some(outcome) => self.tell(format!("{} ran out of time: {}", side, outcome.result)),
+            None => ()
+        }
+        true
+    }
+
+    /// Returns the remaining time of both players for display, None if the game is not timed
+    pub fn clock_text(&self) -> Option<String> {
+        self.game.clock.as_ref().map(|c| {
+            format!("White {}  |  Black {}", clock::format_time(c.remaining(true)), clock::format_time(c.remaining(false)))
+        })
+    }
+
+    /// Adds personalities from a file, they replace known ones with the same name
+    pub fn load_personalities(&mut self, path: &str) {
+        match Personality::load(path) {
+            Ok(list) => {
+                for p in list {
+                    self.personalities.retain(|known| known.name != p.name);
+                    self.personalities.push(p);
+                }
+            },
+            Err(e) => self.tell(format!("Could not load personalities from {}: {}", path, e))
+        }
+    }
+
+    /// Gives the AI of the white ('which' is true) or black player the personality with the name
+    pub fn set_personality(&mut self, which: bool, name: &str) {
+        match self.personalities.iter().find(|p| p.name == name).cloned() {
+            Some(p) => {
+                self.stop_pondering();
+                self.player_mut(which).set_personality(p);
+            },
+            None => self.tell(format!("Unknown personality: {}", name))
+        }
+    }
+
+    /// Switches the AI of the white ('which' is true) or black player to the next personality
+    pub fn cycle_personality(&mut self, which: bool) {
+        let current = self.player_mut(which).personality().name.clone();
+        let at = self.personalities.iter().position(|p| p.name == current);
+        let next = self.personalities[at.map_or(0, |i| (i + 1) % self.personalities.len())].clone();
+        self.stop_pondering();
+        self.player_mut(which).set_personality(next);
+        self.print_player(which);
+    }
+
+    /// Switches the smart AI of the white ('which' is true) or black player to the next difficulty
+    pub fn cycle_difficulty(&mut self, which: bool) {
+        let difficulty = self.player_mut(which).difficulty().next();
+        self.player_mut(which).set_difficulty(difficulty);
+        self.print_player(which);
+    }
+
+    /// Tells the user the type and difficulty of a player
+    fn print_player(&mut self, which: bool) {
+        let name = if which { "White" } else { "Black" };
+        let text = {
+            let player = self.player_mut(which);
+            match player.ptype() {
+                PlayerType::Smart => {
+                    format!("{} player: {:?} AI ({}, {})", name, player.ptype(), player.difficulty(), player.personality().name)
+                },
+                PlayerType::Dumb => format!("{} player: {:?} AI ({})", name, player.ptype(), player.personality().name),
+                PlayerType::Engine => {
+                    // The engine is locked while it looks for a move, the window must not wait for it
+                    let engine = player.engine().and_then(|e| e.try_lock().ok().map(|e| String::from(e.name())));
+                    format!("{} player: {:?} ({})", name, player.ptype(), engine.unwrap_or_default())
+                },
+                _ => format!("{} player: {:?}", name, player.ptype())
+            }
+        };
+        self.tell(text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Waits until the hint's analysis is done and returns what 'poll_hint' said then
+    fn wait_for_hint(system: &mut System) -> bool {
+        loop {
+            if system.poll_hint() {
+                return true
+            }
+            if !system.hint_pending() {
+                return false
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Selects both fields of a move like a click on each and plays it
+    fn play(system: &mut System, from: (u8, u8), to: (u8, u8)) -> bool {
+        system.set_selected(from);
+        system.set_selected(to);
+        system.check_ready_and_play()
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = ::std::env::temp_dir().join(format!("rust-chess-system-{}.pgn", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut system = System::new();
+        system.set_seed(42);
+        for &(from, to) in [((4, 1), (4, 3)), ((3, 6), (3, 4)), ((4, 3), (3, 4)), ((6, 7), (5, 5))].iter() {
+            assert!(play(&mut system, from, to));
+        }
+        system.save_game(path);
+
+        // The loaded game is the replayed one, the board a user interface shows included
+        let mut loaded = System::new();
+        loaded.load_game(path);
+        ::std::fs::remove_file(path).unwrap_or(());
+        assert_eq!(loaded.game().history, system.game().history);
+        assert_eq!(loaded.game().to_fen(), system.game().to_fen());
+        assert_eq!(format!("{}", loaded.game().board), format!("{}", system.game().board));
+        assert_eq!(loaded.seed(), 42);
+        assert!(loaded.take_messages().iter().any(|m| m.starts_with("Loaded game")));
+    }
+
+    #[test]
+    fn hints() {
+        let mut system = System::new();
+        system.set_hint_lines(2);
+        system.show_hint();
+        // The analysis runs in another thread, so the user interface can go on
+        assert!(system.hint_pending());
+        assert!(system.hint().is_none());
+
+        assert!(wait_for_hint(&mut system));
+        assert_eq!(system.hint_moves().len(), 2);
+        assert_eq!(system.hint(), system.hint_moves().first().cloned());
+        assert!(system.take_messages().iter().any(|m| m.starts_with("Hint for White")));
+
+        // A move makes the hint useless, also one that is still searched
+        assert!(play(&mut system, (4, 1), (4, 3)));
+        assert!(system.hint_moves().is_empty());
+        system.show_hint();
+        assert!(play(&mut system, (4, 6), (4, 4)));
+        assert!(!wait_for_hint(&mut system));
+        assert!(system.hint().is_none());
     }
 }
@@ -0,0 +1,230 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Plays the game in a terminal without Vulkan, e.g. over SSH: the board is drawn
+//! with Unicode figures and moves are typed in algebraic notation.
+
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::Duration;
+
+use chess::ChessGame;
+use chess::logic::Color;
+use chess::player::Player;
+use system::System;
+
+/// Number of moves of both players shown below the board
+const SHOWN_MOVES: usize = 8;
+
+/// Time to wait while the AI has to pause between two moves or looks for a hint
+const AI_WAIT: u64 = 20;
+
+const HELP: &'static str = "\
+Type a move like e4, Nf3, exd5, e8=Q, g1f3 or the drop N@f3, or one of the commands:
+  undo              take back the last move (and the AI's reply)
+  moves             show all moves of the game
+  hint              let the AI suggest moves
+  white / black     cycle the player between Human, Dumb AI, Smart AI and engine
+  level white|black cycle the difficulty of the Smart AI
+  resign, draw      resign or offer a draw
+  accept, decline   answer the opponent's draw offer
+  claim             claim a draw by repetition or the fifty move rule
+  save [file]       save the game as PGN (default game.pgn)
+  load [file]       continue a saved game with its AI seed (default game.pgn)
+  board, help, quit";
+
+/// Returns the moves with move numbers like '1. e4 e5 2. Nf3', starting at move 'first'
+fn numbered(moves: &[String], first: usize, white: bool) -> String {
+    let mut text = String::new();
+    let mut number = first;
+    let mut white = white;
+    for (i, san) in moves.iter().enumerate() {
+        if white {
+            text.push_str(&format!("{}. ", number));
+        } else if i == 0 {
+            text.push_str(&format!("{}... ", number));
+        }
+        text.push_str(san);
+        text.push(' ');
+        if !white {
+            number += 1;
+        }
+        white = !white;
+    }
+    text.trim_end().to_string()
+}
+
+/// Returns the moves of the game with move numbers, only the last 'shown' full moves if given
+fn move_text(system: &System, shown: Option<usize>) -> String {
+    let moves = system.move_list();
+    let start = ChessGame::from_fen(&system.game().start_fen).unwrap_or_else(|_| ChessGame::new());
+    // Plies are counted from white's move of the first full move
+    let offset = if start.turn { 0 } else { 1 };
+    let mut skip = shown.map_or(0, |n| moves.len().saturating_sub(2 * n));
+    if skip > 0 && (skip + offset) % 2 == 1 {
+        skip += 1;
+    }
+
+    let number = start.fullmove_number as usize + (skip + offset) / 2;
+    let text = numbered(&moves[skip..], number, (skip + offset) % 2 == 0);
+    if skip > 0 { format!("... {}", text) } else { text }
+}
+
+/// Prints the board, the last moves, the clocks and whose turn it is
+fn print_position(system: &System) {
+    let game = system.game();
+    let fog = system.fog();
+    match fog {
+        Some((viewer, _)) => print!("{}", game.board.fogged(viewer)),
+        None => print!("{:#}", game.board)
+    }
+    if game.variant().has_drops() {
+        let reserve = |p: &Player| -> String {
+            let letters: Vec<String> = p.reserve().iter().map(|f| f.fen_char(Color::White).to_string()).collect();
+            if letters.is_empty() { String::from("-") } else { letters.join(" ") }
+        };
+        println!("Reserves: white {}, black {}", reserve(&game.white_player), reserve(&game.black_player));
+    }
+
+    // The opponent's moves would give away hidden figures
+    if !game.history.is_empty() && fog.is_none() {
+        println!("Moves: {}", move_text(system, Some(SHOWN_MOVES)));
+    }
+    if let Some(text) = system.clock_text() {
+        println!("Clock: {}", text);
+    }
+
+    let side = if game.turn { "White" } else { "Black" };
+    let mut rest = game.clone();
+    match game.outcome {
+        Some(outcome) => println!("Game over by {}: {}", outcome.reason, outcome.result),
+        None if rest.possible_moves().is_empty() => {
+            if game.in_check() {
+                println!("{} is checkmated: {}", side, if game.turn { "0-1" } else { "1-0" })
+            } else {
+                println!("{} is stalemated: 1/2-1/2", side)
+            }
+        },
+        None if game.in_check() => println!("{} to move, check", side),
+        None => println!("{} to move", side)
+    }
+}
+
+/// Runs the game in the terminal until the input ends or the player quits
+pub fn run(mut system: System) {
+    println!("{}", HELP);
+    print_position(&system);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        if system.check_clock() {
+            print_position(&system);
+        }
+        for message in system.take_messages() {
+            println!("{}", message);
+        }
+
+        if system.ai_to_move() {
+            if system.execute_ai_turn() {
+                print_position(&system);
+            } else if system.ai_to_move() {
+                thread::sleep(Duration::from_millis(AI_WAIT));
+            }
+            continue
+        }
+
+        print!("> ");
+        io::stdout().flush().unwrap_or(());
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return
+        };
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => continue
+        };
+
+        match command {
+            "quit" | "exit" => return,
+            "help" => println!("{}", HELP),
+            "board" => print_position(&system),
+            "moves" if system.fog().is_some() => println!("The moves stay hidden until the game is over"),
+            "moves" => println!("{}", move_text(&system, None)),
+            "hint" => {
+                // The terminal has nothing else to do meanwhile
+                system.show_hint();
+                while system.hint_pending() && !system.poll_hint() {
+                    thread::sleep(Duration::from_millis(AI_WAIT));
+                }
+            },
+            "undo" => {
+                if system.undo() {
+                    print_position(&system);
+                } else {
+                    println!("There is no move to take back");
+                }
+            },
+            "white" | "black" => system.toggle_player_ai(command == "white"),
+            "level" => {
+                match words.next() {
+                    Some(side) if side == "white" || side == "black" => system.cycle_difficulty(side == "white"),
+                    _ => println!("level expects white or black")
+                }
+            },
+            "resign" => system.resign(),
+            "draw" => system.offer_draw(),
+            "accept" => system.answer_draw(true),
+            "decline" => system.answer_draw(false),
+            "claim" => system.claim_draw(),
+            "save" => system.save_game(words.next().unwrap_or(::SAVE_FILE)),
+            "load" => {
+                system.load_game(words.next().unwrap_or(::SAVE_FILE));
+                print_position(&system);
+            },
+            mv => {
+                match system.play_typed(mv) {
+                    Ok(_) => print_position(&system),
+                    Err(e) => println!("{}", e)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn move_numbers() {
+        assert_eq!(numbered(&moves("e4 e5 Nf3"), 1, true), "1. e4 e5 2. Nf3");
+        assert_eq!(numbered(&moves("e5 Nf3 Nc6"), 1, false), "1... e5 2. Nf3 Nc6");
+        assert_eq!(numbered(&moves("Bb5"), 3, true), "3. Bb5");
+        assert_eq!(numbered(&[], 1, true), "");
+    }
+}
@@ -0,0 +1,260 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Texel tuning of the evaluation weights: reads positions labelled with the
+//! result of their game and adjusts the weights until the evaluation predicts
+//! the results as well as possible. The weights are written to a file the
+//! game and the engines load with --eval or the EvalFile option.
+
+extern crate rust_chess;
+
+use std::fs::File;
+use std::io::Read;
+
+use rust_chess::chess;
+use chess::ChessGame;
+use chess::eval::{self, Eval, TERMS, TERM_NAMES};
+use chess::pgn;
+
+/// Plies at the start of a PGN game which are not used, they mostly come from opening books
+const DEFAULT_SKIP_PLIES: usize = 8;
+
+/// Most rounds over all weights
+const DEFAULT_ITERATIONS: usize = 100;
+
+/// A position reduced to its features with the result of its game from white's view
+struct Sample {
+    features: [i32; TERMS],
+    result: f64
+}
+
+/// Returns the result of a game from white's view
+fn result_value(result: &str) -> Option<f64> {
+    match result.trim_matches(|c| c == '"' || c == ';' || c == '[' || c == ']') {
+        "1-0" | "1.0" | "1" => Some(1.0),
+        "0-1" | "0.0" | "0" => Some(0.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        _ => None
+    }
+}
+
+/// Adds the position to the samples unless the king is in check, the evaluation
+/// only makes sense for quiet positions
+fn add_sample(samples: &mut Vec<Sample>, game: &ChessGame, result: f64) {
+    if !game.in_check() {
+        samples.push(Sample{ features: eval::features(game), result: result });
+    }
+}
+
+/// Reads EPD lines like '<fen> c9 "1-0";' or '<fen> [0.5]'
+fn read_epd(text: &str, samples: &mut Vec<Sample>) -> usize {
+    let mut skipped = 0;
+    for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 5 {
+            skipped += 1;
+            continue
+        }
+        let fen = parts[..4].join(" ");
+        let result = parts[4..].iter().filter_map(|p| result_value(p)).next();
+
+        match (ChessGame::from_fen(&fen), result) {
+            (Ok(game), Some(r)) => add_sample(samples, &game, r),
+            _ => skipped += 1
+        }
+    }
+    skipped
+}
+
+/// Reads all positions of the games in a PGN file after the first plies
+fn read_pgn(text: &str, skip_plies: usize, samples: &mut Vec<Sample>) -> usize {
+    let mut skipped = 0;
+    for record in pgn::read_games(text) {
+        let result = match result_value(&record.result) {
+            Some(r) => r,
+            None => {
+                skipped += 1;
+                continue
+            }
+        };
+        let mut game = match record.tag("FEN").map_or(Ok(ChessGame::new()), ChessGame::from_fen) {
+            Ok(game) => game,
+            Err(_) => {
+                skipped += 1;
+                continue
+            }
+        };
+
+        for (ply, san) in record.moves.iter().enumerate() {
+            // Our rules lack some moves, the rest of such a game can not be followed
+            match pgn::parse_san(&mut game, san) {
+                Some((from, to)) if game.play(from, to) => (),
+                _ => {
+                    skipped += 1;
+                    break
+                }
+            }
+            if ply + 1 >= skip_plies && !game.gameover {
+                add_sample(samples, &game, result);
+            }
+        }
+    }
+    skipped
+}
+
+/// Probability of a white win for a score in tenths of a pawn
+fn sigmoid(k: f64, score: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score as f64 / 40.0))
+}
+
+/// Mean squared difference between the results and the predictions of the evaluation
+fn error(samples: &[Sample], eval: &Eval, k: f64) -> f64 {
+    let sum: f64 = samples.iter()
+                          .map(|s| {
+                              let diff = s.result - sigmoid(k, eval.score(&s.features));
+                              diff * diff
+                          })
+                          .sum();
+    sum / samples.len() as f64
+}
+
+/// Finds the scaling of the scores which fits the current weights best
+fn fit_k(samples: &[Sample], eval: &Eval) -> f64 {
+    let mut best = (1.0, error(samples, eval, 1.0));
+    let mut step = 0.5;
+    // Narrow the search around the best value found so far
+    while step > 0.001 {
+        for &k in [best.0 - step, best.0 + step].iter() {
+            if k > 0.0 {
+                let e = error(samples, eval, k);
+                if e < best.1 {
+                    best = (k, e);
+                }
+            }
+        }
+        step /= 2.0;
+    }
+    best.0
+}
+
+/// Changes one weight at a time by one as long as the error gets smaller
+fn tune(samples: &[Sample], mut eval: Eval, k: f64, iterations: usize) -> Eval {
+    let mut best = error(samples, &eval, k);
+    println!("Start error {:.6}", best);
+
+    for iteration in 1..iterations + 1 {
+        let mut improved = false;
+        for i in 0..TERMS {
+            for &delta in [1, -1].iter() {
+                let mut candidate = eval;
+                candidate.weights[i] += delta;
+                let e = error(samples, &candidate, k);
+                if e < best {
+                    best = e;
+                    eval = candidate;
+                    improved = true;
+                    break
+                }
+            }
+        }
+        println!("Iteration {}: error {:.6}", iteration, best);
+        if !improved {
+            break
+        }
+    }
+    eval
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut epd = Vec::new();
+    let mut pgn = Vec::new();
+    let mut out = String::from("eval.txt");
+    let mut start = Eval::default();
+    let mut skip_plies = DEFAULT_SKIP_PLIES;
+    let mut iterations = DEFAULT_ITERATIONS;
+
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            // Files with labelled positions, both may be given several times
+            "--epd" => epd.extend(args.next()),
+            "--pgn" => pgn.extend(args.next()),
+            // File the tuned weights are written to
+            "--out" => {
+                match args.next() {
+                    Some(path) => out = path,
+                    None => println!("--out expects a file")
+                }
+            },
+            // Weights to start from instead of the built-in ones
+            "--start" => {
+                match args.next().map(|path| (Eval::load(&path), path)) {
+                    Some((Ok(eval), _)) => start = eval,
+                    Some((Err(e), path)) => println!("Could not load evaluation weights {}: {}", path, e),
+                    None => println!("--start expects a file with evaluation weights")
+                }
+            },
+            "--skip-plies" => {
+                match args.next().and_then(|v| v.parse::<usize>().ok()) {
+                    Some(n) => skip_plies = n,
+                    None => println!("--skip-plies expects a number of plies")
+                }
+            },
+            "--iterations" => {
+                match args.next().and_then(|v| v.parse::<usize>().ok()) {
+                    Some(n) => iterations = n,
+                    None => println!("--iterations expects a number")
+                }
+            },
+            _ => println!("Unknown argument: {}", arg)
+        }
+    }
+
+    let mut samples = Vec::new();
+    for (path, is_pgn) in epd.iter().map(|p| (p, false)).chain(pgn.iter().map(|p| (p, true))) {
+        let mut text = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut text)) {
+            println!("Could not read {}: {}", path, e);
+            continue
+        }
+        let before = samples.len();
+        let skipped = if is_pgn { read_pgn(&text, skip_plies, &mut samples) } else { read_epd(&text, &mut samples) };
+        println!("{}: {} positions, {} skipped", path, samples.len() - before, skipped);
+    }
+
+    if samples.is_empty() {
+        println!("No labelled positions, give some with --epd or --pgn");
+        std::process::exit(1);
+    }
+
+    let k = fit_k(&samples, &start);
+    println!("Tuning {} weights on {} positions with K = {:.3}", TERMS, samples.len(), k);
+    let tuned = tune(&samples, start, k, iterations);
+
+    for i in 0..TERMS {
+        println!("{:>14} {:>5} -> {:>5}", TERM_NAMES[i], start.weights[i], tuned.weights[i]);
+    }
+    match tuned.save(&out) {
+        Ok(()) => println!("Wrote weights to {}", out),
+        Err(e) => println!("Could not write {}: {}", out, e)
+    }
+}
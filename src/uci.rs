@@ -0,0 +1,369 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Engine speaking the Universal Chess Interface over stdin and stdout,
+//! so the AI can be used by other chess GUIs. It does not touch Vulkan.
+
+extern crate rust_chess;
+
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rust_chess::chess;
+use chess::ChessGame;
+use chess::ai::{self, Difficulty, Explanation};
+use chess::eval::Eval;
+use chess::personality::Personality;
+use chess::variant::{self, Variant};
+use chess::logic::{parse_move, format_move, Position};
+use chess::search::{SearchContext, SearchLimits, SearchResult, TranspositionTable, DEFAULT_HASH_MB};
+
+/// Largest transposition table we allow in megabytes
+const MAX_HASH_MB: usize = 4096;
+
+/// Most threads we search with
+const MAX_THREADS: usize = 256;
+
+/// State of the engine between commands
+struct Engine {
+    // None after a 'position' command failed, then 'go' answers with the null move
+    game: Option<ChessGame>,
+    difficulty: Difficulty,
+    tt: Arc<TranspositionTable>,
+    threads: usize,
+    eval: Eval,
+    personality: Personality,
+    variant: Arc<Variant>,
+    // Context and thread of the running search
+    search: Option<(SearchContext, JoinHandle<()>)>
+}
+
+impl Engine {
+    fn new() -> Self {
+        Engine{ game: Some(ChessGame::new()),
+                difficulty: Difficulty::Strongest,
+                tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+                threads: 1,
+                eval: Eval::default(),
+                personality: Personality::default(),
+                variant: Arc::new(variant::Standard),
+                search: None }
+    }
+
+    /// Handles one command, returns false if the engine should quit
+    fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"uci") => {
+                println!("id name RustChess");
+                println!("id author Franziska Becker, Rene Warnking");
+                println!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MB, MAX_HASH_MB);
+                println!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS);
+                println!("option name EvalFile type string default <empty>");
+                let names: Vec<String> = Personality::builtin().into_iter().map(|p| format!("var {}", p.name)).collect();
+                println!("option name Personality type combo default {} {}", Personality::default().name, names.join(" "));
+                println!("option name Difficulty type combo default {} var beginner var novice var intermediate \
+                          var advanced var expert var strongest", Difficulty::Strongest);
+                let variants: Vec<String> = variant::NAMES.iter().map(|n| format!("var {}", n)).collect();
+                println!("option name UCI_Variant type combo default standard {}", variants.join(" "));
+                println!("uciok");
+            },
+            Some(&"isready") => println!("readyok"),
+            Some(&"ucinewgame") => {
+                self.stop();
+                self.tt.clear();
+                self.game = Some(ChessGame::from_fen_variant(self.variant.start_fen(), self.variant.clone())
+                                            .unwrap_or_else(|_| ChessGame::new()));
+            },
+            Some(&"setoption") => {
+                self.stop();
+                self.set_option(&tokens[1..]);
+            },
+            Some(&"position") => {
+                self.stop();
+                // Searching the previous position would answer with a move for another game
+                self.game = match self.set_position(&tokens[1..]) {
+                    Ok(game) => Some(game),
+                    Err(e) => {
+                        println!("info string {}", e);
+                        None
+                    }
+                };
+            },
+            Some(&"go") => {
+                self.stop();
+                self.go(&tokens[1..]);
+            },
+            // We do not offer pondering, but if a GUI ponders anyway the search is over at once
+            Some(&"stop") | Some(&"ponderhit") => self.stop(),
+            Some(&"quit") => {
+                self.stop();
+                return false
+            },
+            Some(other) => println!("info string Unknown command '{}'", other),
+            None => ()
+        }
+        true
+    }
+
+    /// Handles 'setoption name <name> value <value>'
+    fn set_option(&mut self, tokens: &[&str]) {
+        let value_at = tokens.iter().position(|&t| t == "value").unwrap_or(tokens.len());
+        let name = tokens[..value_at].iter().skip(1).cloned().collect::<Vec<_>>().join(" ").to_lowercase();
+        // Paths keep their case
+        let raw = tokens.get(value_at + 1..).map_or(String::new(), |v| v.join(" "));
+        let value = raw.to_lowercase();
+
+        match name.as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(mb) => self.tt = Arc::new(TranspositionTable::new(mb.max(1).min(MAX_HASH_MB))),
+                Err(_) => println!("info string Invalid hash size '{}'", value)
+            },
+            "threads" => match value.parse::<usize>() {
+                Ok(n) => self.threads = n.max(1).min(MAX_THREADS),
+                Err(_) => println!("info string Invalid thread count '{}'", value)
+            },
+            "difficulty" => match Difficulty::from_name(&value) {
+                Some(d) => self.difficulty = d,
+                None => println!("info string Unknown difficulty '{}'", value)
+            },
+            "personality" => match Personality::find(&[], &value) {
+                Some(p) => self.personality = p,
+                None => println!("info string Unknown personality '{}'", value)
+            },
+            "uci_variant" => match variant::find(&value) {
+                Some(v) => self.variant = v,
+                None => println!("info string Unknown variant '{}'", value)
+            },
+            "evalfile" => match Eval::load(&raw) {
+                Ok(eval) => self.eval = eval,
+                Err(e) => println!("info string Could not load evaluation weights '{}': {}", raw, e)
+            },
+            _ => println!("info string Unknown option '{}'", name)
+        }
+    }
+
+    /// Returns the game of 'position [startpos | fen <fen>] [moves <move>...]'
+    fn set_position(&self, tokens: &[&str]) -> Result<ChessGame, String> {
+        let moves_at = tokens.iter().position(|&t| t == "moves").unwrap_or(tokens.len());
+        let mut game = match tokens.first() {
+            Some(&"startpos") => try!(ChessGame::from_fen_variant(self.variant.start_fen(), self.variant.clone())),
+            Some(&"fen") => try!(ChessGame::from_fen_variant(&tokens[1..moves_at].join(" "), self.variant.clone())),
+            _ => return Err(String::from("position needs 'startpos' or 'fen'"))
+        };
+
+        for mv in tokens.iter().skip(moves_at + 1) {
+            match parse_move(mv) {
+                Some((from, to)) if game.play(from, to) => (),
+                _ => return Err(format!("Illegal move '{}'", mv))
+            }
+        }
+        Ok(game)
+    }
+
+    /// Returns the limits of a 'go' command and whether it searches until 'stop'. We do not
+    /// offer pondering, but a ponder search has to wait for 'stop' or 'ponderhit' as well.
+    fn parse_go(&self, tokens: &[&str]) -> (SearchLimits, bool) {
+        let mut limits = SearchLimits::infinite();
+        let mut clock = (None, None);
+        let mut increment = (Duration::from_millis(0), Duration::from_millis(0));
+        let mut moves_to_go = None;
+        let mut infinite = tokens.is_empty();
+
+        let mut iter = tokens.iter();
+        while let Some(&token) = iter.next() {
+            if token == "infinite" || token == "ponder" {
+                infinite = true;
+                continue
+            }
+            let value = match iter.next().and_then(|v| v.parse::<u64>().ok()) {
+                Some(v) => v,
+                None => {
+                    println!("info string '{}' needs a number", token);
+                    continue
+                }
+            };
+            let ms = Duration::from_millis(value);
+            match token {
+                "depth" => limits.depth = Some(value.min(u8::max_value() as u64) as u8),
+                "nodes" => limits.nodes = Some(value),
+                "movetime" => limits.movetime = Some(ms),
+                "wtime" => clock.0 = Some(ms),
+                "btime" => clock.1 = Some(ms),
+                "winc" => increment.0 = ms,
+                "binc" => increment.1 = ms,
+                "movestogo" => moves_to_go = Some(value as u32),
+                _ => println!("info string Unknown go parameter '{}'", token)
+            }
+        }
+
+        let white = self.game.as_ref().map_or(true, |game| game.turn);
+        let (remaining, inc) = if white { (clock.0, increment.0) } else { (clock.1, increment.1) };
+        if let (Some(remaining), None) = (remaining, limits.movetime) {
+            limits.movetime = SearchLimits::from_clock(remaining, inc, moves_to_go).movetime;
+        }
+        (limits, infinite)
+    }
+
+    /// Handles 'go' and starts searching in another thread
+    fn go(&mut self, tokens: &[&str]) {
+        let game = match self.game.clone() {
+            Some(game) => game,
+            None => {
+                println!("bestmove 0000");
+                return
+            }
+        };
+        let (limits, infinite) = self.parse_go(tokens);
+        let context = SearchContext{ threads: self.threads,
+                                     eval: self.personality.apply(&self.eval),
+                                     contempt: self.personality.contempt,
+                                     .. SearchContext::with_table(self.tt.clone()) };
+        let thread_context = context.clone();
+        let difficulty = self.difficulty;
+
+        let handle = thread::spawn(move || {
+            let mut reported = None;
+            let result = ai::smart_search(&game, difficulty, limits, &thread_context, |r| {
+                print_info(&game, r);
+                reported = Some(r.clone());
+            });
+
+            // With 'go infinite' the best move may only be sent after 'stop'
+            while infinite && !thread_context.stopped() {
+                thread::sleep(Duration::from_millis(5));
+            }
+            // Also report an unfinished iteration that changed the result
+            if reported.as_ref() != Some(&result) {
+                print_info(&game, &result);
+            }
+            println!("info string {}", Explanation::new(&game, &result, &thread_context.eval));
+            print_best_move(&game, &result);
+        });
+        self.search = Some((context, handle));
+    }
+
+    /// Stops the running search and waits until it sent its best move
+    fn stop(&mut self) {
+        if let Some((context, handle)) = self.search.take() {
+            context.stop();
+            handle.join().unwrap_or(());
+        }
+    }
+}
+
+/// Returns the moves of a line in long algebraic notation, starting at the game's position
+fn line_to_string(game: &ChessGame, line: &[(Position, Position)]) -> Vec<String> {
+    let mut game = game.clone();
+    line.iter()
+        .map(|&(from, to)| {
+            let s = format_move(&game.board, from, to);
+            game.play(from, to);
+            s
+        })
+        .collect()
+}
+
+/// Sends an 'info' line for a search result
+fn print_info(game: &ChessGame, result: &SearchResult) {
+    if result.depth == 0 {
+        return
+    }
+
+    // Our scores are in tenths of a pawn
+    let score = match result.mate_in() {
+        Some(n) => format!("mate {}", n),
+        None => format!("cp {}", result.score * 10)
+    };
+    let ms = result.time.as_secs() * 1000 + (result.time.subsec_nanos() / 1000000) as u64;
+
+    println!("info depth {} score {} nodes {} time {} nps {} pv {}", result.depth, score, result.nodes, ms,
+             result.nodes * 1000 / ms.max(1), line_to_string(game, &result.pv).join(" "));
+}
+
+/// Sends the 'bestmove' line, without a move to ponder on as we do not offer pondering
+fn print_best_move(game: &ChessGame, result: &SearchResult) {
+    match result.best {
+        Some(best) => println!("bestmove {}", format_move(&game.board, best.0, best.1)),
+        None => println!("bestmove 0000")
+    }
+}
+
+fn main() {
+    let mut engine = Engine::new();
+    let stdin = io::stdin();
+
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) => if !engine.handle(line.trim()) {
+                return
+            },
+            Err(_) => break
+        }
+    }
+    engine.stop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn go_limits() {
+        let engine = Engine::new();
+        assert_eq!(engine.parse_go(&[]), (SearchLimits::infinite(), true));
+        assert_eq!(engine.parse_go(&["depth", "5", "nodes", "1000"]).0,
+                   SearchLimits{ depth: Some(5), nodes: Some(1000), movetime: None });
+        assert_eq!(engine.parse_go(&["movetime", "300"]).0.movetime, Some(Duration::from_millis(300)));
+        assert_eq!(engine.parse_go(&["wtime", "60000", "btime", "1000"]).0.movetime,
+                   SearchLimits::from_clock(Duration::from_millis(60000), Duration::from_millis(0), None).movetime);
+    }
+
+    // 'ponder' has no value, so the limit after it must still be read, and the search
+    // waits for 'stop' or 'ponderhit'
+    #[test]
+    fn go_ponder() {
+        let engine = Engine::new();
+        assert_eq!(engine.parse_go(&["ponder", "depth", "3"]), (SearchLimits::depth(3), true));
+        assert_eq!(engine.parse_go(&["ponder", "infinite"]), (SearchLimits::infinite(), true));
+    }
+
+    #[test]
+    fn positions() {
+        let mut engine = Engine::new();
+        engine.handle("position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1");
+        assert_eq!(engine.game.as_ref().unwrap().to_fen(),
+                   "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4");
+        engine.handle("position fen 4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2 moves e5d6");
+        assert_eq!(engine.game.as_ref().unwrap().to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 2");
+
+        // After an illegal move the engine has no position to search
+        engine.handle("position startpos moves e2e4 e7e5 e1g1");
+        assert!(engine.game.is_none());
+        engine.handle("position fen 4k3/4P3/8/8/8/8/8/4K3 w - - 0 1 moves e7e8n");
+        assert!(engine.game.is_none());
+        engine.handle("ucinewgame");
+        assert!(engine.game.is_some());
+    }
+}
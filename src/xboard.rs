@@ -0,0 +1,489 @@
+// MIT License
+//
+// Copyright (c) 2017 Franziska Becker, René Warking
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Engine speaking the Chess Engine Communication Protocol of XBoard/WinBoard
+//! over stdin and stdout. Like the UCI engine it does not touch Vulkan.
+
+extern crate rust_chess;
+
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use rust_chess::chess;
+use chess::ChessGame;
+use chess::ai::{self, Difficulty};
+use chess::logic::{parse_move, format_move, Position};
+use chess::search::{SearchContext, SearchLimits, SearchResult, TranspositionTable, DEFAULT_HASH_MB};
+
+/// Input of the main loop, either a command from the GUI or the move of a finished search
+enum Event {
+    Command(String),
+    // Number of the search and its result
+    Searched(u32, SearchResult)
+}
+
+/// Time control set with 'level' or 'st'
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum TimeControl {
+    // Moves per period (0 for the whole game), base time and increment
+    Level(u32, Duration, Duration),
+    // Fixed time per move
+    PerMove(Duration)
+}
+
+impl Default for TimeControl {
+    /// Forty moves in five minutes until the GUI tells otherwise
+    fn default() -> Self {
+        TimeControl::Level(40, Duration::from_secs(300), Duration::from_secs(0))
+    }
+}
+
+/// State of the engine between commands
+struct Engine {
+    game: ChessGame,
+    // Side the engine plays, None in force mode
+    engine_white: Option<bool>,
+    time_control: TimeControl,
+    // Time left on our clock as told by 'time'
+    clock: Option<Duration>,
+    max_depth: Option<u8>,
+    post: bool,
+    threads: usize,
+    tt: Arc<TranspositionTable>,
+    // Context and number of the running search, results of older searches are ignored
+    search: Option<(SearchContext, u32)>,
+    searches: u32,
+    events: Sender<Event>
+}
+
+impl Engine {
+    fn new(events: Sender<Event>) -> Self {
+        Engine{ game: ChessGame::new(),
+                engine_white: Some(false),
+                time_control: TimeControl::default(),
+                clock: None,
+                max_depth: None,
+                post: false,
+                threads: 1,
+                tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+                search: None,
+                searches: 0,
+                events: events }
+    }
+
+    /// Handles one command, returns false if the engine should quit
+    fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let args = if tokens.len() > 1 { &tokens[1..] } else { &[] };
+
+        match tokens.first().cloned().unwrap_or("") {
+            "xboard" | "accepted" | "rejected" | "random" | "computer" | "hard" | "easy" | "otim" | "name" | "" => (),
+            "protover" => {
+                println!("feature myname=\"RustChess\" usermove=1 setboard=1 ping=1 playother=1 colors=0 \
+                          analyze=0 sigint=0 sigterm=0 reuse=1 smp=1 variants=\"normal\"");
+                println!("feature done=1");
+            },
+            "new" => {
+                self.abort_search();
+                self.tt.clear();
+                self.game = ChessGame::new();
+                self.engine_white = Some(false);
+                // The clocks and time control of the last game do not carry over
+                self.time_control = TimeControl::default();
+                self.clock = None;
+                self.max_depth = None;
+            },
+            "setboard" => {
+                self.abort_search();
+                match ChessGame::from_fen(&args.join(" ")) {
+                    Ok(game) => self.game = game,
+                    Err(e) => println!("tellusererror Illegal position: {}", e)
+                }
+            },
+            "force" => {
+                self.abort_search();
+                self.engine_white = None;
+            },
+            "go" => {
+                self.engine_white = Some(self.game.turn);
+                self.think();
+            },
+            "playother" => {
+                self.engine_white = Some(!self.game.turn);
+            },
+            "usermove" => self.user_move(args.first().cloned().unwrap_or("")),
+            "?" => {
+                // Move now with what the search found so far
+                if let Some((ref context, _)) = self.search {
+                    context.stop();
+                }
+            },
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "result" => {
+                self.abort_search();
+                self.engine_white = None;
+            },
+            "level" => self.set_level(args),
+            "st" => match args.first().and_then(|s| s.parse::<u64>().ok()) {
+                Some(s) => self.time_control = TimeControl::PerMove(Duration::from_secs(s)),
+                None => println!("Error (st needs a number of seconds): {}", line)
+            },
+            "sd" => match args.first().and_then(|s| s.parse::<u8>().ok()) {
+                Some(d) => self.max_depth = Some(d),
+                None => println!("Error (sd needs a depth): {}", line)
+            },
+            "time" => match args.first().and_then(|s| s.parse::<u64>().ok()) {
+                // The time is given in centiseconds
+                Some(cs) => self.clock = Some(Duration::from_millis(cs * 10)),
+                None => println!("Error (time needs a number of centiseconds): {}", line)
+            },
+            "cores" => match args.first().and_then(|s| s.parse::<usize>().ok()) {
+                Some(n) => self.threads = n.max(1),
+                None => println!("Error (cores needs a number): {}", line)
+            },
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "ping" => println!("pong {}", args.first().cloned().unwrap_or("")),
+            "quit" => {
+                self.abort_search();
+                return false
+            },
+            other => {
+                // Old GUIs send moves without 'usermove'
+                if parse_move(other).is_some() {
+                    self.user_move(other);
+                } else {
+                    println!("Error (unknown command): {}", other);
+                }
+            }
+        }
+        true
+    }
+
+    /// Handles 'level <moves per period> <minutes[:seconds]> <increment seconds>'
+    fn set_level(&mut self, args: &[&str]) {
+        if args.len() != 3 {
+            println!("Error (level needs three values): level {}", args.join(" "));
+            return
+        }
+
+        let moves = args[0].parse::<u32>().ok();
+        let base = {
+            let mut parts = args[1].splitn(2, ':').map(|p| p.parse::<u64>().ok());
+            match (parts.next().and_then(|m| m), parts.next()) {
+                (Some(m), None) => Some(m * 60),
+                (Some(m), Some(Some(s))) => Some(m * 60 + s),
+                _ => None
+            }
+        };
+        let increment = args[2].parse::<f64>().ok().filter(|i| *i >= 0.0);
+
+        match (moves, base, increment) {
+            (Some(moves), Some(base), Some(inc)) => {
+                self.time_control = TimeControl::Level(moves, Duration::from_secs(base),
+                                                       Duration::from_millis((inc * 1000.0) as u64));
+            },
+            _ => println!("Error (invalid time control): level {}", args.join(" "))
+        }
+    }
+
+    /// Plays the opponent's move and starts thinking if it is our turn then
+    fn user_move(&mut self, mv: &str) {
+        self.abort_search();
+        match parse_move(mv) {
+            Some((from, to)) if self.game.play(from, to) => {
+                if !self.announce_result() {
+                    self.think();
+                }
+            },
+            _ => println!("Illegal move: {}", mv)
+        }
+    }
+
+    /// Takes back 'count' moves
+    fn take_back(&mut self, count: usize) {
+        self.abort_search();
+        for _ in 0..count {
+            self.game.undo();
+        }
+    }
+
+    /// Returns the limits for our next move from the time control
+    fn limits(&self) -> SearchLimits {
+        let mut limits = match self.time_control {
+            TimeControl::PerMove(t) => SearchLimits::movetime(t),
+            TimeControl::Level(moves, base, inc) => {
+                let moves_to_go = if moves > 0 {
+                    let played = (self.game.fullmove_number - 1) % moves;
+                    Some(moves - played)
+                } else {
+                    None
+                };
+                SearchLimits::from_clock(self.clock.unwrap_or(base), inc, moves_to_go)
+            }
+        };
+        limits.depth = self.max_depth;
+        limits
+    }
+
+    /// Starts a search in another thread if it is the engine's turn
+    fn think(&mut self) {
+        if self.engine_white != Some(self.game.turn) || self.game.gameover || self.search.is_some() {
+            return
+        }
+
+        self.searches += 1;
+        let id = self.searches;
+        let context = SearchContext{ threads: self.threads, .. SearchContext::with_table(self.tt.clone()) };
+        let thread_context = context.clone();
+        let game = self.game.clone();
+        let limits = self.limits();
+        let post = self.post;
+        let events = self.events.clone();
+
+        thread::spawn(move || {
+            let result = ai::smart_search(&game, Difficulty::Strongest, limits, &thread_context, |r| {
+                if post {
+                    print_thinking(&game, r);
+                }
+            });
+            events.send(Event::Searched(id, result)).unwrap_or(());
+        });
+        self.search = Some((context, id));
+    }
+
+    /// Stops the running search, its move will not be played
+    fn abort_search(&mut self) {
+        if let Some((context, _)) = self.search.take() {
+            context.stop();
+        }
+    }
+
+    /// Plays the move of a finished search if it belongs to the current search
+    fn searched(&mut self, id: u32, result: SearchResult) {
+        match self.search {
+            Some((_, current)) if current == id => self.search = None,
+            _ => return
+        }
+
+        if let Some((from, to)) = result.best {
+            let mv = format_move(&self.game.board, from, to);
+            if self.game.play(from, to) {
+                println!("move {}", mv);
+                self.announce_result();
+            }
+        }
+    }
+
+    /// Sends the result if the game is over and returns whether it is, a draw by
+    /// repetition or the fifty move rule is claimed for whoever may claim it
+    fn announce_result(&mut self) -> bool {
+        let (result, reason) = if self.game.possible_moves().is_empty() {
+            match (self.game.in_check(), self.game.turn) {
+                (true, true) => ("0-1", "Black mates"),
+                (true, false) => ("1-0", "White mates"),
+                (false, _) => ("1/2-1/2", "Stalemate")
+            }
+        } else if let Some(reason) = self.game.draw_claim() {
+            ("1/2-1/2", reason)
+        } else {
+            return false
+        };
+
+        self.game.finish(result, reason);
+        println!("{} {{{}}}", result, reason);
+        true
+    }
+}
+
+/// Sends a thinking output line: depth, score in centipawns, time in centiseconds, nodes and PV
+fn print_thinking(game: &ChessGame, result: &SearchResult) {
+    let mut replay = game.clone();
+    let pv: Vec<String> = result.pv
+                                .iter()
+                                .map(|&(from, to): &(Position, Position)| {
+                                    let s = format_move(&replay.board, from, to);
+                                    replay.play(from, to);
+                                    s
+                                })
+                                .collect();
+    let cs = result.time.as_secs() * 100 + (result.time.subsec_nanos() / 10000000) as u64;
+
+    // Our scores are in tenths of a pawn
+    println!("{} {} {} {} {}", result.depth, result.score * 10, cs, result.nodes, pv.join(" "));
+}
+
+fn main() {
+    let (sender, receiver) = mpsc::channel();
+    let mut engine = Engine::new(sender.clone());
+
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => if sender.send(Event::Command(line)).is_err() {
+                    break
+                },
+                Err(_) => break
+            }
+        }
+        sender.send(Event::Command(String::from("quit"))).unwrap_or(());
+    });
+
+    for event in receiver.iter() {
+        match event {
+            Event::Command(line) => if !engine.handle(line.trim()) {
+                return
+            },
+            Event::Searched(id, result) => engine.searched(id, result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver};
+
+    #[test]
+    fn new_resets_time_control() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut engine = Engine::new(sender);
+        engine.handle("level 0 2:30 5");
+        engine.handle("time 1000");
+        engine.handle("sd 4");
+        assert_eq!(engine.time_control, TimeControl::Level(0, Duration::from_secs(150), Duration::from_secs(5)));
+        assert_eq!(engine.clock, Some(Duration::from_secs(10)));
+
+        engine.handle("new");
+        assert_eq!(engine.time_control, TimeControl::default());
+        assert_eq!(engine.clock, None);
+        assert_eq!(engine.max_depth, None);
+        assert_eq!(engine.limits(), SearchLimits::from_clock(Duration::from_secs(300), Duration::from_secs(0), Some(40)));
+
+        engine.handle("st 3");
+        engine.handle("new");
+        assert_eq!(engine.time_control, TimeControl::default());
+    }
+
+    /// An engine in force mode, so it only plays the moves it is told
+    fn forced() -> (Engine, Receiver<Event>) {
+        let (sender, receiver) = mpsc::channel();
+        let mut engine = Engine::new(sender);
+        engine.handle("force");
+        (engine, receiver)
+    }
+
+    fn outcome(engine: &Engine) -> Option<(&'static str, &'static str)> {
+        engine.game.outcome.as_ref().map(|o| (o.result, o.reason))
+    }
+
+    #[test]
+    fn user_moves() {
+        let (mut engine, _receiver) = forced();
+        engine.handle("usermove e2e4");
+        engine.handle("e7e5");
+        engine.handle("usermove e2e4");
+        assert_eq!(engine.game.history.len(), 2);
+
+        engine.handle("setboard r3k2r/8/8/8/3p4/8/4P3/R3K2R w KQkq - 0 1");
+        engine.handle("usermove e2e4");
+        engine.handle("usermove d4e3");
+        engine.handle("usermove e1g1");
+        engine.handle("usermove e8c8");
+        assert_eq!(engine.game.to_fen(), "2kr3r/8/8/8/8/4p3/8/R4RK1 w - - 2 3");
+        assert!(engine.search.is_none());
+    }
+
+    #[test]
+    fn go_and_force() {
+        let (mut engine, receiver) = forced();
+        engine.handle("sd 1");
+        engine.handle("usermove e2e4");
+        engine.handle("go");
+        assert_eq!(engine.engine_white, Some(false));
+        match receiver.recv().unwrap() {
+            Event::Searched(id, result) => engine.searched(id, result),
+            Event::Command(_) => unreachable!()
+        }
+        assert_eq!(engine.game.history.len(), 2);
+        assert!(engine.search.is_none());
+
+        // Our move was played, the engine now answers the opponent's move
+        engine.handle("usermove d2d4");
+        assert!(engine.search.is_some());
+        engine.handle("force");
+        assert!(engine.search.is_none());
+        assert_eq!(engine.engine_white, None);
+    }
+
+    #[test]
+    fn undo_and_remove() {
+        let (mut engine, _receiver) = forced();
+        for mv in ["e2e4", "e7e5", "g1f3", "b8c6"].iter() {
+            engine.handle(&format!("usermove {}", mv));
+        }
+        engine.handle("undo");
+        assert_eq!(engine.game.history.len(), 3);
+        engine.handle("remove");
+        assert_eq!(engine.game.history.len(), 1);
+        assert!(!engine.game.turn);
+    }
+
+    #[test]
+    fn set_board() {
+        let (mut engine, _receiver) = forced();
+        engine.handle("setboard 4k3/8/8/8/8/8/4P3/4K3 b - - 3 20");
+        assert_eq!(engine.game.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 b - - 3 20");
+        // An illegal position keeps the old one
+        engine.handle("setboard 4k3/8/8/8 w - - 0 1");
+        assert_eq!(engine.game.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 b - - 3 20");
+    }
+
+    #[test]
+    fn results() {
+        let (mut engine, _receiver) = forced();
+        engine.handle("setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        engine.handle("usermove a1a8");
+        assert_eq!(outcome(&engine), Some(("1-0", "White mates")));
+
+        engine.handle("setboard 7k/8/6Q1/8/8/8/8/K7 w - - 0 1");
+        engine.handle("usermove g6f7");
+        assert_eq!(outcome(&engine), Some(("1/2-1/2", "Stalemate")));
+
+        engine.handle("setboard 4k3/8/8/8/8/8/8/4K2R w - - 99 70");
+        engine.handle("usermove h1h2");
+        assert_eq!(outcome(&engine), Some(("1/2-1/2", "fifty move rule")));
+
+        engine.handle("setboard 4k3/8/8/8/8/8/8/4K2R w - - 0 1");
+        for mv in ["e1d1", "e8d8", "d1e1", "d8e8", "e1d1", "e8d8", "d1e1"].iter() {
+            engine.handle(&format!("usermove {}", mv));
+            assert_eq!(outcome(&engine), None);
+        }
+        engine.handle("usermove d8e8");
+        assert_eq!(outcome(&engine), Some(("1/2-1/2", "threefold repetition")));
+    }
+}